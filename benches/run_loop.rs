@@ -0,0 +1,107 @@
+//! measures how fast the CPU runs a tight instruction loop (the autograder's hot path)
+//!
+//! run with `cargo bench`, the instructions per second are printed for each program
+
+use mips_emu_wasm::cpu::CPU;
+use mips_emu_wasm::program::Program;
+use std::time::Instant;
+
+/// arithmetic, a load/store pair and a branch per iteration
+const ARITHMETIC_LOOP: &str = r#"
+    li $t0, 0
+    li $t1, 100000
+    li $s0, 0x10010000
+loop:
+    addu $t2, $t2, $t0
+    xor $t3, $t2, $t1
+    sll $t4, $t3, 2
+    sw $t4, 0($s0)
+    lw $t5, 0($s0)
+    addi $t0, $t0, 1
+    bne $t0, $t1, loop
+"#;
+
+/// recursive calls that push and pop stack frames
+const RECURSIVE_CALLS: &str = r#"
+    li $s0, 0
+    li $s1, 2000
+outer:
+    li $a0, 20
+    jal sum
+    addu $s2, $s2, $v0
+    addi $s0, $s0, 1
+    bne $s0, $s1, outer
+    j end
+sum:
+    addi $sp, $sp, -8
+    sw $ra, 4($sp)
+    sw $a0, 0($sp)
+    li $v0, 0
+    beq $a0, $zero, sum_done
+    addi $a0, $a0, -1
+    jal sum
+    lw $a0, 0($sp)
+    addu $v0, $v0, $a0
+sum_done:
+    lw $ra, 4($sp)
+    addi $sp, $sp, 8
+    jr $ra
+end:
+"#;
+
+/// word and byte accesses sweeping a 4 KiB heap buffer
+const MEMORY_SWEEP: &str = r#"
+    li $s0, 0x10010000
+    li $s1, 200
+pass:
+    li $t0, 0
+fill:
+    addu $t1, $s0, $t0
+    sw $t0, 0($t1)
+    lw $t2, 0($t1)
+    lbu $t3, 1($t1)
+    addu $t4, $t4, $t3
+    addi $t0, $t0, 4
+    slti $t5, $t0, 4096
+    bne $t5, $zero, fill
+    addi $s1, $s1, -1
+    bne $s1, $zero, pass
+"#;
+
+/// number of instructions the program executes (counted once by stepping through it)
+fn count_instructions(source: &str) -> u64 {
+    let mut cpu = CPU::new();
+    let program = Program::parse(source, &mut cpu.memory).unwrap();
+    cpu.load_program(program);
+
+    let mut count = 0;
+    while cpu.next().is_ok() {
+        count += 1;
+    }
+    count
+}
+
+fn bench(name: &str, source: &str, repeats: u32) {
+    let executed = count_instructions(source);
+    let mut best = f64::MAX;
+
+    for _ in 0..repeats {
+        let mut cpu = CPU::new();
+        cpu.max_instructions = 0;
+
+        let start = Instant::now();
+        cpu.run_input(source).unwrap();
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+
+    println!(
+        "{:<16} {:>10} instructions  {:>8.2} ms  {:>7.2} M instructions/s",
+        name, executed, best * 1000.0, executed as f64 / best / 1e6
+    );
+}
+
+fn main() {
+    bench("arithmetic_loop", ARITHMETIC_LOOP, 5);
+    bench("recursive_calls", RECURSIVE_CALLS, 5);
+    bench("memory_sweep", MEMORY_SWEEP, 5);
+}
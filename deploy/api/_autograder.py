@@ -2,6 +2,7 @@ import sys, os
 sys.path.insert(0, os.path.dirname(__file__))
 
 import os
+import re
 import json
 import logging
 import requests
@@ -28,6 +29,24 @@ def _emulator_url():
     return f"{protocol}://{vercel_url}/api/emulator"
 
 
+REGISTER_NAMES = [
+    '$zero', '$at', '$v0', '$v1', '$a0', '$a1', '$a2', '$a3',
+    '$t0', '$t1', '$t2', '$t3', '$t4', '$t5', '$t6', '$t7',
+    '$s0', '$s1', '$s2', '$s3', '$s4', '$s5', '$s6', '$s7',
+    '$t8', '$t9', '$k0', '$k1', '$gp', '$sp', '$fp', '$ra',
+]
+
+def _canonical_register(name):
+    """Map numeric names and aliases (`$8`, `$s8`) to the names the emulator reports."""
+    name = str(name).strip()
+    if name == '$s8':
+        return '$fp'
+    number = name[1:]
+    if name.startswith('$') and number.isdigit() and int(number) < len(REGISTER_NAMES):
+        return REGISTER_NAMES[int(number)]
+    return name
+
+
 def _strip_expected_for_student(results_list):
     sanitized = []
     for test in results_list:
@@ -42,7 +61,9 @@ def _strip_expected_for_student(results_list):
 
 
 def run_mips_via_wasm(source_code, initial_registers=None,
-                      initial_memory=None, check_memory=None, use_isolation=False):
+                      initial_memory=None, check_memory=None, use_isolation=False,
+                      stdin='', delayed_branching=False, check_heap=False,
+                      check_uninitialized=False, check_segments=False):
     """
     Run student code by POSTing to the Node.js /api/emulator endpoint.
     This replaces the old subprocess.run(GRADER_BINARY, ...) call.
@@ -53,6 +74,11 @@ def run_mips_via_wasm(source_code, initial_registers=None,
         'initial_memory':    initial_memory or {},
         'check_memory':      check_memory or [],
         'use_isolation':     use_isolation,
+        'stdin':             stdin or '',
+        'delayed_branching': delayed_branching,
+        'check_heap':        check_heap,
+        'check_uninitialized': check_uninitialized,
+        'check_segments':    check_segments,
     }
 
     try:
@@ -78,7 +104,21 @@ def run_mips_via_wasm(source_code, initial_registers=None,
         return {'error': 'Emulator execution failed', 'registers': {}, 'memory': {}}
 
 
-def calculate_grade(test_cases, source_code, use_isolation=False):
+def stdout_matches(actual, expected, mode='exact'):
+    """Compare program output using 'exact', 'whitespace' (ignore all whitespace) or 'regex' (full match)."""
+    actual = actual or ''
+    if mode == 'whitespace':
+        return ''.join(actual.split()) == ''.join(expected.split())
+    if mode == 'regex':
+        try:
+            return re.fullmatch(expected, actual, re.DOTALL) is not None
+        except re.error:
+            return False
+    return actual == expected
+
+
+def calculate_grade(test_cases, source_code, use_isolation=False, delayed_branching=False,
+                    check_heap=False, check_uninitialized=False, check_segments=False):
     """Grade by running source code against each test case via the WASM endpoint."""
 
     total_points = 0
@@ -95,6 +135,8 @@ def calculate_grade(test_cases, source_code, use_isolation=False):
         initial_mem  = test.get('initial_memory', {})
         expected_regs = test.get('expected_registers', {})
         expected_mem  = test.get('expected_memory', {})
+        expected_stdout = test.get('expected_stdout')
+        stdout_match    = test.get('stdout_match', 'exact')
 
         total_points += points
 
@@ -106,8 +148,24 @@ def calculate_grade(test_cases, source_code, use_isolation=False):
             initial_memory=initial_mem,
             check_memory=check_memory,
             use_isolation=use_isolation, # Pass the flag here
+            stdin=test.get('stdin', ''),
+            delayed_branching=delayed_branching,
+            check_heap=check_heap,
+            check_uninitialized=check_uninitialized,
+            check_segments=check_segments,
         )
 
+        # a trap or break firing means one of the lab's self-checks failed
+        trap = run_result.get('trap')
+        if trap:
+            failed += 1
+            results.append({
+                'name': test_name, 'status': 'FAIL', 'points': points,
+                'earned': 0, 'message': f'Check failed: `{trap["instruction"]}` fired on line {trap["line"]}',
+                'trap': trap,
+            })
+            continue
+
         if run_result.get('error'):
             failed += 1
             results.append({
@@ -123,6 +181,7 @@ def calculate_grade(test_cases, source_code, use_isolation=False):
         mismatches = []
 
         for register, expected_value in expected_regs.items():
+            register = _canonical_register(register)
             student_value = student_registers.get(register)
             if student_value != expected_value:
                 all_correct = False
@@ -142,6 +201,16 @@ def calculate_grade(test_cases, source_code, use_isolation=False):
                     'actual':   student_value,
                 })
 
+        if expected_stdout is not None:
+            student_stdout = run_result.get('stdout', '')
+            if not stdout_matches(student_stdout, expected_stdout, stdout_match):
+                all_correct = False
+                mismatches.append({
+                    'register': 'stdout',
+                    'expected': expected_stdout,
+                    'actual':   student_stdout,
+                })
+
         if all_correct:
             earned_points += points
             passed += 1
@@ -202,6 +271,9 @@ def get_test_cases_for_lab(lab_id):
                 'initial_memory':     inp.get('memory', {}),
                 'expected_registers': exp.get('registers', {}),
                 'expected_memory':    exp.get('memory', {}),
+                'stdin':              inp.get('stdin', ''),
+                'expected_stdout':    exp.get('stdout'),
+                'stdout_match':       exp.get('stdout_match', 'exact'),
                 'is_hidden':          tc.get('is_hidden', False),
             })
         return result
@@ -300,11 +372,17 @@ def grade_submission():
         cur = conn.cursor()
 
         # Before calling calculate_grade, fetch the lab configuration
-        cur.execute("SELECT use_isolation FROM labs WHERE lab_id = %s", (lab_id,))
+        cur.execute("SELECT use_isolation, delayed_branching, check_heap, check_uninitialized, check_segments FROM labs WHERE lab_id = %s", (lab_id,))
         lab_config = cur.fetchone()
         use_isolation = lab_config[0] if lab_config else False
+        delayed_branching = lab_config[1] if lab_config else False
+        check_heap = lab_config[2] if lab_config else False
+        check_uninitialized = lab_config[3] if lab_config else False
+        check_segments = lab_config[4] if lab_config else False
 
-        grade_report = calculate_grade(test_cases, source_code, use_isolation=use_isolation)
+        grade_report = calculate_grade(test_cases, source_code, use_isolation=use_isolation,
+                                       delayed_branching=delayed_branching, check_heap=check_heap,
+                                       check_uninitialized=check_uninitialized, check_segments=check_segments)
 
         # Save to DB
         normalized_source = source_code.strip().replace('\n', '\\n')
@@ -488,17 +566,27 @@ def verify_solution(lab_id):
         return jsonify({'error': 'Database connection failed'}), 500
     try:
         cur = conn.cursor()
-        cur.execute("SELECT use_isolation FROM labs WHERE lab_id = %s", (lab_id,))
+        cur.execute("SELECT use_isolation, delayed_branching, check_heap, check_uninitialized, check_segments FROM labs WHERE lab_id = %s", (lab_id,))
         lab_config = cur.fetchone()
         use_isolation = lab_config[0] if lab_config else False
+        delayed_branching = lab_config[1] if lab_config else False
+        check_heap = lab_config[2] if lab_config else False
+        check_uninitialized = lab_config[3] if lab_config else False
+        check_segments = lab_config[4] if lab_config else False
     except Exception as e:
         log.error('verify_solution db error: %s', e, exc_info=True)
         use_isolation = False
+        delayed_branching = False
+        check_heap = False
+        check_uninitialized = False
+        check_segments = False
     finally:
         conn.close()
 
     try:
-        grade_report = calculate_grade(test_cases, source_code, use_isolation=use_isolation)
+        grade_report = calculate_grade(test_cases, source_code, use_isolation=use_isolation,
+                                       delayed_branching=delayed_branching, check_heap=check_heap,
+                                       check_uninitialized=check_uninitialized, check_segments=check_segments)
         return jsonify({'success': True, 'lab_id': lab_id, 'grade_report': grade_report})
     except Exception as e:
         log.error('verify_solution grading error: %s', e, exc_info=True)
@@ -20,12 +20,21 @@ function collectRegisters(cpu) {
 /**
  * Run student MIPS code with initial state and return register/memory results.
  */
-function runEmulator(sourceCode, initialRegisters, initialMemory, checkMemory, useIsolation) {
+function runEmulator(sourceCode, initialRegisters, initialMemory, checkMemory, useIsolation, stdin, delayedBranching, checkHeap, checkUninitialized, checkSegments) {
   const cpu = new WasmCPU();
+  cpu.set_segment_checking(Boolean(checkSegments));
+  cpu.set_delayed_branching(Boolean(delayedBranching));
+  cpu.set_heap_checking(Boolean(checkHeap));
+  cpu.set_uninitialized_check(checkUninitialized ? 'strict' : 'off');
 
   const loadResult = cpu.load_source(sourceCode);
   if (loadResult && loadResult.error && loadResult.error.length > 0) {
-    return { registers: {}, memory: {}, error: loadResult.error };
+    return { registers: {}, memory: {}, stdout: '', error: loadResult.error };
+  }
+
+  // scripted console input for the read syscalls
+  if (stdin) {
+    cpu.push_input(String(stdin));
   }
 
   // apply initial register values
@@ -33,7 +42,7 @@ function runEmulator(sourceCode, initialRegisters, initialMemory, checkMemory, u
     try {
       cpu.set_register(reg, Number(val) >>> 0);
     } catch (e) {
-      return { registers: {}, memory: {}, error: `Bad register ${reg}: ${e.message}` };
+      return { registers: {}, memory: {}, stdout: '', error: `Bad register ${reg}: ${e.message}` };
     }
   }
 
@@ -42,13 +51,15 @@ function runEmulator(sourceCode, initialRegisters, initialMemory, checkMemory, u
     try {
       cpu.set_memory_word(parseInt(addrStr, 10), Number(val));
     } catch (e) {
-      return { registers: {}, memory: {}, error: `Bad memory addr ${addrStr}: ${e.message}` };
+      return { registers: {}, memory: {}, stdout: '', error: `Bad memory addr ${addrStr}: ${e.message}` };
     }
   }
 
   // execute
   let execResult;
   let runError = '';
+  let stdout = '';
+  let trap = null;
 
   try {
     if (useIsolation) {
@@ -57,9 +68,11 @@ function runEmulator(sourceCode, initialRegisters, initialMemory, checkMemory, u
 
     try {
       execResult = cpu.run();
-      
+      stdout = (execResult && execResult.output) || '';
+
       if (execResult && execResult.error && execResult.error !== 'Termination') {
         runError = execResult.error;
+        trap = execResult.trap || null;
       }
     } finally {
       cpu.set_isolation(false); // Always cleanup
@@ -107,7 +120,9 @@ function runEmulator(sourceCode, initialRegisters, initialMemory, checkMemory, u
   return {
     registers: finalRegisters,
     memory:    finalMemory,
+    stdout:    stdout,
     error:     runError,
+    trap:      trap,
   };
 }
 
@@ -118,7 +133,7 @@ module.exports = async (req, res) => {
   }
 
   try {
-    const { source_code, initial_registers, initial_memory, check_memory, use_isolation } = req.body;
+    const { source_code, initial_registers, initial_memory, check_memory, use_isolation, stdin, delayed_branching, check_heap, check_uninitialized, check_segments } = req.body;
 
     if (!source_code && source_code !== '') {
       return res.status(400).json({ error: 'source_code is required' });
@@ -130,6 +145,11 @@ module.exports = async (req, res) => {
       initial_memory || {},
       check_memory || [],
       use_isolation || false,
+      stdin || '',
+      delayed_branching || false,
+      check_heap || false,
+      check_uninitialized || false,
+      check_segments || false,
     );
 
     return res.status(200).json(result);
@@ -139,6 +159,7 @@ module.exports = async (req, res) => {
     return res.status(500).json({
       registers: {},
       memory: {},
+      stdout: '',
       error: `Server error: ${err.message}`,
     });
   }
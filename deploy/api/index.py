@@ -149,7 +149,7 @@ def get_labs():
                 starter_code, solution_code, register_mapping, initial_values,
                 max_memory_kb, time_limit_seconds, max_instructions,
                 total_points, release_date, due_date, is_published, difficulty,
-                use_isolation
+                use_isolation, delayed_branching, check_heap, check_uninitialized, check_segments
             FROM labs ORDER BY lab_id
         """)
         labs = cursor.fetchall()
@@ -172,6 +172,10 @@ def get_labs():
                 'points':           lab.get('total_points'),
                 'register_mapping': reg_map,
                 'initial_values':   init_vals,
+                'delayed_branching': bool(lab.get('delayed_branching')),
+                'check_heap':       bool(lab.get('check_heap')),
+                'check_uninitialized': bool(lab.get('check_uninitialized')),
+                'check_segments':   bool(lab.get('check_segments')),
                 'test_cases':       test_cases,
             }
 
@@ -218,8 +222,8 @@ def create_lab():
                 starter_code, solution_code, register_mapping, initial_values,
                 difficulty, total_points, max_instructions, time_limit_seconds,
                 max_memory_kb, release_date, due_date, is_published,
-                use_isolation
-            ) VALUES (%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s)
+                use_isolation, delayed_branching, check_heap, check_uninitialized, check_segments
+            ) VALUES (%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s,%s)
             RETURNING lab_id
         """, (
             data['lab_id'], default_course_id, data['title'],
@@ -232,6 +236,10 @@ def create_lab():
             data.get('max_memory_kb', 1024), data.get('release_date'),
             data.get('due_date'), data.get('is_published', True),
             data.get('use_isolation', False),
+            data.get('delayed_branching', False),
+            data.get('check_heap', False),
+            data.get('check_uninitialized', False),
+            data.get('check_segments', False),
         ))
 
         conn.commit()
@@ -271,7 +279,11 @@ def update_lab(lab_id):
                 total_points = COALESCE(%s, total_points),
                 due_date = COALESCE(%s, due_date),
                 is_published = COALESCE(%s, is_published),
-                use_isolation = COALESCE(%s, use_isolation)
+                use_isolation = COALESCE(%s, use_isolation),
+                delayed_branching = COALESCE(%s, delayed_branching),
+                check_heap = COALESCE(%s, check_heap),
+                check_uninitialized = COALESCE(%s, check_uninitialized),
+                check_segments = COALESCE(%s, check_segments)
             WHERE lab_id = %s RETURNING lab_id
         """, (
             data.get('title'), data.get('description'), data.get('instructions'),
@@ -279,7 +291,9 @@ def update_lab(lab_id):
             json.dumps(data.get('register_mapping')) if 'register_mapping' in data else None,
             json.dumps(data.get('initial_values')) if 'initial_values' in data else None,
             data.get('difficulty'), data.get('points'), data.get('due_date'),
-            data.get('is_published'), data.get('use_isolation'), lab_id,
+            data.get('is_published'), data.get('use_isolation'),
+            data.get('delayed_branching'), data.get('check_heap'),
+            data.get('check_uninitialized'), data.get('check_segments'), lab_id,
         ))
 
         if cursor.rowcount == 0:
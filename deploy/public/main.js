@@ -701,6 +701,11 @@ function renderTestCases(testCases) {
 const consoleOut = document.getElementById("console-output");
 const runBtn = document.getElementById("run");
 const stepBtn = document.getElementById("step");
+const stepOverBtn = document.getElementById("stepOver");
+const stepOutBtn = document.getElementById("stepOut");
+const runToCursorBtn = document.getElementById("runToCursor");
+const stepBackBtn = document.getElementById("stepBack");
+const runBackBtn = document.getElementById("runBack");
 const stopBtn = document.getElementById("stop");
 const registersDiv = document.getElementById("registers");
 const codeEl = document.querySelector(".assembler textarea");
@@ -865,6 +870,8 @@ let wasmReady = false;
 let isProgramLoaded = false;
 let lastRegs = {};
 let breakpoints = new Set();
+// condition, ignore count and log message of the breakpoints edited with shift-click, by line
+let breakpointOptions = new Map();
 let currentLineMarker = null;
 let currentLabData = null;
 let currentLessonId = null;
@@ -898,7 +905,7 @@ CodeMirror.defineSimpleMode("mips-custom", {
     {
       // instructions
       regex:
-        /(?:add|addu|addi|addiu|sub|subu|li|sw|lw|sb|lb|sh|lh|lui|la|j|jal|jr|or|ori|and|andi|beq|bne|slt|slti|sltiu|sltu|blt|bgt|ble|bge|move|mult|multu|mflo|mfhi|xor|xori|div|divu|nor|sll|srl|sra)\b/i,
+        /(?:(?:add|sub|mul|div|abs|neg|mov|sqrt)\.[sd]|cvt\.[sdw]\.[sdw]|c\.(?:eq|lt|le)\.[sd]|add|addu|addi|addiu|sub|subu|li|sw|lw|sb|lb|sh|lh|lui|la|j|jal|jr|or|ori|and|andi|beq|bne|slt|slti|sltiu|sltu|blt|bgt|ble|bge|move|mult|multu|mflo|mfhi|xor|xori|div|divu|nor|sll|srl|sra|syscall|nop|mfc0|mtc0|eret|jalr|bgezal|bltzal|bgez|bgtz|blez|bltz|beqz|bnez|bltu|bgtu|bleu|bgeu|bal|b|lbu|lhu|lwl|lwr|swl|swr|sllv|srlv|srav|mul|madd|maddu|msub|msubu|clz|clo|movn|movz|seb|seh|ext|ins|rem|neg|not|abs|seq|sne|sge|sgt|bc1t|bc1f|lwc1|swc1|ldc1|sdc1|mtc1|mfc1|ll|sc|sync|teq|tne|tge|tgeu|tlt|tltu|teqi|tnei|tgei|tgeiu|tlti|tltiu|break)\b/i,
       token: "keyword",
     },
     {
@@ -1135,23 +1142,68 @@ function highlightCurrentLine() {
   }
 }
 
-// breakpoints via gutter click
-cpuEditor.on("gutterClick", (cm, lineIndex, gutter) => {
+// sends a breakpoint's edited settings to the CPU (a line without any becomes a plain breakpoint)
+function applyBreakpoint(line) {
+  const options = breakpointOptions.get(line) || { condition: "", ignoreCount: 0, logMessage: "" };
+  const error = cpu.set_breakpoint(line, options.condition || null, options.ignoreCount, options.logMessage || null);
+  if (error) log(`Breakpoint on line ${line + 1}: ${error}`);
+}
+
+// loading a program clears the CPU's breakpoints, so the edited settings are sent again after each load
+function applyBreakpointOptions() {
+  for (const line of breakpointOptions.keys()) {
+    applyBreakpoint(line);
+  }
+}
+
+// asks for a breakpoint's condition, ignore count and log message (false if cancelled)
+function editBreakpointOptions(lineIndex) {
+  const current = breakpointOptions.get(lineIndex) || { condition: "", ignoreCount: 0, logMessage: "" };
+
+  const condition = window.prompt("Stop only when (e.g. $t0 == 10 && mem[$sp+4] > 0), empty for always:", current.condition);
+  if (condition === null) return false;
+  const ignore = window.prompt("Number of hits to ignore:", String(current.ignoreCount));
+  if (ignore === null) return false;
+  const logMessage = window.prompt("Log message printed instead of stopping (e.g. i = {$t0}), empty to stop:", current.logMessage);
+  if (logMessage === null) return false;
+
+  const options = { condition: condition.trim(), ignoreCount: Math.max(0, parseInt(ignore, 10) || 0), logMessage };
+  if (!options.condition && !options.ignoreCount && !options.logMessage) {
+    breakpointOptions.delete(lineIndex);
+  } else {
+    breakpointOptions.set(lineIndex, options);
+  }
+  return true;
+}
+
+// breakpoints via gutter click (shift-click sets a condition, ignore count or log message)
+cpuEditor.on("gutterClick", (cm, lineIndex, gutter, event) => {
   if (gutter !== "breakpoints") return;
 
   const info = cm.lineInfo(lineIndex);
-  if (info.gutterMarkers && info.gutterMarkers.breakpoints) {
+  const editing = event && event.shiftKey;
+  if (info.gutterMarkers && info.gutterMarkers.breakpoints && !editing) {
     cm.setGutterMarker(lineIndex, "breakpoints", null);
     breakpoints.delete(lineIndex);
+    breakpointOptions.delete(lineIndex);
   } else {
+    if (editing && !editBreakpointOptions(lineIndex)) return;
+
+    const options = breakpointOptions.get(lineIndex);
     const marker = document.createElement("div");
-    marker.innerHTML = "●";
+    marker.innerHTML = options ? (options.logMessage ? "◆" : "◐") : "●";
+    marker.title = options ? [options.condition, options.logMessage].filter(Boolean).join(" | ") : "";
     marker.style.cursor = "pointer";
     marker.style.padding = "0 3px";
     marker.style.fontSize = "13px";
     marker.style.color = "#f00";
     cm.setGutterMarker(lineIndex, "breakpoints", marker);
     breakpoints.add(lineIndex);
+
+    if (editing && cpu && wasmReady && isProgramLoaded) {
+      cpu.set_breakpoints(Array.from(breakpoints));
+      applyBreakpoint(lineIndex);
+    }
   }
 });
 
@@ -1171,6 +1223,7 @@ function resetEmulator() {
   if (runBtn) runBtn.disabled = false;
 
   breakpoints.clear();
+  breakpointOptions.clear();
   cpuEditor.clearGutter("breakpoints");
   clearHighlight();
   updateMemoryView();
@@ -1195,6 +1248,9 @@ function loadProgram() {
   paintRegisters({});
   lastRegs = {};
 
+  // labs with the strict setting stop at a read of an unwritten register or address, otherwise it is only listed
+  cpu.set_uninitialized_check(currentLabData && currentLabData.check_uninitialized ? "strict" : "warn");
+
   const src = cpuEditor.getValue() || "";
   const result = cpu.load_source(src);
 
@@ -1211,14 +1267,28 @@ function loadProgram() {
   if (currentLabData) {
     applyInitialValues(currentLabData);
   }
+  cpu.set_delayed_branching(Boolean(currentLabData && currentLabData.delayed_branching));
+  cpu.set_heap_checking(Boolean(currentLabData && currentLabData.check_heap));
+  // free-form programs always get segment checking, labs opt in (older labs keep their arrays at low addresses)
+  cpu.set_segment_checking(!currentLabData || Boolean(currentLabData.check_segments));
 
   cpu.set_breakpoints(Array.from(breakpoints));
+  applyBreakpointOptions();
   highlightCurrentLine();
   updateWidgets(cpu.get_mmio_state());
 
   return true;
 }
 
+// lists the reads of registers and memory the program never wrote (collected in warn mode)
+function logUninitializedReads() {
+  const reads = cpu.uninitialized_reads() || [];
+  for (const read of reads) {
+    const what = read.location.startsWith("$") ? read.location : "memory at " + read.location;
+    log(`Warning: ${what} is read before it was written (line ${read.line})`);
+  }
+}
+
 function handleWasmResult(result, { fromRun = false } = {}) {
   // Support both result formats (raw registers or snapshot object)
   const rawRegs = (result && result.snapshot && result.snapshot.registers) || 
@@ -1226,6 +1296,12 @@ function handleWasmResult(result, { fromRun = false } = {}) {
                   
   const allRegs = coerceRegs(rawRegs);
 
+  // console output from print syscalls
+  if (result && result.output && consoleOut) {
+    consoleOut.textContent += result.output;
+    consoleOut.scrollTop = consoleOut.scrollHeight;
+  }
+
   const usedNames = getUsedRegisterNames();
   const tabRegs = filterForTab(allRegs, lastRegs, usedNames);
   
@@ -1265,11 +1341,29 @@ function handleWasmResult(result, { fromRun = false } = {}) {
         }
         log("\nProgram Finished");
       }
+      logUninitializedReads();
       isProgramLoaded = false;
       
       if (stepBtn) stepBtn.disabled = true;
       if (runBtn) runBtn.disabled = true;
 
+    } else if (result.error === "InputRequired") {
+      // read syscall is waiting; it is retried once input has been pushed
+      const input = window.prompt("Program input:");
+      if (input !== null) {
+        cpu.push_input(input + "\n");
+
+        if (fromRun) {
+          lastRegs = allRegs;
+          handleWasmResult(cpu.run(), { fromRun: true });
+          return;
+        }
+      }
+      highlightCurrentLine();
+      isProgramLoaded = true;
+      if (stepBtn) stepBtn.disabled = false;
+      if (runBtn) runBtn.disabled = false;
+
     } else if (result.error === "Breakpoint") {
       log("\n--- Hit Breakpoint ---");
       highlightCurrentLine();
@@ -1277,6 +1371,15 @@ function handleWasmResult(result, { fromRun = false } = {}) {
       if (stepBtn) stepBtn.disabled = false;
       if (runBtn) runBtn.disabled = false;
 
+    } else if (result.error === "Watchpoint") {
+      // the accessed memory cell is already highlighted from the snapshot
+      const watch = result.watchpoint;
+      log(`\n--- Hit Watchpoint ${watch.id}: ${watch.location} ${fmt(watch.old)} -> ${fmt(watch.new)} ---`);
+      highlightCurrentLine();
+      isProgramLoaded = true;
+      if (stepBtn) stepBtn.disabled = false;
+      if (runBtn) runBtn.disabled = false;
+
     } else {
       clearHighlight();
 
@@ -1315,6 +1418,7 @@ function handleWasmResult(result, { fromRun = false } = {}) {
     if (runBtn) runBtn.disabled = true;
 
     log("\nProgram Finished");
+    logUninitializedReads();
   }
 
   // keep snapshots in sync
@@ -1386,6 +1490,76 @@ if (stepBtn) {
   });
 }
 
+// step over / step out / run to cursor, stopping where the CPU's call tracking (or the line) says
+function stepUntil(action) {
+  if (!wasmReady || !cpu) {
+    log("WASM not initialized yet.");
+    return;
+  }
+
+  if (!isProgramLoaded) {
+    logRunTelemetry(true);
+    if (!loadProgram()) return;
+  }
+
+  cpu.set_breakpoints(Array.from(breakpoints));
+  runCount++;
+
+  handleWasmResult(action());
+  if (isProgramLoaded) {
+    highlightCurrentLine();
+  }
+}
+
+if (stepOverBtn) {
+  stepOverBtn.addEventListener("click", () => stepUntil(() => cpu.step_over()));
+}
+
+if (stepOutBtn) {
+  stepOutBtn.addEventListener("click", () => stepUntil(() => cpu.step_out()));
+}
+
+if (runToCursorBtn) {
+  runToCursorBtn.addEventListener("click", () =>
+    stepUntil(() => cpu.run_to_line(cpuEditor.getCursor().line))
+  );
+}
+
+// reverse execution using the CPU's undo log (console output is not taken back)
+function stepBackwards(toBreakpoint) {
+  if (!wasmReady || !cpu) {
+    log("WASM not initialized yet.");
+    return;
+  }
+
+  const result = toBreakpoint ? cpu.run_back_to_breakpoint() : cpu.step_back();
+  if (result.error === "NoHistory" && !toBreakpoint) {
+    log("\n--- No earlier instruction recorded ---");
+    return;
+  }
+
+  if (result.error === "Breakpoint") {
+    log("\n--- Hit Breakpoint ---");
+  } else if (result.error === "NoHistory") {
+    log("\n--- Reached the oldest recorded instruction ---");
+  }
+  handleWasmResult({ ...result, error: "" });
+
+  // stepping back from the end of the program resumes it
+  isProgramLoaded = true;
+  if (stepBtn) stepBtn.disabled = false;
+  if (runBtn) runBtn.disabled = false;
+  highlightCurrentLine();
+}
+
+if (stepBackBtn) {
+  stepBackBtn.addEventListener("click", () => stepBackwards(false));
+}
+
+if (runBackBtn) {
+  runBackBtn.addEventListener("click", () => stepBackwards(true));
+}
+
 if (stopBtn) {
   stopBtn.addEventListener("click", () => {
     resetEmulator();
@@ -1748,6 +1922,8 @@ const requestedLessonId = urlParams.get("lesson") || null;
 init()
   .then(() => {
     cpu = new WasmCPU();
+    // undo log for Step Back / Run Back
+    cpu.set_history_size(10000);
     wasmReady = true;
     resetEmulator();
     log("WASM initialized.");
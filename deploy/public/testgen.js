@@ -28,7 +28,7 @@ let allRegsSnapshot = {};
 CodeMirror.defineSimpleMode("mips-custom", {
   start: [
     {
-      regex: /(?:add|addu|addi|addiu|sub|subu|li|sw|lw|sb|lb|sh|lh|lui|la|j|jal|jr|or|ori|and|andi|beq|bne|slt|slti|sltiu|sltu|blt|bgt|ble|bge|move|mult|multu|mflo|mfhi|xor|xori|div|divu|nor|sll|srl|sra)\b/i,
+      regex: /(?:(?:add|sub|mul|div|abs|neg|mov|sqrt)\.[sd]|cvt\.[sdw]\.[sdw]|c\.(?:eq|lt|le)\.[sd]|add|addu|addi|addiu|sub|subu|li|sw|lw|sb|lb|sh|lh|lui|la|j|jal|jr|or|ori|and|andi|beq|bne|slt|slti|sltiu|sltu|blt|bgt|ble|bge|move|mult|multu|mflo|mfhi|xor|xori|div|divu|nor|sll|srl|sra|syscall|nop|mfc0|mtc0|eret|jalr|bgezal|bltzal|bgez|bgtz|blez|bltz|beqz|bnez|bltu|bgtu|bleu|bgeu|bal|b|lbu|lhu|lwl|lwr|swl|swr|sllv|srlv|srav|mul|madd|maddu|msub|msubu|clz|clo|movn|movz|seb|seh|ext|ins|rem|neg|not|abs|seq|sne|sge|sgt|bc1t|bc1f|lwc1|swc1|ldc1|sdc1|mtc1|mfc1|ll|sc|sync|teq|tne|tge|tgeu|tlt|tltu|teqi|tnei|tgei|tgeiu|tlti|tltiu|break)\b/i,
       token: "keyword",
     },
     {
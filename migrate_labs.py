@@ -34,6 +34,12 @@ def seed_database():
     cur = conn.cursor()
 
     try:
+        # columns added after the initial schema
+        cur.execute("ALTER TABLE labs ADD COLUMN IF NOT EXISTS delayed_branching boolean DEFAULT false")
+        cur.execute("ALTER TABLE labs ADD COLUMN IF NOT EXISTS check_segments boolean DEFAULT false")
+        cur.execute("ALTER TABLE labs ADD COLUMN IF NOT EXISTS check_heap boolean DEFAULT false")
+        cur.execute("ALTER TABLE labs ADD COLUMN IF NOT EXISTS check_uninitialized boolean DEFAULT false")
+
         cur.execute("SELECT course_id FROM courses LIMIT 1")
         course = cur.fetchone()
         if not course:
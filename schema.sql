@@ -51,6 +51,10 @@ CREATE TABLE public.labs (
     is_published boolean DEFAULT false,
     difficulty character varying(20) DEFAULT 'intermediate',
     use_isolation boolean DEFAULT false,
+    delayed_branching boolean DEFAULT false,
+    check_segments boolean DEFAULT false,
+    check_heap boolean DEFAULT false,
+    check_uninitialized boolean DEFAULT false,
     CONSTRAINT labs_pkey PRIMARY KEY (lab_id),
     CONSTRAINT labs_course_fkey FOREIGN KEY (course_id) REFERENCES public.courses(course_id)
 );
@@ -4,9 +4,11 @@ use crate::parser::ProgramStatement;
 use crate::parser::Label;
 use crate::instruction::Instruction;
 use crate::instruction::CoreInstruction;
+use crate::instruction::Register;
 use crate::instruction::PseudoInstruction;
 use crate::program::EmuError; 
-use crate::memory::Memory;
+use crate::memory::{Memory, DEFAULT_TEXT_BASE_ADDRESS};
+use crate::encoding::encode;
 
 // use crate::lexer::alert;
 
@@ -16,6 +18,9 @@ pub struct Assembler {
     instruction_index: u32,
     line_number_index: usize,
 
+    // index of the first kernel (`.ktext`) instruction, which are placed after the user instructions
+    pub kernel_start: usize,
+
     pub parser: Parser,
 }
 
@@ -24,6 +29,7 @@ impl Assembler {
         Assembler {
             instruction_index: 0,
             line_number_index: 0,
+            kernel_start: 0,
 
             parser: Parser::new(),
         }
@@ -37,6 +43,14 @@ impl Assembler {
                 let mut core_instructions: Vec<CoreInstruction> = Vec::new();
                 let mut new_line_numbers: Vec<usize> = Vec::new();
 
+                // instructions, line numbers and index of the text segment that is not currently being assembled
+                let mut other_segment: (Vec<CoreInstruction>, Vec<usize>, u32) = (Vec::new(), Vec::new(), 0);
+                let mut in_kernel_text = false;
+                let kernel_text_base = self.parser.kernel_text_base;
+
+                // `la` of a text label is patched once every label has an address (kernel segment, index, label)
+                let mut la_fixups: Vec<(bool, usize, String)> = Vec::new();
+
                 // alert(format!("Line numbers: {:?}", errors).as_str());
 
                 for program_statement in &program_statements {
@@ -44,9 +58,12 @@ impl Assembler {
                         ProgramStatement::Instruction(insn) => {
                             match insn {
                                 Instruction::Core(core) => {
-                                    new_line_numbers.push(line_numbers[self.line_number_index as usize]);
-                                    core_instructions.push(core.clone());
-                                    self.instruction_index += 1;
+                                    let expanded = expand_immediate(core);
+                                    let line = line_numbers[self.line_number_index];
+
+                                    new_line_numbers.extend(std::iter::repeat_n(line, expanded.len()));
+                                    self.instruction_index += expanded.len() as u32;
+                                    core_instructions.extend(expanded);
                                     self.line_number_index += 1;
                                     // alert(format!("Core instruction: {:?}", core).as_str());
                                 },
@@ -54,13 +71,14 @@ impl Assembler {
                                     match pseudo {
                                         PseudoInstruction::La { rt, label } => {
                                             let address = self.parser.symbol_table.get(label).cloned().unwrap_or(0);
+                                            la_fixups.push((in_kernel_text, core_instructions.len(), label.clone()));
                                             let inst_1 = CoreInstruction::Lui {
-                                                rt: "$at".to_string(),
+                                                rt: Register::AT,
                                                 imm: (address >> 16) as u32,
                                             };
                                             let inst_2 = CoreInstruction::Ori {
-                                                rt: rt.clone(),
-                                                rs: "$at".to_string(),
+                                                rt: *rt,
+                                                rs: Register::AT,
                                                 imm: (address & 0xFFFF) as u32,
                                             };
 
@@ -81,12 +99,12 @@ impl Assembler {
 
                                             if imm_signed_16_bits {
                                                 let inst_1 = CoreInstruction::Lui {
-                                                    rt: "$at".to_string(),
+                                                    rt: Register::AT,
                                                     imm: 0,
                                                 };
                                                 let inst_2 = CoreInstruction::Addi {
-                                                    rt: rd.clone(),
-                                                    rs: "$at".to_string(),
+                                                    rt: *rd,
+                                                    rs: Register::AT,
                                                     imm: *imm as i32,
                                                 };
                                                 core_instructions.push(inst_1);
@@ -98,12 +116,12 @@ impl Assembler {
                                                 self.line_number_index += 1;
                                             } else if imm_unsigned_16_bits {
                                                 let inst_1 = CoreInstruction::Lui {
-                                                    rt: "$at".to_string(),
+                                                    rt: Register::AT,
                                                     imm: 0,
                                                 };
                                                 let inst_2 = CoreInstruction::Ori {
-                                                    rt: rd.clone(),
-                                                    rs: "$at".to_string(),
+                                                    rt: *rd,
+                                                    rs: Register::AT,
                                                     imm: *imm,
                                                 };
                                                 core_instructions.push(inst_1);
@@ -118,12 +136,12 @@ impl Assembler {
                                                 let lower_imm = (*imm & 0xFFFF) as u32;
 
                                                 let inst_1 = CoreInstruction::Lui {
-                                                    rt: "$at".to_string(),
+                                                    rt: Register::AT,
                                                     imm: upper_imm,
                                                 };
                                                 let inst_2 = CoreInstruction::Ori {
-                                                    rt: rd.clone(),
-                                                    rs: "$at".to_string(),
+                                                    rt: *rd,
+                                                    rs: Register::AT,
                                                     imm: lower_imm,
                                                 };
 
@@ -138,13 +156,13 @@ impl Assembler {
                                         },
                                         PseudoInstruction::Blt { rs, rt, label } => {
                                             let inst_1 = CoreInstruction::Slt {
-                                                rd: "$at".to_string(),
-                                                rs: rs.clone(),
-                                                rt: rt.clone(),
+                                                rd: Register::AT,
+                                                rs: *rs,
+                                                rt: *rt,
                                             };
                                             let inst_2 = CoreInstruction::Bne {
-                                                rs: "$at".to_string(),
-                                                rt: "$zero".to_string(),
+                                                rs: Register::AT,
+                                                rt: Register::ZERO,
                                                 label: label.clone(),
                                             };
 
@@ -158,13 +176,13 @@ impl Assembler {
                                         },
                                         PseudoInstruction::Bgt { rs, rt, label } => {
                                             let inst_1 = CoreInstruction::Slt {
-                                                rd: "$at".to_string(),
-                                                rs: rt.clone(),
-                                                rt: rs.clone(),
+                                                rd: Register::AT,
+                                                rs: *rt,
+                                                rt: *rs,
                                             };
                                             let inst_2 = CoreInstruction::Bne {
-                                                rs: "$at".to_string(),
-                                                rt: "$zero".to_string(),
+                                                rs: Register::AT,
+                                                rt: Register::ZERO,
                                                 label: label.clone(),
                                             };
 
@@ -178,13 +196,13 @@ impl Assembler {
                                         },
                                         PseudoInstruction::Ble { rs, rt, label } => {
                                             let inst_1 = CoreInstruction::Slt {
-                                                rd: "$at".to_string(),
-                                                rs: rt.clone(),
-                                                rt: rs.clone(),
+                                                rd: Register::AT,
+                                                rs: *rt,
+                                                rt: *rs,
                                             };
                                             let inst_2 = CoreInstruction::Beq {
-                                                rs: "$at".to_string(),
-                                                rt: "$zero".to_string(),
+                                                rs: Register::AT,
+                                                rt: Register::ZERO,
                                                 label: label.clone(),
                                             };
 
@@ -198,13 +216,13 @@ impl Assembler {
                                         },
                                         PseudoInstruction::Bge { rs, rt, label } => {
                                             let inst_1 = CoreInstruction::Slt {
-                                                rd: "$at".to_string(),
-                                                rs: rs.clone(),
-                                                rt: rt.clone(),
+                                                rd: Register::AT,
+                                                rs: *rs,
+                                                rt: *rt,
                                             };
                                             let inst_2 = CoreInstruction::Beq {
-                                                rs: "$at".to_string(),
-                                                rt: "$zero".to_string(),
+                                                rs: Register::AT,
+                                                rt: Register::ZERO,
                                                 label: label.clone(),
                                             };
 
@@ -218,13 +236,13 @@ impl Assembler {
                                         },
                                         PseudoInstruction::Move { rd, rs } => {
                                             let inst_1 = CoreInstruction::Lui {
-                                                rt: "$at".to_string(),
+                                                rt: Register::AT,
                                                 imm: 0,
                                             };
                                             let inst_2 = CoreInstruction::Add {
-                                                rd: rd.clone(),
-                                                rs: rs.clone(),
-                                                rt: "$at".to_string(),
+                                                rd: *rd,
+                                                rs: *rs,
+                                                rt: Register::AT,
                                             };
                                             core_instructions.push(inst_1);
                                             core_instructions.push(inst_2);
@@ -234,14 +252,43 @@ impl Assembler {
                                             self.instruction_index += 2;
                                             self.line_number_index += 1;
                                         },
+                                        PseudoInstruction::Bltu { .. } | PseudoInstruction::Bgtu { .. } |
+                                        PseudoInstruction::Bleu { .. } | PseudoInstruction::Bgeu { .. } |
+                                        PseudoInstruction::Beqz { .. } | PseudoInstruction::Bnez { .. } |
+                                        PseudoInstruction::B { .. } | PseudoInstruction::Bal { .. } |
+                                        PseudoInstruction::BranchImm { .. } |
+                                        PseudoInstruction::MulImm { .. } | PseudoInstruction::Rem { .. } |
+                                        PseudoInstruction::Neg { .. } | PseudoInstruction::Not { .. } |
+                                        PseudoInstruction::Abs { .. } | PseudoInstruction::Seq { .. } |
+                                        PseudoInstruction::Sne { .. } | PseudoInstruction::Sge { .. } |
+                                        PseudoInstruction::Sgt { .. } => {
+                                            let expanded = expand_pseudo(pseudo);
+                                            let line = line_numbers[self.line_number_index];
+
+                                            new_line_numbers.extend(std::iter::repeat_n(line, expanded.len()));
+                                            self.instruction_index += expanded.len() as u32;
+                                            core_instructions.extend(expanded);
+                                            self.line_number_index += 1;
+                                        },
                                         _ => {}
                                     }
                                 }
                                 // alert(format!("Pseudo instruction: {:?}", pseudo).as_str());
                             }
                         },
+                        ProgramStatement::UserText | ProgramStatement::KernelText => {
+                            let to_kernel = matches!(program_statement, ProgramStatement::KernelText);
+
+                            if to_kernel != in_kernel_text {
+                                std::mem::swap(&mut core_instructions, &mut other_segment.0);
+                                std::mem::swap(&mut new_line_numbers, &mut other_segment.1);
+                                std::mem::swap(&mut self.instruction_index, &mut other_segment.2);
+                                in_kernel_text = to_kernel;
+                            }
+                        },
                         ProgramStatement::Label(label) => {
-                            let address = crate::memory::DEFAULT_TEXT_BASE_ADDRESS + (self.instruction_index * 4);
+                            let base = if in_kernel_text { kernel_text_base } else { DEFAULT_TEXT_BASE_ADDRESS };
+                            let address = base + (self.instruction_index * 4);
                             symbol_table.insert(label.name.clone(), address);
                             // alert(format!("Label: {} at address: 0x{:x}", label.name, label.address).as_str());
                         },
@@ -254,6 +301,40 @@ impl Assembler {
 
                 // alert(format!("New line numbers: {:?}", new_line_numbers).as_str());
 
+                // kernel instructions follow the user instructions
+                if in_kernel_text {
+                    std::mem::swap(&mut core_instructions, &mut other_segment.0);
+                    std::mem::swap(&mut new_line_numbers, &mut other_segment.1);
+                }
+                self.kernel_start = core_instructions.len();
+                core_instructions.append(&mut other_segment.0);
+                new_line_numbers.append(&mut other_segment.1);
+
+                for (in_kernel, index, label) in la_fixups {
+                    let index = if in_kernel { self.kernel_start + index } else { index };
+                    let address = symbol_table.get(&label).copied().unwrap_or(0);
+
+                    if let CoreInstruction::Lui { imm, .. } = &mut core_instructions[index] {
+                        *imm = address >> 16;
+                    }
+                    if let CoreInstruction::Ori { imm, .. } = &mut core_instructions[index + 1] {
+                        *imm = address & 0xFFFF;
+                    }
+                }
+
+                // place the encoded machine words in the text segments
+                for (i, core) in core_instructions.iter().enumerate() {
+                    let pc = if i < self.kernel_start {
+                        DEFAULT_TEXT_BASE_ADDRESS + (i as u32 * 4)
+                    } else {
+                        kernel_text_base + ((i - self.kernel_start) as u32 * 4)
+                    };
+                    let word = encode(core, pc, &symbol_table)
+                        .map_err(|e| EmuError::ParsingError(format!("Line {}: Cannot encode instruction ({:?})", new_line_numbers[i], e)))?;
+
+                    memory.set_word(pc, word as i32);
+                }
+
                 Ok((core_instructions, symbol_table, new_line_numbers))
             },
             Err(e) => return Err(e),
@@ -267,6 +348,187 @@ impl Assembler {
     }
 }
 
+/// expands the pseudo-instructions that are not handled inline in `Assembler::assemble`
+fn expand_pseudo(pseudo: &PseudoInstruction) -> Vec<CoreInstruction> {
+    match pseudo {
+        PseudoInstruction::Bltu { rs, rt, label } => expand_compare_branch("bltu", *rs, *rt, label),
+        PseudoInstruction::Bgtu { rs, rt, label } => expand_compare_branch("bgtu", *rs, *rt, label),
+        PseudoInstruction::Bleu { rs, rt, label } => expand_compare_branch("bleu", *rs, *rt, label),
+        PseudoInstruction::Bgeu { rs, rt, label } => expand_compare_branch("bgeu", *rs, *rt, label),
+
+        PseudoInstruction::Beqz { rs, label } => vec![
+            CoreInstruction::Beq { rs: *rs, rt: Register::ZERO, label: label.clone() },
+        ],
+        PseudoInstruction::Bnez { rs, label } => vec![
+            CoreInstruction::Bne { rs: *rs, rt: Register::ZERO, label: label.clone() },
+        ],
+        PseudoInstruction::B { label } => vec![
+            CoreInstruction::Beq { rs: Register::ZERO, rt: Register::ZERO, label: label.clone() },
+        ],
+        PseudoInstruction::Bal { label } => vec![
+            CoreInstruction::Bgezal { rs: Register::ZERO, label: label.clone() },
+        ],
+
+        // load the immediate into $at and use the register form
+        PseudoInstruction::BranchImm { op, rs, imm, label } => {
+            let mut insns = load_at(*imm);
+            insns.extend(expand_compare_branch(op, *rs, Register::AT, label));
+            insns
+        },
+
+        PseudoInstruction::MulImm { rd, rs, imm } => {
+            let mut insns = load_at(*imm);
+            insns.push(CoreInstruction::Mul { rd: *rd, rs: *rs, rt: Register::AT });
+            insns
+        },
+        PseudoInstruction::Rem { rd, rs, rt } => vec![
+            CoreInstruction::Div { rs: *rs, rt: *rt },
+            CoreInstruction::Mfhi { rd: *rd },
+        ],
+        PseudoInstruction::Neg { rd, rs } => vec![
+            CoreInstruction::Sub { rd: *rd, rs: Register::ZERO, rt: *rs },
+        ],
+        PseudoInstruction::Not { rd, rs } => vec![
+            CoreInstruction::Nor { rd: *rd, rs: *rs, rt: Register::ZERO },
+        ],
+
+        // $at is all ones for a negative value: (x ^ $at) - $at flips and adds one
+        PseudoInstruction::Abs { rd, rs } => vec![
+            CoreInstruction::Sra { rd: Register::AT, rt: *rs, imm: 31 },
+            CoreInstruction::Xor { rd: *rd, rs: *rs, rt: Register::AT },
+            CoreInstruction::Subu { rd: *rd, rs: *rd, rt: Register::AT },
+        ],
+
+        PseudoInstruction::Seq { rd, rs, rt } => vec![
+            CoreInstruction::Subu { rd: *rd, rs: *rs, rt: *rt },
+            CoreInstruction::Sltiu { rt: *rd, rs: *rd, imm: 1 },
+        ],
+        PseudoInstruction::Sne { rd, rs, rt } => vec![
+            CoreInstruction::Subu { rd: *rd, rs: *rs, rt: *rt },
+            CoreInstruction::Sltu { rd: *rd, rs: Register::ZERO, rt: *rd },
+        ],
+        PseudoInstruction::Sge { rd, rs, rt } => vec![
+            CoreInstruction::Slt { rd: *rd, rs: *rs, rt: *rt },
+            CoreInstruction::Xori { rt: *rd, rs: *rd, imm: 1 },
+        ],
+        PseudoInstruction::Sgt { rd, rs, rt } => vec![
+            CoreInstruction::Slt { rd: *rd, rs: *rt, rt: *rs },
+        ],
+
+        _ => unreachable!("pseudo-instruction is expanded inline"),
+    }
+}
+
+/// expands an instruction whose immediate does not fit in its 16-bit field the way MARS does: the value is built in
+/// $at and the register form is used (a load or store adds the upper half of the offset to the base in $at)
+fn expand_immediate(core: &CoreInstruction) -> Vec<CoreInstruction> {
+    let with_at = |imm: u32, insn: CoreInstruction| {
+        let mut insns = load_at(imm);
+        insns.push(insn);
+        insns
+    };
+
+    match core {
+        CoreInstruction::Addi { rt, rs, imm } if !is_16_bit_signed(*imm) =>
+            with_at(*imm as u32, CoreInstruction::Add { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::Addiu { rt, rs, imm } if !is_16_bit_signed(*imm as i32) =>
+            with_at(*imm, CoreInstruction::Addu { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::Slti { rt, rs, imm } if !is_16_bit_signed(*imm) =>
+            with_at(*imm as u32, CoreInstruction::Slt { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::Sltiu { rt, rs, imm } if !is_16_bit_signed(*imm as i32) =>
+            with_at(*imm, CoreInstruction::Sltu { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::Andi { rt, rs, imm } if !is_16_bit_unsigned(*imm) =>
+            with_at(*imm, CoreInstruction::And { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::Ori { rt, rs, imm } if !is_16_bit_unsigned(*imm) =>
+            with_at(*imm, CoreInstruction::Or { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::Xori { rt, rs, imm } if !is_16_bit_unsigned(*imm) =>
+            with_at(*imm, CoreInstruction::Xor { rd: *rt, rs: *rs, rt: Register::AT }),
+        CoreInstruction::TrapImm { cond, rs, imm } if !is_16_bit_signed(*imm) =>
+            with_at(*imm as u32, CoreInstruction::Trap { cond: *cond, rs: *rs, rt: Register::AT, code: 0 }),
+
+        _ => {
+            let mut access = core.clone();
+            let Some((rs, imm)) = memory_operand(&mut access).filter(|(_, imm)| !is_16_bit_signed(**imm)) else {
+                return vec![access];
+            };
+
+            // the access sign-extends the low half, so the upper half is rounded up to make up for it
+            let (base, low) = (*rs, *imm as i16 as i32);
+            let high = (imm.wrapping_sub(low) as u32) >> 16;
+            (*rs, *imm) = (Register::AT, low);
+
+            vec![
+                CoreInstruction::Lui { rt: Register::AT, imm: high },
+                CoreInstruction::Addu { rd: Register::AT, rs: Register::AT, rt: base },
+                access,
+            ]
+        },
+    }
+}
+
+/// base register and offset of a load or store
+fn memory_operand(core: &mut CoreInstruction) -> Option<(&mut Register, &mut i32)> {
+    match core {
+        CoreInstruction::Lb { rs, imm, .. } | CoreInstruction::Lh { rs, imm, .. } | CoreInstruction::Lw { rs, imm, .. } |
+        CoreInstruction::Lbu { rs, imm, .. } | CoreInstruction::Lhu { rs, imm, .. } |
+        CoreInstruction::Lwl { rs, imm, .. } | CoreInstruction::Lwr { rs, imm, .. } |
+        CoreInstruction::Sb { rs, imm, .. } | CoreInstruction::Sh { rs, imm, .. } | CoreInstruction::Sw { rs, imm, .. } |
+        CoreInstruction::Swl { rs, imm, .. } | CoreInstruction::Swr { rs, imm, .. } |
+        CoreInstruction::Ll { rs, imm, .. } | CoreInstruction::Sc { rs, imm, .. } |
+        CoreInstruction::Lwc1 { rs, imm, .. } | CoreInstruction::Swc1 { rs, imm, .. } |
+        CoreInstruction::Ldc1 { rs, imm, .. } | CoreInstruction::Sdc1 { rs, imm, .. } => Some((rs, imm)),
+        _ => None,
+    }
+}
+
+/// expands a register-register branch (`beq`, `bne`, `blt` ... `bgeu`) using slt/sltu into $at
+fn expand_compare_branch(op: &str, rs: Register, rt: Register, label: &str) -> Vec<CoreInstruction> {
+    let label = label.to_string();
+
+    // (operands of the comparison, unsigned compare, branch when the comparison is true)
+    let (lhs, rhs, unsigned, when_set) = match op {
+        "beq" => return vec![CoreInstruction::Beq { rs, rt, label }],
+        "bne" => return vec![CoreInstruction::Bne { rs, rt, label }],
+        "blt" => (rs, rt, false, true),
+        "bgt" => (rt, rs, false, true),
+        "ble" => (rt, rs, false, false),
+        "bge" => (rs, rt, false, false),
+        "bltu" => (rs, rt, true, true),
+        "bgtu" => (rt, rs, true, true),
+        "bleu" => (rt, rs, true, false),
+        "bgeu" => (rs, rt, true, false),
+        _ => unreachable!("not a compare branch"),
+    };
+
+    let (rd, rs, rt) = (Register::AT, lhs, rhs);
+    let compare = if unsigned {
+        CoreInstruction::Sltu { rd, rs, rt }
+    } else {
+        CoreInstruction::Slt { rd, rs, rt }
+    };
+
+    let (rs, rt) = (Register::AT, Register::ZERO);
+    let branch = if when_set {
+        CoreInstruction::Bne { rs, rt, label }
+    } else {
+        CoreInstruction::Beq { rs, rt, label }
+    };
+
+    vec![compare, branch]
+}
+
+/// loads a 32-bit immediate into $at (one instruction when it fits in 16 signed bits)
+fn load_at(imm: u32) -> Vec<CoreInstruction> {
+    if is_16_bit_signed(imm as i32) {
+        vec![CoreInstruction::Addiu { rt: Register::AT, rs: Register::ZERO, imm }]
+    } else {
+        vec![
+            CoreInstruction::Lui { rt: Register::AT, imm: imm >> 16 },
+            CoreInstruction::Ori { rt: Register::AT, rs: Register::AT, imm: imm & 0xFFFF },
+        ]
+    }
+}
+
 pub fn is_16_bit_signed(value: i32) -> bool {
     value >= i16::MIN as i32 && value <= i16::MAX as i32
 }
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use crate::memory::{
+    DEFAULT_HEAP_BASE_ADDRESS, DEFAULT_STACK_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS,
+    DEFAULT_TEXT_BASE_ADDRESS, PAGE_MASK, PAGE_POWER, PAGE_SIZE, WORD_SIZE,
+};
+
+/// storage for the address space below MMIO (isolation, MMIO and ll/sc reservations are handled by `Memory`)
+pub trait MemoryBackend {
+    /// fills `buf` with the bytes starting at `address` (bytes that were never written read as zero)
+    fn read(&self, address: u32, buf: &mut [u8]);
+
+    /// writes `bytes` starting at `address`, allocating storage as needed
+    fn write(&mut self, address: u32, bytes: &[u8]);
+
+    /// returns true if `address` has storage behind it (the isolation snapshot only answers for these)
+    fn is_mapped(&self, address: u32) -> bool;
+
+    /// copies the backend (used to freeze the grader's inputs)
+    fn clone_box(&self) -> Box<dyn MemoryBackend>;
+
+    fn read_word(&self, address: u32) -> u32 {
+        let mut bytes = [0; WORD_SIZE];
+        self.read(address, &mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn write_word(&mut self, address: u32, value: u32) {
+        self.write(address, &value.to_le_bytes());
+    }
+}
+
+#[inline]
+fn page_index(addr: u32) -> u32 { addr >> PAGE_POWER }
+
+#[inline]
+fn page_offset(addr: u32) -> usize { (addr & PAGE_MASK) as usize }
+
+/// sparse 512-byte pages allocated on first write
+#[derive(Clone, Default)]
+pub struct PagedBackend {
+    pub pages: HashMap<u32, Box<[u8; PAGE_SIZE]>>,
+}
+
+impl PagedBackend {
+    pub fn new() -> Self {
+        let mut backend = Self::default();
+
+        // standard pages
+        for address in [DEFAULT_STACK_BASE_ADDRESS - PAGE_SIZE as u32, DEFAULT_HEAP_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS, DEFAULT_TEXT_BASE_ADDRESS] {
+            backend.pages.insert(page_index(address), Box::new([0; PAGE_SIZE]));
+        }
+        backend
+    }
+}
+
+impl MemoryBackend for PagedBackend {
+    fn read(&self, address: u32, buf: &mut [u8]) {
+        for (i, b) in buf.iter_mut().enumerate() {
+            let addr = address.wrapping_add(i as u32);
+            *b = self.pages.get(&page_index(addr)).map_or(0, |page| page[page_offset(addr)]);
+        }
+    }
+
+    fn write(&mut self, address: u32, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            let addr = address.wrapping_add(i as u32);
+            let page = self.pages.entry(page_index(addr)).or_insert_with(|| Box::new([0; PAGE_SIZE]));
+            page[page_offset(addr)] = *b;
+        }
+    }
+
+    fn is_mapped(&self, address: u32) -> bool {
+        self.pages.contains_key(&page_index(address))
+    }
+
+    fn clone_box(&self) -> Box<dyn MemoryBackend> {
+        Box::new(self.clone())
+    }
+
+    fn read_word(&self, address: u32) -> u32 {
+        let offset = page_offset(address);
+        match self.pages.get(&page_index(address)) {
+            Some(page) if offset + WORD_SIZE <= PAGE_SIZE => u32::from_le_bytes([page[offset], page[offset + 1], page[offset + 2], page[offset + 3]]),
+            _ => {
+                let mut bytes = [0; WORD_SIZE];
+                self.read(address, &mut bytes);
+                u32::from_le_bytes(bytes)
+            }
+        }
+    }
+}
+
+// smallest amount a segment grows by, so a run of pushes or stores does not reallocate every time
+const SEGMENT_CHUNK: usize = 4096;
+
+pub const TEXT_SEGMENT_SIZE: u32 = 0x0010_0000;
+pub const DATA_SEGMENT_SIZE: u32 = 0x0040_0000;
+pub const STACK_SEGMENT_SIZE: u32 = 0x0010_0000;
+
+/// a fixed region of the address space backed by one vector, which covers the part written so far
+/// (from `start` upwards, or from `end` downwards for the stack)
+#[derive(Clone)]
+struct Segment {
+    start: u32,
+    end: u64,
+    grows_down: bool,
+    data: Vec<u8>,
+}
+
+impl Segment {
+    fn up(start: u32, size: u32) -> Self {
+        Segment { start, end: start as u64 + size as u64, grows_down: false, data: Vec::new() }
+    }
+
+    fn down(end: u64, size: u32) -> Self {
+        Segment { start: (end - size as u64) as u32, end, grows_down: true, data: Vec::new() }
+    }
+
+    #[inline]
+    fn contains(&self, address: u32, len: usize) -> bool {
+        address >= self.start && address as u64 + len as u64 <= self.end
+    }
+
+    /// lowest address held by `data`
+    #[inline]
+    fn window_start(&self) -> u32 {
+        if self.grows_down { (self.end - self.data.len() as u64) as u32 } else { self.start }
+    }
+
+    /// index of `address` in `data` if the `len` bytes from it are all held
+    #[inline]
+    fn offset(&self, address: u32, len: usize) -> Option<usize> {
+        let offset = address.checked_sub(self.window_start())? as usize;
+        (offset + len <= self.data.len()).then_some(offset)
+    }
+
+    fn read(&self, address: u32, buf: &mut [u8]) {
+        if let Some(offset) = self.offset(address, buf.len()) {
+            buf.copy_from_slice(&self.data[offset..offset + buf.len()]);
+            return;
+        }
+
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = self.offset(address + i as u32, 1).map_or(0, |offset| self.data[offset]);
+        }
+    }
+
+    fn write(&mut self, address: u32, bytes: &[u8]) {
+        self.grow_to(address, bytes.len());
+        let offset = address as usize - self.window_start() as usize;
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// extends `data` (at least doubling it) so it covers the `len` bytes at `address`
+    fn grow_to(&mut self, address: u32, len: usize) {
+        let size = (self.end - self.start as u64) as usize;
+        let needed = if self.grows_down {
+            (self.end - address as u64) as usize
+        } else {
+            address as usize + len - self.start as usize
+        };
+
+        if needed <= self.data.len() {
+            return;
+        }
+
+        let new_len = needed.max(self.data.len() * 2).max(SEGMENT_CHUNK).min(size);
+        if self.grows_down {
+            let mut data = vec![0; new_len];
+            data[new_len - self.data.len()..].copy_from_slice(&self.data);
+            self.data = data;
+        } else {
+            self.data.resize(new_len, 0);
+        }
+    }
+}
+
+/// contiguous vectors for the text, data/heap and stack segments, with sparse pages for any other address
+#[derive(Clone)]
+pub struct ContiguousBackend {
+    segments: [Segment; 3],
+    fallback: PagedBackend,
+}
+
+impl Default for ContiguousBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContiguousBackend {
+    pub fn new() -> Self {
+        ContiguousBackend {
+            segments: [
+                Segment::up(DEFAULT_TEXT_BASE_ADDRESS, TEXT_SEGMENT_SIZE),
+                Segment::up(DEFAULT_STATIC_DATA_BASE_ADDRESS, DATA_SEGMENT_SIZE),
+                Segment::down(DEFAULT_STACK_BASE_ADDRESS as u64 + 1, STACK_SEGMENT_SIZE),
+            ],
+            fallback: PagedBackend::default(),
+        }
+    }
+
+    #[inline]
+    fn segment(&self, address: u32, len: usize) -> Option<&Segment> {
+        self.segments.iter().find(|s| s.contains(address, len))
+    }
+}
+
+impl MemoryBackend for ContiguousBackend {
+    fn read(&self, address: u32, buf: &mut [u8]) {
+        match self.segment(address, buf.len()) {
+            Some(segment) => segment.read(address, buf),
+            None if buf.len() > 1 => {
+                // straddles a segment boundary or lies in the sparse pages
+                for (i, b) in buf.iter_mut().enumerate() {
+                    self.read(address.wrapping_add(i as u32), std::slice::from_mut(b));
+                }
+            },
+            None => self.fallback.read(address, buf),
+        }
+    }
+
+    fn write(&mut self, address: u32, bytes: &[u8]) {
+        let len = bytes.len();
+        match self.segments.iter_mut().find(|s| s.contains(address, len)) {
+            Some(segment) => segment.write(address, bytes),
+            None if len > 1 => {
+                for (i, b) in bytes.iter().enumerate() {
+                    self.write(address.wrapping_add(i as u32), std::slice::from_ref(b));
+                }
+            },
+            None => self.fallback.write(address, bytes),
+        }
+    }
+
+    fn is_mapped(&self, address: u32) -> bool {
+        match self.segment(address, 1) {
+            Some(segment) => segment.offset(address, 1).is_some(),
+            None => self.fallback.is_mapped(address),
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn MemoryBackend> {
+        Box::new(self.clone())
+    }
+
+    #[inline]
+    fn read_word(&self, address: u32) -> u32 {
+        if let Some(segment) = self.segment(address, WORD_SIZE)
+            && let Some(offset) = segment.offset(address, WORD_SIZE) {
+            let bytes = &segment.data[offset..offset + WORD_SIZE];
+            return u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let mut bytes = [0; WORD_SIZE];
+        self.read(address, &mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+}
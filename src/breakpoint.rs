@@ -0,0 +1,100 @@
+use crate::expr::Expr;
+use crate::memory::Memory;
+
+/// piece of a log message: literal text or a `{expression}` replaced by its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogPart {
+    Text(String),
+    Value(Expr),
+}
+
+/// what reaching a breakpoint does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointAction {
+    /// the condition is false or the ignore count has not run out
+    Continue,
+    Stop,
+
+    /// a logpoint prints the message and keeps running
+    Log(String),
+}
+
+/// a line breakpoint with the optional settings of the IDE's breakpoint editor
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Breakpoint {
+    /// only counts as hit while the expression is non-zero
+    pub condition: Option<Expr>,
+
+    /// number of hits to pass before stopping (e.g. 99 stops on the 100th iteration)
+    pub ignore_count: u32,
+
+    /// prints this line to the console instead of stopping (a logpoint)
+    pub log_message: Option<Vec<LogPart>>,
+
+    /// times the breakpoint was reached with its condition holding
+    pub hits: u32,
+}
+
+impl Breakpoint {
+    /// builds a breakpoint from the editor's text fields (an empty condition or message is treated as none)
+    pub fn new(condition: Option<&str>, ignore_count: u32, log_message: Option<&str>) -> Result<Self, String> {
+        let condition = match condition.map(str::trim).filter(|c| !c.is_empty()) {
+            Some(text) => Some(Expr::parse(text).map_err(|e| format!("condition: {}", e))?),
+            None => None,
+        };
+        let log_message = match log_message.filter(|m| !m.is_empty()) {
+            Some(text) => Some(Self::parse_log_message(text).map_err(|e| format!("log message: {}", e))?),
+            None => None,
+        };
+
+        Ok(Breakpoint { condition, ignore_count, log_message, hits: 0 })
+    }
+
+    /// splits "i = {$t0}" into text and expressions
+    fn parse_log_message(text: &str) -> Result<Vec<LogPart>, String> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+
+        while let Some(open) = rest.find('{') {
+            let close = rest[open..].find('}').ok_or("missing '}'")? + open;
+            if open > 0 {
+                parts.push(LogPart::Text(rest[..open].to_string()));
+            }
+            parts.push(LogPart::Value(Expr::parse(&rest[open + 1..close])?));
+            rest = &rest[close + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(LogPart::Text(rest.to_string()));
+        }
+        Ok(parts)
+    }
+
+    /// returns true if there is no condition or it holds
+    pub fn condition_holds(&self, registers: &[u32; 32], memory: &mut Memory) -> bool {
+        self.condition.as_ref().is_none_or(|condition| condition.eval(registers, memory) != 0)
+    }
+
+    /// records that execution reached the breakpoint and decides what happens
+    pub fn hit(&mut self, registers: &[u32; 32], memory: &mut Memory) -> BreakpointAction {
+        if !self.condition_holds(registers, memory) {
+            return BreakpointAction::Continue;
+        }
+
+        self.hits += 1;
+        if self.hits <= self.ignore_count {
+            return BreakpointAction::Continue;
+        }
+
+        match &self.log_message {
+            Some(parts) => {
+                let message = parts.iter().map(|part| match part {
+                    LogPart::Text(text) => text.clone(),
+                    LogPart::Value(expr) => expr.eval(registers, memory).to_string(),
+                }).collect();
+                BreakpointAction::Log(message)
+            },
+            None => BreakpointAction::Stop,
+        }
+    }
+}
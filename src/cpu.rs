@@ -1,35 +1,151 @@
-use crate::instruction::CoreInstruction;
+use crate::breakpoint::{Breakpoint, BreakpointAction};
+use crate::console::{BufferedConsole, Console};
+use crate::disassembler;
+use crate::encoding::{decode, fp_register_number, REGISTER_NAMES};
+use crate::history::{History, RegisterFile, UndoEntry};
+use crate::instruction::{CoreInstruction, FpCondition, FpFormat, FpRegister, Register};
 use crate::memory::*;
 use crate::program::{EmuError, Program};
+use crate::segments::{Access, FaultKind};
+use crate::shadow::{ByteState, ShadowMemory, UninitCheck, UninitializedRead};
+use crate::watch::{Comparison, WatchTarget, Watchpoint};
 use crate::Snapshot;
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 // use crate::lexer::alert;
 
+// coprocessor 0 register numbers
+pub const CP0_BADVADDR: usize = 8;
+pub const CP0_STATUS: usize = 12;
+pub const CP0_CAUSE: usize = 13;
+pub const CP0_EPC: usize = 14;
+
+// Status interrupt enable and exception level bits (EXL is set while the exception handler runs)
+pub const STATUS_IE: u32 = 1 << 0;
+pub const STATUS_EXL: u32 = 1 << 1;
+
+// Cause branch delay bit and exception code field (bits 6..2)
+pub const CAUSE_BD: u32 = 1 << 31;
+pub const CAUSE_EXC_CODE_MASK: u32 = 0x1F << 2;
+
+// interrupt pending bits in Cause (and mask bits in Status), IP0-1 are software and IP2-7 hardware lines
+pub const INTERRUPT_MASK: u32 = 0xFF << 8;
+pub const HARDWARE_INTERRUPT_SHIFT: u32 = 10;
+
+// exception codes stored in the Cause register
+pub const EXC_INTERRUPT: u32 = 0;
+pub const EXC_ADDRESS_LOAD: u32 = 4;
+pub const EXC_ADDRESS_STORE: u32 = 5;
+pub const EXC_BREAKPOINT: u32 = 9;
+pub const EXC_RESERVED_INSTRUCTION: u32 = 10;
+pub const EXC_OVERFLOW: u32 = 12;
+pub const EXC_TRAP: u32 = 13;
+
+// FCSR bit of condition code 0 (codes 1-7 are stored in bits 25..31)
+pub const FCSR_CONDITION_0: u32 = 23;
+
+/// a call in progress, pushed by `jal`, `jalr` and taken `bgezal`/`bltzal` and popped by the jump back to its return address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// address the call returns to
+    pub return_address: u32,
+
+    /// register file at the call, to validate $sp and $fp on return
+    pub registers: [u32; 32],
+}
+
+// registers holding a value before the program runs ($zero, the pointers set at reset and the return address into the OS)
+const INITIALLY_DEFINED: u32 = 1 << Register::ZERO.0 | 1 << Register::GP.0 | 1 << Register::SP.0 | 1 << Register::FP.0 | 1 << Register::RA.0;
+
 pub struct CPU { 
     // processor state 
-    registers: HashMap<String, u32>,
+    registers: [u32; 32],
     
     // special registers that can't be directly accessed 
     pub pc: u32,
     lo: u32,
     hi: u32, 
 
-    // program + memory
-    program: Option<Program>,
+    // coprocessor 0 registers (only BadVAddr, Status, Cause and EPC are used)
+    cp0: [u32; 32],
+
+    // coprocessor 1 registers $f0-$f31 (raw bits, a double uses an even/odd pair with the low word in the even register)
+    fpr: [u32; 32],
+
+    // floating-point control/status register (only the condition codes are used)
+    fcsr: u32,
+
+    // program + memory (shared so `next` can borrow the current instruction while executing it)
+    program: Option<Rc<Program>>,
     pub memory: Memory,     
 
-    // line numbers of instructions containing breakpoints (indicated in the UI)
-    pub breakpoints: HashSet<usize>,
+    // breakpoints by the line number of their instruction (indicated in the UI)
+    pub breakpoints: HashMap<usize, Breakpoint>,
+
+    // registers and memory ranges that stop execution when accessed (see `add_watchpoint`)
+    pub watchpoints: Vec<Watchpoint>,
+
+    // id given to the next watchpoint
+    next_watchpoint_id: usize,
 
-    // stack to validate saved registers for procedure execution
-    pub validation_stack: Vec<HashMap<String, u32>>,
+    // calls in progress, innermost last (drives step over/out and validates saved registers on return)
+    pub call_stack: Vec<CallFrame>,
+
+    // frames the current instruction returned from, kept for the undo log while history is recorded
+    returned_calls: Vec<CallFrame>,
 
     // record the last memory read/write to update memory UI
     pub last_mem_access: Option<(u32, u32)>,
 
     // maximum number of instructions before halting
-    pub max_instructions: u64
+    pub max_instructions: u64,
+
+    // console used by the I/O syscalls
+    pub console: Box<dyn Console>,
+
+    // status code set by the exit syscalls (stops further execution)
+    pub exit_code: Option<i32>,
+
+    // fetch and decode each instruction word from memory instead of the assembled instruction list
+    // (stores into the text segment take effect on the next fetch)
+    pub fetch_from_memory: bool,
+
+    // fault on accesses outside the segment map or against a segment's permissions (off by default: older labs
+    // place their arrays at low addresses such as 4000)
+    pub check_segments: bool,
+
+    // fault on loads and stores between the program break and the end of the heap (memory `sbrk` has not handed out)
+    pub check_heap: bool,
+
+    // emulate branch delay slots: the instruction after a branch/jump always executes and jal links PC+8
+    pub delayed_branching: bool,
+
+    // target of a branch waiting for its delay slot instruction to execute
+    delay_slot_target: Option<u32>,
+
+    // index of this CPU when several harts share one memory (identifies its `ll` reservation)
+    pub hart_id: usize,
+
+    // how reads of registers and memory the program never wrote are reported (see `set_uninit_check`)
+    uninit_check: UninitCheck,
+
+    // one bit per register holding a defined value (all set while the check is off)
+    defined_registers: u32,
+
+    // the same for the floating-point registers (only the FP print services check them)
+    defined_fp_registers: u32,
+
+    // undefined registers read by the instruction being executed
+    undefined_reads: Cell<u32>,
+
+    // uninitialized reads found in warn mode
+    pub uninit_warnings: Vec<UninitializedRead>,
+
+    // undo log of the last instructions for `step_back` (its size is configuration, kept across resets)
+    pub history: History
 }
 
 impl CPU {
@@ -37,135 +153,271 @@ impl CPU {
     pub fn get_hi(&self) -> u32 { self.hi}
     pub fn new() -> Self {
         CPU { 
-            registers: Self::create_register_map(), 
+            registers: Self::initial_registers(), 
             pc: DEFAULT_TEXT_BASE_ADDRESS,  
             lo: 0,
             hi: 0,
+            cp0: [0; 32],
+            fpr: [0; 32],
+            fcsr: 0,
             program: None, 
             memory: Memory::new(),
-            breakpoints: HashSet::new(),
-            validation_stack: Vec::new(),
+            breakpoints: HashMap::new(),
+            watchpoints: Vec::new(),
+            next_watchpoint_id: 1,
+            call_stack: Vec::new(),
+            returned_calls: Vec::new(),
             last_mem_access: None,
-            max_instructions: 1_000_000
-        }
-    }
-
-    fn create_register_map() -> HashMap<String, u32> {
-        let mut registers: HashMap<String, u32> = HashMap::new();
-        // $pc should be modified by accessing `self.pc`
-
-        // general purpose
-        registers.insert("$zero".to_string(), 0);
-        registers.insert("$at".to_string(), 0);
-        registers.insert("$v0".to_string(), 0);
-        registers.insert("$v1".to_string(), 0);
-        registers.insert("$a0".to_string(), 0);
-        registers.insert("$a1".to_string(), 0);
-        registers.insert("$a2".to_string(), 0);
-        registers.insert("$a3".to_string(), 0);
-        registers.insert("$t0".to_string(), 0);
-        registers.insert("$t1".to_string(), 0);
-        registers.insert("$t2".to_string(), 0);
-        registers.insert("$t3".to_string(), 0);
-        registers.insert("$t4".to_string(), 0);
-        registers.insert("$t5".to_string(), 0);
-        registers.insert("$t6".to_string(), 0);
-        registers.insert("$t7".to_string(), 0);
-        registers.insert("$s0".to_string(), 0);
-        registers.insert("$s1".to_string(), 0);
-        registers.insert("$s2".to_string(), 0);
-        registers.insert("$s3".to_string(), 0);
-        registers.insert("$s4".to_string(), 0);
-        registers.insert("$s5".to_string(), 0);
-        registers.insert("$s6".to_string(), 0);
-        registers.insert("$s7".to_string(), 0);
-        registers.insert("$t8".to_string(), 0);
-        registers.insert("$t9".to_string(), 0);
-        registers.insert("$k0".to_string(), 0);
-        registers.insert("$k1".to_string(), 0);
-        registers.insert("$ra".to_string(), 0);
-
-        // special
-        registers.insert("$gp".to_string(), DEFAULT_STATIC_DATA_BASE_ADDRESS);
-        registers.insert("$sp".to_string(), DEFAULT_STACK_POINTER); 
-        registers.insert("$fp".to_string(), DEFAULT_STACK_BASE_ADDRESS); 
+            max_instructions: 1_000_000,
+            console: Box::new(BufferedConsole::new()),
+            exit_code: None,
+            fetch_from_memory: false,
+            check_segments: false,
+            check_heap: false,
+            delayed_branching: false,
+            delay_slot_target: None,
+            hart_id: 0,
+            uninit_check: UninitCheck::Off,
+            defined_registers: u32::MAX,
+            defined_fp_registers: u32::MAX,
+            undefined_reads: Cell::new(0),
+            uninit_warnings: Vec::new(),
+            history: History::new(0)
+        }
+    }
+
+    /// register file at reset ($gp, $sp and $fp point into their segments, everything else is 0)
+    fn initial_registers() -> [u32; 32] {
+        let mut registers = [0; 32];
+
+        registers[Register::GP.index()] = DEFAULT_STATIC_DATA_BASE_ADDRESS;
+        registers[Register::SP.index()] = DEFAULT_STACK_POINTER;
+        registers[Register::FP.index()] = DEFAULT_STACK_BASE_ADDRESS;
 
         registers
     }
 
-    /// returns the value of a register as a 32-bit unsigned integer
+    /// returns the value of a register as a 32-bit unsigned integer (0 for an unknown name)
     pub fn get_reg(&self, name: &str) -> u32 {
-        *self.registers.get(name).unwrap_or(&0)
+        Register::from_name(name).map_or(0, |r| self.registers[r.index()])
     }
 
-    /// sets a register value to a 32-bit unsigned integer 
+    /// sets a register value to a 32-bit unsigned integer (unknown names and $zero are ignored)
     pub fn set_reg(&mut self, name: &str, value: u32) {
-        self.registers.insert(name.to_string(), value);
+        if let Some(r) = Register::from_name(name) {
+            self.write_reg(r, value);
+        }
+    }
+
+    /// returns the value of a register resolved by the parser
+    pub fn read_reg(&self, register: Register) -> u32 {
+        let bit = 1 << register.0;
+        if self.defined_registers & bit == 0 {
+            self.undefined_reads.set(self.undefined_reads.get() | bit);
+        }
+        self.registers[register.index()]
+    }
+
+    /// writes a register ($zero is hardwired, so writes to it are discarded)
+    pub fn write_reg(&mut self, register: Register, value: u32) {
+        if register != Register::ZERO {
+            self.registers[register.index()] = value;
+            self.defined_registers |= 1 << register.0;
+        }
+    }
+
+    /// returns the value of a coprocessor 0 register
+    pub fn get_cp0(&self, reg: usize) -> u32 {
+        self.cp0[reg]
+    }
+
+    /// returns the raw bits of a floating-point register (e.g. "$f0")
+    pub fn get_fpr(&self, name: &str) -> u32 {
+        fp_register_number(name).map_or(0, |n| self.fpr[n as usize])
+    }
+
+    /// sets the raw bits of a floating-point register
+    pub fn set_fpr(&mut self, name: &str, value: u32) {
+        if let Some(n) = fp_register_number(name) {
+            self.write_fpr(n as usize, value);
+        }
+    }
+
+    /// returns a floating-point register as a single precision value
+    pub fn get_float(&self, name: &str) -> f32 {
+        f32::from_bits(self.get_fpr(name))
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.set_fpr(name, value.to_bits());
+    }
+
+    /// returns the register pair starting at `name` as a double precision value
+    pub fn get_double(&self, name: &str) -> f64 {
+        self.read_double(FpRegister::from_name(name).unwrap_or(FpRegister(0)))
+    }
+
+    pub fn set_double(&mut self, name: &str, value: f64) {
+        self.write_double(FpRegister::from_name(name).unwrap_or(FpRegister(0)), value);
+    }
+
+    /// returns the register pair starting at a register resolved by the parser as a double
+    fn read_double(&self, register: FpRegister) -> f64 {
+        let n = register.index();
+        let bits = ((self.fpr[(n + 1) % 32] as u64) << 32) | self.fpr[n] as u64;
+        f64::from_bits(bits)
+    }
+
+    fn write_double(&mut self, register: FpRegister, value: f64) {
+        let n = register.index();
+        let bits = value.to_bits();
+        self.write_fpr(n, bits as u32);
+        self.write_fpr((n + 1) % 32, (bits >> 32) as u32);
+    }
+
+    /// writes the raw bits of a floating-point register by number, marking it defined
+    fn write_fpr(&mut self, n: usize, value: u32) {
+        self.fpr[n] = value;
+        self.defined_fp_registers |= 1 << n;
+    }
+
+    /// reports an uninitialized read of $f12 (and $f13 for a double) by the FP print services
+    fn check_fp_argument(&mut self, double: bool) -> Result<(), EmuError> {
+        let bits = if double { 0b11 } else { 0b01 } << 12;
+        if self.defined_fp_registers & bits != bits {
+            self.report_uninitialized("$f12".to_string(), self.pc)?;
+        }
+        Ok(())
+    }
+
+    /// returns the floating-point control/status register
+    pub fn get_fcsr(&self) -> u32 {
+        self.fcsr
+    }
+
+    /// returns the value of floating-point condition code `cc` (0-7) set by the compare instructions
+    pub fn fp_condition(&self, cc: u32) -> bool {
+        self.fcsr & Self::fp_condition_bit(cc) != 0
+    }
+
+    fn set_fp_condition(&mut self, cc: u32, value: bool) {
+        let bit = Self::fp_condition_bit(cc);
+        if value { self.fcsr |= bit } else { self.fcsr &= !bit }
+    }
+
+    fn fp_condition_bit(cc: u32) -> u32 {
+        match cc & 0x7 {
+            0 => 1 << FCSR_CONDITION_0,
+            n => 1 << (24 + n),
+        }
+    }
+
+    /// reads a single or double precision operand (widened to f64)
+    fn read_fp(&self, fmt: FpFormat, register: FpRegister) -> f64 {
+        let bits = self.fpr[register.index()];
+
+        match fmt {
+            FpFormat::S => f32::from_bits(bits) as f64,
+            FpFormat::D => self.read_double(register),
+            FpFormat::W => bits as i32 as f64,
+        }
+    }
+
+    /// writes a single or double precision result (rounded to f32 for single precision)
+    fn write_fp(&mut self, fmt: FpFormat, register: FpRegister, value: f64) {
+        match fmt {
+            FpFormat::S => self.write_fpr(register.index(), (value as f32).to_bits()),
+            FpFormat::D => self.write_double(register, value),
+
+            // out of range and NaN convert to the MIPS default result 2^31-1
+            FpFormat::W => {
+                let rounded = value.round_ties_even();
+                let word = if rounded.is_nan() || rounded < i32::MIN as f64 || rounded > i32::MAX as f64 {
+                    i32::MAX
+                } else {
+                    rounded as i32
+                };
+                self.write_fpr(register.index(), word as u32);
+            }
+        }
+    }
+
+    /// computes F[fd] = F[fs] op F[ft] (single precision results are rounded once, so computing in f64 is exact)
+    fn fp_arith(&mut self, fmt: FpFormat, fd: FpRegister, fs: FpRegister, ft: FpRegister, op: fn(f64, f64) -> f64) {
+        let result = op(self.read_fp(fmt, fs), self.read_fp(fmt, ft));
+        self.write_fp(fmt, fd, result);
     }
 
     pub fn load_program(&mut self, program: Program) {
-        self.program = Some(program);
+        self.program = Some(Rc::new(program));
         self.pc = DEFAULT_TEXT_BASE_ADDRESS;
+        self.delay_slot_target = None;
     }
 
     pub fn execute(&mut self, insn: &CoreInstruction) -> Result<(), EmuError> {
-        let mut is_branch = false;
+        // target address of a taken branch or jump
+        let mut branch_target: Option<u32> = None;
         
         // handle instruction based on type
         match insn {
             CoreInstruction::Add { rd, rs, rt } => {
-                let r1 = self.get_reg(rs) as i32; 
-                let r2 = self.get_reg(rt) as i32;
+                let r1 = self.read_reg(*rs) as i32; 
+                let r2 = self.read_reg(*rt) as i32;
                 
-                self.set_reg(rd, r1.wrapping_add(r2) as u32);
+                // signed overflow traps and leaves rd unchanged (addu wraps instead)
+                let result = r1.checked_add(r2).ok_or_else(|| self.overflow_error())?;
+                self.write_reg(*rd, result as u32);
             },
 
             CoreInstruction::Addi { rt, rs, imm } => {
-                let r = self.get_reg(rs) as i32;
-                self.set_reg(rt, r.wrapping_add(*imm) as u32);
+                let r = self.read_reg(*rs) as i32;
+                let result = r.checked_add(*imm).ok_or_else(|| self.overflow_error())?;
+                self.write_reg(*rt, result as u32);
             },
 
             CoreInstruction::Addiu { rt, rs, imm } => {
-                let r = self.get_reg(rs) as i32;
-                self.set_reg(rt, r.wrapping_add(*imm as i32) as u32);
+                let r = self.read_reg(*rs) as i32;
+                self.write_reg(*rt, r.wrapping_add(*imm as i32) as u32);
             },
 
             CoreInstruction::Addu { rd, rs, rt } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
-                self.set_reg(rd,r1.wrapping_add(r2));
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
+                self.write_reg(*rd,r1.wrapping_add(r2));
             },   
 
             CoreInstruction::Sub { rd, rs, rt } => {
-                let r1 = self.get_reg(rs) as i32;
-                let r2 = self.get_reg(rt) as i32;
+                let r1 = self.read_reg(*rs) as i32;
+                let r2 = self.read_reg(*rt) as i32;
 
-                self.set_reg(rd, r1.wrapping_sub(r2) as u32);
+                let result = r1.checked_sub(r2).ok_or_else(|| self.overflow_error())?;
+                self.write_reg(*rd, result as u32);
             },
 
             CoreInstruction::Subu { rd, rs, rt } => {
-                let r1 = self.get_reg(rs) as u32;
-                let r2 = self.get_reg(rt) as u32;
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
 
-                self.set_reg(rd, r1.wrapping_sub(r2) as u32);
+                self.write_reg(*rd, r1.wrapping_sub(r2));
             },
 
             CoreInstruction::Lw { rt, rs, imm } => {
-                let base = self.get_reg(rs);
+                let base = self.read_reg(*rs);
                 let addr = base.wrapping_add(*imm as u32);
 
                 if addr % 4 != 0 {
                     return Err(EmuError::UnalignedAccess(addr));
                 }
 
+                self.check_access(addr, 4, Access::Load)?;
                 let val = self.memory.load_word(addr);   // load 4 bytes starting at addr
-                self.set_reg(rt, val as u32);
+                self.write_reg(*rt, val as u32);
                 
                 self.last_mem_access = Some((addr, 4));
             },
 
             CoreInstruction::Sw { rs, rt, imm } => {
-                let base = self.get_reg(rs);
+                let base = self.read_reg(*rs);
                 let addr = base.wrapping_add(*imm as u32);
 
                 // would only allow multiples of 4 to be input numbers 
@@ -173,107 +425,175 @@ impl CPU {
                    return Err(EmuError::UnalignedAccess(addr)); 
                 }
 
-                let val = self.get_reg(rt) as i32;
+                self.check_access(addr, 4, Access::Store)?;
+                let val = self.read_reg(*rt) as i32;
                 self.memory.set_word(addr, val);
 
                 self.last_mem_access = Some((addr, 4));
             },
 
             CoreInstruction::Lui { rt, imm } => {
-                self.set_reg(rt, imm << 16);
+                self.write_reg(*rt, imm << 16);
             },
 
             CoreInstruction::Lb { rt, rs, imm } => {
-                let base = self.get_reg(rs);
+                let base = self.read_reg(*rs);
                 let addr = base.wrapping_add(*imm as u32);
+                self.check_access(addr, 1, Access::Load)?;
                 let val = self.memory.load_byte(addr);
                 
-                self.set_reg(rt, val as u32);
+                self.write_reg(*rt, val as u32);
                 self.last_mem_access = Some((addr, 1));
             },
 
             CoreInstruction::Sb { rs, rt, imm } => {
-                let base = self.get_reg(rs);
+                let base = self.read_reg(*rs);
                 let addr = base.wrapping_add(*imm as u32);
-                let val = self.get_reg(rt)as i8;
+                let val = self.read_reg(*rt)as i8;
 
+                self.check_access(addr, 1, Access::Store)?;
                 self.memory.set_byte(addr, val);
                 self.last_mem_access = Some((addr, 1));
             },
 
             CoreInstruction::Lh { rt, rs, imm } => {
-                let base = self.get_reg(rs);
+                let base = self.read_reg(*rs);
                 let addr = base.wrapping_add(*imm as u32);
+                self.check_access(addr, 2, Access::Load)?;
                 let val = self.memory.load_halfword(addr);
 
-                self.set_reg(rt, val as u32);
+                self.write_reg(*rt, val as u32);
                 self.last_mem_access = Some((addr, 2));
             },
 
             CoreInstruction::Sh { rt, rs, imm } => {
-                let base = self.get_reg(rs);
+                let base = self.read_reg(*rs);
                 let addr = base.wrapping_add(*imm as u32);
-                let val = self.get_reg(rt)as i16;
+                let val = self.read_reg(*rt)as i16;
 
+                self.check_access(addr, 2, Access::Store)?;
                 self.memory.set_halfword(addr, val);
                 self.last_mem_access = Some((addr, 2));
             },
 
+            CoreInstruction::Lbu { rt, rs, imm } => {
+                let base = self.read_reg(*rs);
+                let addr = base.wrapping_add(*imm as u32);
+                self.check_access(addr, 1, Access::Load)?;
+                let val = self.memory.load_byte(addr) as u8;
+
+                self.write_reg(*rt, val as u32);
+                self.last_mem_access = Some((addr, 1));
+            },
+
+            CoreInstruction::Lhu { rt, rs, imm } => {
+                let base = self.read_reg(*rs);
+                let addr = base.wrapping_add(*imm as u32);
+                self.check_access(addr, 2, Access::Load)?;
+                let val = self.memory.load_halfword(addr) as u16;
+
+                self.write_reg(*rt, val as u32);
+                self.last_mem_access = Some((addr, 2));
+            },
+
+            // unaligned word access (little-endian): lwl/swl handle the bytes up to addr in the word containing
+            // addr, lwr/swr the bytes from addr, so `lwr $t0, 0($a0)` + `lwl $t0, 3($a0)` loads any word
+            CoreInstruction::Lwl { rt, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                self.check_access(addr & !3, 4, Access::Load)?;
+                let word = self.memory.load_word(addr & !3) as u32;
+                let shift = 8 * (3 - (addr & 3));
+
+                let keep = self.read_reg(*rt) & ((1u64 << shift) - 1) as u32;
+                self.write_reg(*rt, (word << shift) | keep);
+                self.last_mem_access = Some((addr & !3, 4));
+            },
+
+            CoreInstruction::Lwr { rt, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                self.check_access(addr & !3, 4, Access::Load)?;
+                let word = self.memory.load_word(addr & !3) as u32;
+                let shift = 8 * (addr & 3);
+
+                let keep = self.read_reg(*rt) & !(u32::MAX >> shift);
+                self.write_reg(*rt, (word >> shift) | keep);
+                self.last_mem_access = Some((addr & !3, 4));
+            },
+
+            CoreInstruction::Swl { rt, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                self.check_access(addr & !3, 4, Access::Store)?;
+                let word = self.memory.load_word(addr & !3) as u32;
+                let shift = 8 * (3 - (addr & 3));
+
+                let mask = u32::MAX >> shift;
+                let merged = (word & !mask) | (self.read_reg(*rt) >> shift);
+                self.memory.set_word(addr & !3, merged as i32);
+                self.last_mem_access = Some((addr & !3, 4));
+            },
+
+            CoreInstruction::Swr { rt, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                self.check_access(addr & !3, 4, Access::Store)?;
+                let word = self.memory.load_word(addr & !3) as u32;
+                let shift = 8 * (addr & 3);
+
+                let keep = word & ((1u64 << shift) - 1) as u32;
+                let merged = keep | (self.read_reg(*rt) << shift);
+                self.memory.set_word(addr & !3, merged as i32);
+                self.last_mem_access = Some((addr & !3, 4));
+            },
+
             CoreInstruction::J { label } => {
                 let target = self.program.as_ref()
                     .unwrap()
                     .get_label_address(label)
                     .ok_or(EmuError::UndefinedLabel(label.clone()))?;
 
-                self.pc = target;
-                is_branch = true;
+                branch_target = Some(target);
 
                 // alert(format!("Jumping to address: 0x{:08X}", target).as_str());
             },
 
             CoreInstruction::Jal { label } => {
-                // create snapshot of registers for stack validation
-                let mut snapshot = HashMap::new();
-                snapshot.insert("$sp".to_string(), self.get_reg("$sp"));
-                snapshot.insert("$fp".to_string(), self.get_reg("$fp"));
+                // record the call (with a snapshot of registers for stack validation)
+                self.push_call_frame();
 
-                for i in 0..=7 {
-                    let reg_name = format!("$s{}", i);
-                    snapshot.insert(reg_name.clone(), self.get_reg(&reg_name));
-                }
-                self.validation_stack.push(snapshot);
-
-                // jump and set $ra register
-                let return_addr = self.pc + 4;
-                self.set_reg("$ra", return_addr);
+                // jump and set $ra register (skipping the delay slot when it is emulated)
+                let return_addr = self.return_address();
+                self.write_reg(Register::RA, return_addr);
 
                 let target = self.program.as_ref()
                     .unwrap()
                     .get_label_address(label)
                     .unwrap();
 
-                self.pc = target;
-                is_branch = true; 
+                branch_target = Some(target);
             },
 
             CoreInstruction::Jr { rs } => {
-                // validate the snapshop of stack registers from our snapshot
-                if rs == "$ra" {
-                    if let Some(snapshot) = self.validation_stack.pop() {
-                        
+                let target = self.read_reg(*rs);
+
+                // a jump to the return address of a call in progress returns from it (and from any calls it made that
+                // never returned) once the snapshot of stack registers is validated and the target checked
+                let returned_depth = self.call_stack.iter().rposition(|frame| frame.return_address == target);
+                if let Some(depth) = returned_depth {
+                    let snapshot = self.call_stack[depth].registers;
+
+                    {
                         // check $sp
-                        let current_sp = self.get_reg("$sp");
-                        if current_sp != snapshot["$sp"] {
+                        let (current_sp, saved_sp) = (self.read_reg(Register::SP), snapshot[Register::SP.index()]);
+                        if current_sp != saved_sp {
                             return Err(EmuError::CallingConventionViolation(
-                                format!("stack pointer $sp not restored. Expected 0x{:x}, found 0x{:x}", snapshot["$sp"], current_sp)
+                                format!("stack pointer $sp not restored. Expected 0x{:x}, found 0x{:x}", saved_sp, current_sp)
                             ));
                         }
                         
                         // check $fp
-                        let current_fp = self.get_reg("$fp");
-                        if current_fp != snapshot["$fp"] {
+                        let (current_fp, saved_fp) = (self.read_reg(Register::FP), snapshot[Register::FP.index()]);
+                        if current_fp != saved_fp {
                             return Err(EmuError::CallingConventionViolation(
-                                format!("frame pointer $fp not restored. Expected 0x{:x}, found 0x{:x}", snapshot["$fp"], current_fp)
+                                format!("frame pointer $fp not restored. Expected 0x{:x}, found 0x{:x}", saved_fp, current_fp)
                             ));
                         }
                         
@@ -281,10 +601,10 @@ impl CPU {
                         // usually registers like $s0 must be preserved after a function call
 
                         // check $s0 through $s7 registers
-                        // for i in 0..=7 {
-                        //     let reg_name = format!("$s{}", i);
-                        //     let current_val = self.get_reg(&reg_name);
-                        //     let snapshot_val = snapshot[&reg_name];
+                        // for i in 16..=23 {
+                        //     let reg_name = Register(i).name();
+                        //     let current_val = self.registers[i as usize];
+                        //     let snapshot_val = snapshot[i as usize];
 
                         //     if current_val != snapshot_val {
                         //         return Err(EmuError::CallingConventionViolation(
@@ -294,60 +614,109 @@ impl CPU {
                         // }
                     }
                 }
-
-                let target = self.get_reg(rs);
                 
                 // check if 4-byte aligned
                 if target % 4 != 0 {
                     return Err(EmuError::UnalignedAccess(target));
                 }
             
-                // check if $pc maps to some instruction in the array 
-                if let Some(program) = &self.program {
-                    if program.pc_to_index(target).is_none() {
-                        return Err(EmuError::InvalidJump(target));
-                    }
+                // check if $pc maps to some instruction in the array (or executable memory in fetch mode)
+                if !self.is_code(target) {
+                    return Err(EmuError::InvalidJump(target));
+                }
+
+                // a faulting return leaves the call stack as it was for step out and the stack view
+                if let Some(depth) = returned_depth {
+                    self.return_to(depth);
                 }
             
-                self.pc = target;
-                is_branch = true;
+                branch_target = Some(target);
+            },
+
+            CoreInstruction::Jalr { rd, rs } => {
+                // read the target before linking in case rd == rs
+                let target = self.read_reg(*rs);
+
+                if !target.is_multiple_of(4) {
+                    return Err(EmuError::UnalignedAccess(target));
+                }
+
+                if !self.is_code(target) {
+                    return Err(EmuError::InvalidJump(target));
+                }
+
+                self.push_call_frame();
+                let return_addr = self.return_address();
+                self.write_reg(*rd, return_addr);
+
+                branch_target = Some(target);
+            },
+
+            CoreInstruction::Bgez { rs, label } | CoreInstruction::Bgtz { rs, label } |
+            CoreInstruction::Blez { rs, label } | CoreInstruction::Bltz { rs, label } => {
+                let r = self.read_reg(*rs) as i32;
+
+                let taken = match insn {
+                    CoreInstruction::Bgez { .. } => r >= 0,
+                    CoreInstruction::Bgtz { .. } => r > 0,
+                    CoreInstruction::Blez { .. } => r <= 0,
+                    _ => r < 0,
+                };
+
+                if taken {
+                    branch_target = Some(self.label_target(label)?);
+                }
+            },
+
+            CoreInstruction::Bgezal { rs, label } | CoreInstruction::Bltzal { rs, label } => {
+                let r = self.read_reg(*rs) as i32;
+                let taken = if matches!(insn, CoreInstruction::Bgezal { .. }) { r >= 0 } else { r < 0 };
+
+                // $ra is written whether or not the branch is taken
+                let return_addr = self.return_address();
+                self.write_reg(Register::RA, return_addr);
+
+                if taken {
+                    branch_target = Some(self.label_target(label)?);
+                    self.push_call_frame();
+                }
             },
 
             CoreInstruction::Or {rd, rs, rt } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
-                self.set_reg(rd, r1 | r2);
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
+                self.write_reg(*rd, r1 | r2);
             },
 
             CoreInstruction::Ori {rt, rs, imm} => {
-                let r = self.get_reg(rs);
-                self.set_reg(rt, r | *imm);
+                let r = self.read_reg(*rs);
+                self.write_reg(*rt, r | *imm);
             },
 
             CoreInstruction::And { rd, rs, rt } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
-                self.set_reg(rd, r1 & r2);
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
+                self.write_reg(*rd, r1 & r2);
             },
 
             CoreInstruction::Xor { rd, rs, rt } => {
-                let val = self.get_reg(rs) ^ self.get_reg(rt);
-                self.set_reg(rd, val)
+                let val = self.read_reg(*rs) ^ self.read_reg(*rt);
+                self.write_reg(*rd, val)
             },
 
             CoreInstruction::Xori { rt, rs, imm } => {
-              let val = self.get_reg(rs) ^ imm;
-              self.set_reg(rt, val)
+              let val = self.read_reg(*rs) ^ imm;
+              self.write_reg(*rt, val)
             },
 
             CoreInstruction::Andi { rt, rs, imm } => {
-                let r = self.get_reg(rs);
-                self.set_reg(rt, r & *imm);
+                let r = self.read_reg(*rs);
+                self.write_reg(*rt, r & *imm);
             },
 
             CoreInstruction::Beq { rs, rt, label } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
 
                 if r1 == r2 {
                     let target = self.program.as_ref()
@@ -355,14 +724,13 @@ impl CPU {
                         .get_label_address(label)
                         .ok_or(EmuError::UndefinedLabel(label.clone()))?;
                     
-                    self.pc = target;
-                    is_branch = true;
+                    branch_target = Some(target);
                 }
             },
 
             CoreInstruction::Bne { rs, rt, label } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
 
                 if r1 != r2 {
                     let target = self.program.as_ref()
@@ -370,41 +738,40 @@ impl CPU {
                         .get_label_address(label)
                         .ok_or(EmuError::UndefinedLabel(label.clone()))?;
                     
-                    self.pc = target;
-                    is_branch = true;
+                    branch_target = Some(target);
                 }
             },
 
             CoreInstruction::Slt { rd, rs, rt } => {
-                let r1 = self.get_reg(rs) as i32;
-                let r2 = self.get_reg(rt) as i32;
+                let r1 = self.read_reg(*rs) as i32;
+                let r2 = self.read_reg(*rt) as i32;
 
                 if r1 < r2 {
-                    self.set_reg(rd, 1);
+                    self.write_reg(*rd, 1);
                 } else {
-                    self.set_reg(rd, 0);
+                    self.write_reg(*rd, 0);
                 }
             },
 
             CoreInstruction::Slti {rt, rs, imm } => {
-                let r = self.get_reg(rs) as i32;
-                self.set_reg(rt, if r< *imm { 1 } else {0});
+                let r = self.read_reg(*rs) as i32;
+                self.write_reg(*rt, if r< *imm { 1 } else {0});
             },
 
             CoreInstruction::Sltu {rd, rs, rt } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
-                self.set_reg(rd, if r1<r2 { 1 } else {0});
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
+                self.write_reg(*rd, if r1<r2 { 1 } else {0});
             },
 
             CoreInstruction::Sltiu {rt, rs, imm } => {
-                let r = self.get_reg(rs);
-                self.set_reg(rt, if r< (*imm as u32) { 1 } else {0});
+                let r = self.read_reg(*rs);
+                self.write_reg(*rt, if r< *imm { 1 } else {0});
             },
             
             CoreInstruction::Mult { rs, rt } => {
-                let r1 = self.get_reg(rs) as i32 as i64;
-                let r2 = self.get_reg(rt) as i32 as i64;
+                let r1 = self.read_reg(*rs) as i32 as i64;
+                let r2 = self.read_reg(*rt) as i32 as i64;
                 
                 let result = r1.wrapping_mul(r2);
 
@@ -416,8 +783,8 @@ impl CPU {
             // this implementation treats the operands as unsigned instead of signed
             // this matches ZyBooks/ZyLabs but is "technically" incorrect
             // CoreInstruction::Mult { rs, rt } => {
-            //     let r1 = self.get_reg(rs) as u64;
-            //     let r2 = self.get_reg(rt) as u64;
+            //     let r1 = self.read_reg(*rs) as u64;
+            //     let r2 = self.read_reg(*rt) as u64;
                 
             //     let result = r1.wrapping_mul(r2);
 
@@ -426,16 +793,16 @@ impl CPU {
             // },
 
             CoreInstruction::Mfhi { rd } => {
-                self.set_reg(rd, self.hi);
+                self.write_reg(*rd, self.hi);
             },
 
             CoreInstruction::Mflo { rd } => {
-                self.set_reg(rd, self.lo);
+                self.write_reg(*rd, self.lo);
             },
 
             CoreInstruction::Div {rs, rt} => {
-                let dividend = self.get_reg(rs) as i32;
-                let divisor = self.get_reg(rt) as i32;
+                let dividend = self.read_reg(*rs) as i32;
+                let divisor = self.read_reg(*rt) as i32;
 
                 if divisor == 0 {
                     return Err(EmuError::DivideByZero); // add error to emuerro
@@ -446,31 +813,122 @@ impl CPU {
             },
 
             CoreInstruction::Nor{ rd, rs, rt } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
 
-                self.set_reg(rd, !(r1 | r2));
+                self.write_reg(*rd, !(r1 | r2));
             },
 
             CoreInstruction::Sll {rd, rt, sa } => {
-                let v = self.get_reg(&rt);
-                self.set_reg(&rd, v << sa);
+                let v = self.read_reg(*rt);
+                self.write_reg(*rd, v << sa);
             },
 
             CoreInstruction::Srl { rd, rt, sa} =>{
-                let v = self.get_reg(&rt);
-                self.set_reg(&rd, v >> sa);
+                let v = self.read_reg(*rt);
+                self.write_reg(*rd, v >> sa);
             },
 
             CoreInstruction::Sra { rd, rt, imm } => {
-                let v = self.get_reg(rt) as i32;        // interpret as signed
+                let v = self.read_reg(*rt) as i32;        // interpret as signed
                 let result = v >> imm;                       // arithmetic shift
-                self.set_reg(rd, result as u32);      // store back as u32
+                self.write_reg(*rd, result as u32);      // store back as u32
+            },
+
+            CoreInstruction::Mul { rd, rs, rt } => {
+                let r1 = self.read_reg(*rs) as i32;
+                let r2 = self.read_reg(*rt) as i32;
+
+                // only the low word is kept, Hi and Lo are left unchanged
+                self.write_reg(*rd, r1.wrapping_mul(r2) as u32);
+            },
+
+            CoreInstruction::Madd { rs, rt } | CoreInstruction::Msub { rs, rt } => {
+                let product = (self.read_reg(*rs) as i32 as i64) * (self.read_reg(*rt) as i32 as i64);
+                let acc = self.hi_lo() as i64;
+
+                let result = if matches!(insn, CoreInstruction::Madd { .. }) {
+                    acc.wrapping_add(product)
+                } else {
+                    acc.wrapping_sub(product)
+                };
+                self.set_hi_lo(result as u64);
+            },
+
+            CoreInstruction::Maddu { rs, rt } | CoreInstruction::Msubu { rs, rt } => {
+                let product = (self.read_reg(*rs) as u64) * (self.read_reg(*rt) as u64);
+                let acc = self.hi_lo();
+
+                let result = if matches!(insn, CoreInstruction::Maddu { .. }) {
+                    acc.wrapping_add(product)
+                } else {
+                    acc.wrapping_sub(product)
+                };
+                self.set_hi_lo(result);
+            },
+
+            CoreInstruction::Clz { rd, rs } => {
+                let v = self.read_reg(*rs);
+                self.write_reg(*rd, v.leading_zeros());
+            },
+
+            CoreInstruction::Clo { rd, rs } => {
+                let v = self.read_reg(*rs);
+                self.write_reg(*rd, v.leading_ones());
+            },
+
+            CoreInstruction::Movn { rd, rs, rt } => {
+                if self.read_reg(*rt) != 0 {
+                    self.write_reg(*rd, self.read_reg(*rs));
+                }
+            },
+
+            CoreInstruction::Movz { rd, rs, rt } => {
+                if self.read_reg(*rt) == 0 {
+                    self.write_reg(*rd, self.read_reg(*rs));
+                }
+            },
+
+            CoreInstruction::Seb { rd, rt } => {
+                let v = self.read_reg(*rt) as u8 as i8;
+                self.write_reg(*rd, v as u32);
+            },
+
+            CoreInstruction::Seh { rd, rt } => {
+                let v = self.read_reg(*rt) as u16 as i16;
+                self.write_reg(*rd, v as u32);
+            },
+
+            CoreInstruction::Ext { rt, rs, pos, size } => {
+                let mask = (((1u64 << size) - 1) as u32) << pos;
+                let v = (self.read_reg(*rs) & mask) >> pos;
+                self.write_reg(*rt, v);
+            },
+
+            CoreInstruction::Ins { rt, rs, pos, size } => {
+                let mask = (((1u64 << size) - 1) as u32) << pos;
+                let v = (self.read_reg(*rt) & !mask) | ((self.read_reg(*rs) << pos) & mask);
+                self.write_reg(*rt, v);
+            },
+
+            CoreInstruction::Sllv { rd, rt, rs } => {
+                let v = self.read_reg(*rt);
+                self.write_reg(*rd, v << (self.read_reg(*rs) & 0x1F));
+            },
+
+            CoreInstruction::Srlv { rd, rt, rs } => {
+                let v = self.read_reg(*rt);
+                self.write_reg(*rd, v >> (self.read_reg(*rs) & 0x1F));
+            },
+
+            CoreInstruction::Srav { rd, rt, rs } => {
+                let v = self.read_reg(*rt) as i32;
+                self.write_reg(*rd, (v >> (self.read_reg(*rs) & 0x1F)) as u32);
             },
 
             CoreInstruction::Multu { rs, rt } => {
-                let r1 = self.get_reg(rs) as u64;
-                let r2 = self.get_reg(rt) as u64;
+                let r1 = self.read_reg(*rs) as u64;
+                let r2 = self.read_reg(*rt) as u64;
 
                 let res = r1 * r2;
                 self.lo = (res & 0xFFFF_FFFF) as u32;
@@ -478,44 +936,844 @@ impl CPU {
             },
 
             CoreInstruction::Divu { rs, rt } => {
-                let r1 = self.get_reg(rs);
-                let r2 = self.get_reg(rt);
+                let r1 = self.read_reg(*rs);
+                let r2 = self.read_reg(*rt);
 
                 if r2 != 0 {
                     self.lo = r1 / r2;
                     self.hi = r1 % r2;
                 }
+            },
+
+            CoreInstruction::Ll { rt, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                if !addr.is_multiple_of(4) {
+                    return Err(EmuError::UnalignedAccess(addr));
+                }
+
+                self.check_access(addr, 4, Access::Load)?;
+                let val = self.memory.load_word(addr);
+                self.write_reg(*rt, val as u32);
+                self.memory.reserve(self.hart_id, addr);
+                self.last_mem_access = Some((addr, 4));
+            },
+
+            CoreInstruction::Sc { rt, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                if !addr.is_multiple_of(4) {
+                    return Err(EmuError::UnalignedAccess(addr));
+                }
+
+                self.check_access(addr, 4, Access::Store)?;
+
+                // the store only happens if no store has touched the word since this hart's `ll`
+                if self.memory.take_reservation(self.hart_id, addr) {
+                    let val = self.read_reg(*rt) as i32;
+                    self.memory.set_word(addr, val);
+                    self.write_reg(*rt, 1);
+                    self.last_mem_access = Some((addr, 4));
+                } else {
+                    self.write_reg(*rt, 0);
+                }
+            },
+
+            // harts execute one instruction at a time, so memory is already sequentially consistent
+            CoreInstruction::Sync => {},
+
+            CoreInstruction::Syscall => {
+                self.syscall()?;
+            },
+
+            CoreInstruction::Trap { cond, rs, rt, code } => {
+                if cond.holds(self.read_reg(*rs), self.read_reg(*rt)) {
+                    return Err(EmuError::Trap { insn: self.source_instruction(insn), code: *code, pc: self.pc, line: self.current_line() });
+                }
+            },
+
+            CoreInstruction::TrapImm { cond, rs, imm } => {
+                if cond.holds(self.read_reg(*rs), *imm as u32) {
+                    return Err(EmuError::Trap { insn: self.source_instruction(insn), code: 0, pc: self.pc, line: self.current_line() });
+                }
+            },
+
+            CoreInstruction::Break { code } => {
+                return Err(EmuError::BreakInstruction { code: *code, pc: self.pc, line: self.current_line() });
+            },
+
+            CoreInstruction::Mfc0 { rt, rd } => {
+                self.write_reg(*rt, self.cp0[*rd as usize]);
+            },
+
+            CoreInstruction::Mtc0 { rt, rd } => {
+                self.cp0[*rd as usize] = self.read_reg(*rt);
+            },
+
+            CoreInstruction::Eret => {
+                // return from the handler immediately (eret has no delay slot), an `ll` before the exception is lost
+                self.cp0[CP0_STATUS] &= !STATUS_EXL;
+                self.memory.clear_reservation(self.hart_id);
+                self.pc = self.cp0[CP0_EPC];
+                self.delay_slot_target = None;
+                return Ok(());
+            },
+
+            CoreInstruction::AddF { fmt, fd, fs, ft } => self.fp_arith(*fmt, *fd, *fs, *ft, |a, b| a + b),
+            CoreInstruction::SubF { fmt, fd, fs, ft } => self.fp_arith(*fmt, *fd, *fs, *ft, |a, b| a - b),
+            CoreInstruction::MulF { fmt, fd, fs, ft } => self.fp_arith(*fmt, *fd, *fs, *ft, |a, b| a * b),
+            CoreInstruction::DivF { fmt, fd, fs, ft } => self.fp_arith(*fmt, *fd, *fs, *ft, |a, b| a / b),
+            CoreInstruction::SqrtF { fmt, fd, fs } => self.fp_arith(*fmt, *fd, *fs, *fs, |a, _| a.sqrt()),
+
+            // abs/neg/mov only change the sign bit, so they work on the raw bits (NaN payloads are kept)
+            CoreInstruction::AbsF { fmt, fd, fs } | CoreInstruction::NegF { fmt, fd, fs } | CoreInstruction::MovF { fmt, fd, fs } => {
+                let words = if *fmt == FpFormat::D { 2 } else { 1 };
+                let src = fs.index();
+                let dst = fd.index();
+
+                // the sign bit is in the last word (the odd register of a double)
+                let values: Vec<u32> = (0..words).map(|i| self.fpr[(src + i) % 32]).collect();
+                for (i, value) in values.into_iter().enumerate() {
+                    let value = match insn {
+                        CoreInstruction::AbsF { .. } if i == words - 1 => value & !(1 << 31),
+                        CoreInstruction::NegF { .. } if i == words - 1 => value ^ (1 << 31),
+                        _ => value,
+                    };
+                    self.write_fpr((dst + i) % 32, value);
+                }
+            },
+
+            CoreInstruction::Cvt { to, from, fd, fs } => {
+                let value = self.read_fp(*from, *fs);
+                self.write_fp(*to, *fd, value);
+            },
+
+            CoreInstruction::CompareF { cond, fmt, cc, fs, ft } => {
+                let a = self.read_fp(*fmt, *fs);
+                let b = self.read_fp(*fmt, *ft);
+
+                // comparisons with NaN are unordered and always false
+                let result = match cond {
+                    FpCondition::Eq => a == b,
+                    FpCondition::Lt => a < b,
+                    FpCondition::Le => a <= b,
+                };
+                self.set_fp_condition(*cc, result);
+            },
+
+            CoreInstruction::Bc1t { cc, label } => {
+                if self.fp_condition(*cc) {
+                    branch_target = Some(self.label_target(label)?);
+                }
+            },
+
+            CoreInstruction::Bc1f { cc, label } => {
+                if !self.fp_condition(*cc) {
+                    branch_target = Some(self.label_target(label)?);
+                }
+            },
+
+            CoreInstruction::Lwc1 { ft, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                if !addr.is_multiple_of(4) {
+                    return Err(EmuError::UnalignedAccess(addr));
+                }
+
+                self.check_access(addr, 4, Access::Load)?;
+                let val = self.memory.load_word(addr);
+                self.write_fpr(ft.index(), val as u32);
+                self.last_mem_access = Some((addr, 4));
+            },
+
+            CoreInstruction::Swc1 { ft, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                if !addr.is_multiple_of(4) {
+                    return Err(EmuError::UnalignedAccess(addr));
+                }
+
+                self.check_access(addr, 4, Access::Store)?;
+                self.memory.set_word(addr, self.fpr[ft.index()] as i32);
+                self.last_mem_access = Some((addr, 4));
+            },
+
+            CoreInstruction::Ldc1 { ft, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                if !addr.is_multiple_of(8) {
+                    return Err(EmuError::UnalignedAccess(addr));
+                }
+
+                self.check_access(addr, 8, Access::Load)?;
+                let value = self.memory.load_double(addr);
+                self.write_double(*ft, value);
+                self.last_mem_access = Some((addr, 8));
+            },
+
+            CoreInstruction::Sdc1 { ft, rs, imm } => {
+                let addr = self.read_reg(*rs).wrapping_add(*imm as u32);
+                if !addr.is_multiple_of(8) {
+                    return Err(EmuError::UnalignedAccess(addr));
+                }
+
+                self.check_access(addr, 8, Access::Store)?;
+                self.memory.set_double(addr, self.read_double(*ft));
+                self.last_mem_access = Some((addr, 8));
+            },
+
+            CoreInstruction::Mtc1 { rt, fs } => {
+                let value = self.read_reg(*rt);
+                self.write_fpr(fs.index(), value);
+            },
+
+            CoreInstruction::Mfc1 { rt, fs } => {
+                self.write_reg(*rt, self.fpr[fs.index()]);
             }
         }        
 
+        // target of the branch executed just before this instruction (this instruction was its delay slot)
+        let pending_target = self.delay_slot_target.take();
+
         // branch instructions will modify the PC to another address instead of the sequential instruction
-        // maybe we have to deal with the one instruction leading to jr $ra due to branch delay
-        if !is_branch {
-            self.pc += 4;
+        // with delayed branching the following instruction (delay slot) runs before the branch takes effect
+        match branch_target {
+            Some(target) if self.delayed_branching => {
+                self.delay_slot_target = Some(target);
+                self.pc += 4;
+            },
+            Some(target) => self.pc = target,
+            None => self.pc += 4,
+        }
+
+        if let Some(target) = pending_target {
+            self.pc = target;
         }
 
         Ok(())
     }
 
-    /// executes a single MIPS instruction 
+    /// returns {Hi, Lo} as a 64-bit value
+    fn hi_lo(&self) -> u64 {
+        ((self.hi as u64) << 32) | self.lo as u64
+    }
+
+    fn set_hi_lo(&mut self, value: u64) {
+        self.hi = (value >> 32) as u32;
+        self.lo = value as u32;
+    }
+
+    /// records a call returning to the link address, with the register file so the return can validate $sp, $fp and the saved registers
+    fn push_call_frame(&mut self) {
+        self.call_stack.push(CallFrame { return_address: self.return_address(), registers: self.registers });
+    }
+
+    /// leaves the call at `depth` and every call above it
+    fn return_to(&mut self, depth: usize) {
+        let frames = self.call_stack.split_off(depth);
+        if self.history.limit() > 0 {
+            self.returned_calls.extend(frames);
+        }
+    }
+
+    /// number of calls in progress
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// return address linked by calls (skips the delay slot when it is emulated)
+    fn return_address(&self) -> u32 {
+        if self.delayed_branching { self.pc + 8 } else { self.pc + 4 }
+    }
+
+    /// resolves the address of a branch or jump label
+    fn label_target(&self, label: &str) -> Result<u32, EmuError> {
+        self.program.as_ref()
+            .and_then(|p| p.get_label_address(label))
+            .ok_or(EmuError::UndefinedLabel(label.to_string()))
+    }
+
+    /// returns true if `pc` holds one of the program's instructions or, in fetch mode, any word of an executable segment
+    fn is_code(&self, pc: u32) -> bool {
+        self.program.as_ref().is_some_and(|p| p.pc_to_index(pc).is_some())
+            || self.fetch_from_memory && self.memory.segments.check(pc, 4, Access::Fetch).is_ok()
+    }
+
+    /// returns the source line of the instruction at $pc (0 outside the program)
+    fn current_line(&self) -> usize {
+        self.line_at(self.pc)
+    }
+
+    /// the instruction at $pc as written in the source (`insn` for code without a source line)
+    /// so a trap reports `teqi $t0, 100000` rather than the `teq $t0, $at` it expands to
+    fn source_instruction(&self, insn: &CoreInstruction) -> String {
+        self.program.as_ref()
+            .and_then(|p| p.source_instruction(self.current_line()))
+            .unwrap_or_else(|| insn.to_string())
+    }
+
+    /// returns the source line of the instruction at `pc` (0 outside the program)
+    fn line_at(&self, pc: u32) -> usize {
+        self.program.as_ref()
+            .and_then(|p| p.pc_to_index(pc).map(|index| p.line_numbers[index]))
+            .unwrap_or(0)
+    }
+
+    /// describes a runtime error for the IDE and the grader
+    /// (segmentation faults read like "store to read-only text at line 14")
+    pub fn error_message(&self, error: &EmuError) -> String {
+        match error {
+            EmuError::SegmentationFault { addr, pc, kind } => match self.line_at(*pc) {
+                0 => format!("SegmentationFault: {} (address 0x{:08x}, pc 0x{:08x})", kind, addr, pc),
+                line => format!("SegmentationFault: {} at line {} (address 0x{:08x})", kind, line, addr),
+            },
+            EmuError::OutOfMemory { requested, available, line, .. } if *requested < 0 => {
+                format!("OutOfMemory: sbrk({}) at line {} releases more than the {} bytes allocated", requested, line, available)
+            },
+            EmuError::OutOfMemory { requested, available, line, .. } => {
+                format!("OutOfMemory: sbrk({}) at line {} needs more than the {} bytes left on the heap", requested, line, available)
+            },
+            EmuError::UninitializedRead { location, line, .. } if location.starts_with('$') => {
+                format!("UninitializedRead: {} is read before it was written at line {}", location, line)
+            },
+            EmuError::UninitializedRead { location, line, .. } => {
+                format!("UninitializedRead: memory at {} is read before it was written at line {}", location, line)
+            },
+            other => format!("{:?}", other),
+        }
+    }
+
+    /// updates the shadow state after the instruction at `pc` ran and reports what it read uninitialized
+    /// storing an undefined register only marks the bytes undefined and loading them back makes the
+    /// destination undefined again (saving an $s register the caller never set is fine), any other
+    /// use of an undefined register or a load of bytes that were never written is reported
+    fn check_uninitialized(&mut self, insn: &CoreInstruction, pc: u32) -> Result<(), EmuError> {
+        let mut reads = self.undefined_reads.take();
+
+        match insn {
+            CoreInstruction::Sw { rt, rs, .. } | CoreInstruction::Sh { rt, rs, .. }
+            | CoreInstruction::Sb { rt, rs, .. } | CoreInstruction::Sc { rt, rs, .. }
+                if rt != rs && reads & (1 << rt.0) != 0 => {
+                reads &= !(1 << rt.0);
+                if let (Some((addr, len)), Some(shadow)) = (self.last_mem_access, self.memory.shadow.as_mut()) {
+                    shadow.mark(addr, len, ByteState::Undefined);
+                }
+            },
+            _ => {},
+        }
+
+        // the register is reported first since an undefined base makes the address meaningless
+        if reads != 0 {
+            let register = REGISTER_NAMES[reads.trailing_zeros() as usize];
+            self.report_uninitialized(register.to_string(), pc)?;
+        }
+
+        // floating-point loads are only checked for bytes that were never written
+        let rt = match insn {
+            CoreInstruction::Lw { rt, .. } | CoreInstruction::Lh { rt, .. } | CoreInstruction::Lhu { rt, .. }
+            | CoreInstruction::Lb { rt, .. } | CoreInstruction::Lbu { rt, .. } | CoreInstruction::Ll { rt, .. } => Some(*rt),
+            CoreInstruction::Lwc1 { .. } | CoreInstruction::Ldc1 { .. } => None,
+            _ => return Ok(()),
+        };
+
+        let (Some((addr, len)), Some(shadow)) = (self.last_mem_access, self.memory.shadow.as_ref()) else {
+            return Ok(());
+        };
+
+        if let Some(unwritten) = shadow.first_unwritten(addr, len) {
+            self.report_uninitialized(format!("0x{:08x}", unwritten), pc)?;
+        } else if let Some(rt) = rt && !shadow.is_defined(addr, len) && rt != Register::ZERO {
+            self.defined_registers &= !(1 << rt.0);
+        }
+        Ok(())
+    }
+
+    /// stops with `EmuError::UninitializedRead` in strict mode, otherwise records the read once per location and PC
+    fn report_uninitialized(&mut self, location: String, pc: u32) -> Result<(), EmuError> {
+        let line = self.line_at(pc);
+        if self.uninit_check == UninitCheck::Strict {
+            return Err(EmuError::UninitializedRead { location, pc, line });
+        }
+
+        let read = UninitializedRead { location, pc, line };
+        if !self.uninit_warnings.contains(&read) {
+            self.uninit_warnings.push(read);
+        }
+        Ok(())
+    }
+
+    /// address a store instruction (or the read string syscall) is about to write, from the current registers
+    fn store_address(&self, insn: &CoreInstruction) -> Option<u32> {
+        let reg = |r: &Register| self.registers[r.index()];
+
+        match insn {
+            CoreInstruction::Sw { rs, imm, .. } | CoreInstruction::Sh { rs, imm, .. } | CoreInstruction::Sb { rs, imm, .. }
+            | CoreInstruction::Sc { rs, imm, .. } | CoreInstruction::Swc1 { rs, imm, .. } | CoreInstruction::Sdc1 { rs, imm, .. } => {
+                Some(reg(rs).wrapping_add(*imm as u32))
+            },
+            CoreInstruction::Swl { rs, imm, .. } | CoreInstruction::Swr { rs, imm, .. } => Some(reg(rs).wrapping_add(*imm as u32) & !3),
+            CoreInstruction::Syscall if reg(&Register::V0) == 8 => Some(reg(&Register::A0)),
+            _ => None,
+        }
+    }
+
+    /// reads up to a word at `addr` for a watchpoint (device registers read as 0, since reading them can have side effects)
+    fn watched_value(&mut self, addr: u32, len: u32) -> u32 {
+        if addr >= MMIO_START {
+            return 0;
+        }
+
+        let bytes = self.memory.get_memory_slice(addr, len.min(4) as usize);
+        bytes.iter().rev().fold(0, |value, b| value << 8 | *b as u32)
+    }
+
+    /// stops with `EmuError::Watchpoint` if the instruction that just ran changed a watched register or
+    /// touched a watched memory range (`stored` is the word at the store address before it ran)
+    fn check_watchpoints(&mut self, registers: &[u32; 32], stored: Option<u32>) -> Result<(), EmuError> {
+        for watchpoint in &self.watchpoints {
+            if let WatchTarget::Register(register) = watchpoint.target {
+                let (old, new) = (registers[register.index()], self.registers[register.index()]);
+                if old != new && watchpoint.accepts(new) {
+                    return Err(EmuError::Watchpoint { id: watchpoint.id, addr: register.0 as u32, old, new });
+                }
+            }
+        }
+
+        let Some((addr, len)) = self.last_mem_access else {
+            return Ok(());
+        };
+        let write = stored.is_some();
+        if !self.watchpoints.iter().any(|w| w.observes(addr, len, write)) {
+            return Ok(());
+        }
+
+        let mask = if len >= 4 { u32::MAX } else { (1 << (8 * len)) - 1 };
+        let new = self.watched_value(addr, len);
+        let old = stored.map_or(new, |word| word & mask);
+
+        match self.watchpoints.iter().find(|w| w.observes(addr, len, write) && w.accepts(new)) {
+            Some(watchpoint) => Err(EmuError::Watchpoint { id: watchpoint.id, addr, old, new }),
+            None => Ok(()),
+        }
+    }
+
+    /// adds a watchpoint and returns its id (watchpoints are kept when a new program is loaded)
+    pub fn add_watchpoint(&mut self, target: WatchTarget, condition: Option<(Comparison, i32)>) -> usize {
+        let id = self.next_watchpoint_id;
+        self.next_watchpoint_id += 1;
+
+        self.watchpoints.push(Watchpoint { id, target, condition });
+        id
+    }
+
+    /// removes a watchpoint (returns false if there is no watchpoint with the id)
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|w| w.id != id);
+        self.watchpoints.len() != count
+    }
+
+    /// checks an access of `len` bytes at `addr` against the segment map (when `check_segments` is on) and the
+    /// program break (when `check_heap` is on)
+    /// (self-modifying programs, which fetch from memory, may also store to executable segments)
+    fn check_access(&self, addr: u32, len: u32, access: Access) -> Result<(), EmuError> {
+        if self.check_segments && let Err(kind) = self.memory.segments.check(addr, len, access) {
+            let self_modifying = access == Access::Store && self.fetch_from_memory
+                && self.memory.segments.check(addr, len, Access::Fetch).is_ok();
+            if !self_modifying {
+                return Err(EmuError::SegmentationFault { addr, pc: self.pc, kind });
+            }
+        }
+
+        if self.check_heap && (self.memory.is_unallocated_heap(addr) || self.memory.is_unallocated_heap(addr.wrapping_add(len - 1))) {
+            return Err(EmuError::SegmentationFault { addr, pc: self.pc, kind: FaultKind::Unallocated(access) });
+        }
+        Ok(())
+    }
+
+    /// builds an `ArithmeticOverflow` error for the instruction at $pc
+    fn overflow_error(&self) -> EmuError {
+        EmuError::ArithmeticOverflow { pc: self.pc, line: self.current_line() }
+    }
+
+    /// handles a `syscall` using the SPIM/MARS service numbers in $v0
+    fn syscall(&mut self) -> Result<(), EmuError> {
+        let service = self.read_reg(Register::V0);
+
+        match service {
+            // print integer
+            1 => {
+                let a0 = self.read_reg(Register::A0);
+                self.console.write(&(a0 as i32).to_string());
+            },
+
+            // print float in $f12
+            2 => {
+                self.check_fp_argument(false)?;
+                self.console.write(&format!("{:?}", self.get_float("$f12")));
+            },
+
+            // print double in $f12/$f13
+            3 => {
+                self.check_fp_argument(true)?;
+                self.console.write(&format!("{:?}", self.get_double("$f12")));
+            },
+
+            // print string
+            4 => {
+                let a0 = self.read_reg(Register::A0);
+                self.check_access(a0, 1, Access::Load)?;
+                let text = self.memory.load_string(a0);
+                self.console.write(&text);
+            },
+
+            // read integer
+            5 => {
+                let line = self.console.read_line().ok_or(EmuError::InputRequired)?;
+                let value = line.trim().parse::<i32>()
+                    .map_err(|_| EmuError::InvalidInput(format!("expected an integer, found {:?}", line)))?;
+
+                self.set_reg("$v0", value as u32);
+            },
+
+            // read float into $f0
+            6 => {
+                let line = self.console.read_line().ok_or(EmuError::InputRequired)?;
+                let value = line.trim().parse::<f32>()
+                    .map_err(|_| EmuError::InvalidInput(format!("expected a float, found {:?}", line)))?;
+
+                self.set_float("$f0", value);
+            },
+
+            // read double into $f0/$f1
+            7 => {
+                let line = self.console.read_line().ok_or(EmuError::InputRequired)?;
+                let value = line.trim().parse::<f64>()
+                    .map_err(|_| EmuError::InvalidInput(format!("expected a double, found {:?}", line)))?;
+
+                self.set_double("$f0", value);
+            },
+
+            // read string into buffer $a0 with maximum length $a1 (includes the null terminator)
+            8 => {
+                let a0 = self.read_reg(Register::A0);
+                let max_len = self.read_reg(Register::A1) as i32;
+                let mut line = self.console.read_line().ok_or(EmuError::InputRequired)?;
+                line.push('\n');
+
+                if max_len > 0 {
+                    let bytes: Vec<u8> = line.bytes().take(max_len as usize - 1).collect();
+                    self.check_access(a0, bytes.len() as u32 + 1, Access::Store)?;
+                    for (i, b) in bytes.iter().enumerate() {
+                        self.memory.set_byte(a0.wrapping_add(i as u32), *b as i8);
+                    }
+                    self.memory.set_byte(a0.wrapping_add(bytes.len() as u32), 0);
+                    self.last_mem_access = Some((a0, max_len as u32));
+                }
+            },
+
+            // sbrk (allocate heap memory)
+            9 => {
+                let a0 = self.read_reg(Register::A0);
+                let line = self.current_line();
+                let address = self.memory.sbrk(a0 as i32, line)
+                    .map_err(|available| EmuError::OutOfMemory { requested: a0 as i32, available, pc: self.pc, line })?;
+                self.set_reg("$v0", address);
+            },
+
+            // exit
+            10 => {
+                self.exit_code = Some(0);
+                return Err(EmuError::Termination);
+            },
+
+            // print character
+            11 => {
+                let a0 = self.read_reg(Register::A0);
+                self.console.write(&((a0 & 0xFF) as u8 as char).to_string());
+            },
+
+            // read character
+            12 => {
+                let c = self.console.read_char().ok_or(EmuError::InputRequired)?;
+                self.set_reg("$v0", c as u32);
+            },
+
+            // exit2 (terminate with status code in $a0)
+            17 => {
+                self.exit_code = Some(self.read_reg(Register::A0) as i32);
+                return Err(EmuError::Termination);
+            },
+
+            _ => return Err(EmuError::InvalidSyscall(service))
+        }
+
+        Ok(())
+    }
+
+    /// executes a single MIPS instruction, recording how to undo it when the history is enabled
     pub fn next(&mut self) -> Result<(), EmuError> {
-        let program = self.program.as_ref().unwrap();
+        if self.history.limit() == 0 {
+            return self.execute_next();
+        }
+
+        let before = self.register_file();
+        self.memory.journal = Some(Vec::new());
+
+        // only a `syscall` with the sbrk service in $v0 changes the allocations, so they are not copied for every instruction
+        let allocations = (self.registers[Register::V0.index()] == 9).then(|| self.memory.allocations.clone());
+
+        let result = self.execute_next();
+
+        let writes = self.memory.journal.take().unwrap_or_default();
+        let returned = std::mem::take(&mut self.returned_calls);
+        if let Some(entry) = UndoEntry::new(&before, &self.register_file(), writes, returned, allocations) {
+            self.history.push(entry);
+        }
+        result
+    }
+
+    /// registers `step_back` restores (see `UndoEntry`)
+    fn register_file(&self) -> RegisterFile {
+        RegisterFile {
+            pc: self.pc,
+            hi: self.hi,
+            lo: self.lo,
+            fcsr: self.fcsr,
+            registers: self.registers,
+            fpr: self.fpr,
+            cp0: self.cp0,
+            delay_slot_target: self.delay_slot_target,
+            exit_code: self.exit_code,
+            defined_registers: self.defined_registers,
+            defined_fp_registers: self.defined_fp_registers,
+            call_depth: self.call_stack.len(),
+            heap_break: self.memory.heap_break,
+        }
+    }
+
+    /// undoes the last recorded instruction (false if the history is empty)
+    /// console output and device state are not rolled back
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.history.pop() else {
+            return false;
+        };
+
+        for (register, value) in entry.registers {
+            self.registers[register as usize] = value;
+        }
+        for (register, value) in entry.fp_registers {
+            self.fpr[register as usize] = value;
+        }
+        for (register, value) in entry.cp0_registers {
+            self.cp0[register as usize] = value;
+        }
+        for (address, byte) in entry.memory.into_iter().rev() {
+            self.memory.backend.write(address, &[byte]);
+        }
+
+        self.call_stack.truncate(entry.call_depth - entry.returned_calls.len());
+        self.call_stack.extend(entry.returned_calls);
+        if let Some((heap_break, allocations)) = entry.heap {
+            self.memory.heap_break = heap_break;
+            self.memory.allocations = allocations;
+        }
+
+        self.pc = entry.pc;
+        self.hi = entry.hi;
+        self.lo = entry.lo;
+        self.fcsr = entry.fcsr;
+        self.delay_slot_target = entry.delay_slot_target;
+        self.exit_code = entry.exit_code;
+        self.defined_registers = entry.defined_registers;
+        self.defined_fp_registers = entry.defined_fp_registers;
         self.last_mem_access = None;
+        true
+    }
+
+    /// steps back until $pc reaches a breakpoint whose condition holds (hit counts and logpoints only apply going forward)
+    /// returns false if the history ran out first
+    pub fn run_back_to_breakpoint(&mut self) -> bool {
+        while self.step_back() {
+            let Some(breakpoint) = self.breakpoint_line().and_then(|line| self.breakpoints.get(&line)) else {
+                continue;
+            };
+            if breakpoint.log_message.is_none() && breakpoint.condition_holds(&self.registers, &mut self.memory) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// executes the instruction at $pc
+    fn execute_next(&mut self) -> Result<(), EmuError> {
+        // the program already called one of the exit syscalls
+        if self.exit_code.is_some() {
+            return Err(EmuError::Termination);
+        }
+
+        self.last_mem_access = None;
+
+        if self.check_interrupts() {
+            return Ok(());
+        }
+
+        let program = Rc::clone(self.program.as_ref().unwrap());
 
         // get the current instruction using the $pc register
         // we could iterate the array but this is better when we also deal with branches and jumps 
-        let index = program.pc_to_index(self.pc)
-            .ok_or(EmuError::Termination)?;
+        // (in fetch mode code stored outside the program runs too, but an empty word there ends the program
+        // like running past its last instruction)
+        let index = program.pc_to_index(self.pc);
+        if index.is_none() && !(self.is_code(self.pc) && self.memory.load_word(self.pc) != 0) {
+            // a delay slot past the end of the program behaves like a nop
+            if let Some(target) = self.delay_slot_target.take() {
+                self.pc = target;
+                return Ok(());
+            }
+            return Err(EmuError::Termination);
+        }
 
-        let insn = program.core_instructions[index].clone();
+        let decoded = match index {
+            Some(index) if !self.fetch_from_memory => Ok(Cow::Borrowed(&program.core_instructions[index])),
+            _ => {
+                if let Err(e) = self.check_access(self.pc, 4, Access::Fetch) {
+                    return self.raise_exception(e, None);
+                }
+                let word = self.memory.load_word(self.pc) as u32;
+                decode(word, self.pc, &program.symbol_table).map(Cow::Owned)
+            },
+        };
+
+        let insn = match decoded {
+            Ok(insn) => insn,
+            Err(e) => return self.raise_exception(e, None),
+        };
         // alert(format!("Executing instruction: {:?} at PC: 0x{:08X}", insn, self.pc).as_str());
-        self.execute(&insn)?;
+        let pc = self.pc;
+        self.undefined_reads.set(0);
+
+        // values the watchpoints compare against: the registers and the word the instruction stores over
+        let watched = (!self.watchpoints.is_empty()).then(|| {
+            let stored = self.store_address(&insn).map(|addr| self.watched_value(addr, 4));
+            (self.registers, stored)
+        });
+
+        let result = match self.execute(&insn) {
+            // exit2 reads its status from $a0 before terminating
+            Err(EmuError::Termination) if self.uninit_check != UninitCheck::Off => {
+                self.check_uninitialized(&insn, pc).and(Err(EmuError::Termination))
+            },
+            Err(e) => self.raise_exception(e, Some(&insn)),
+            Ok(()) if self.uninit_check != UninitCheck::Off => self.check_uninitialized(&insn, pc),
+            Ok(()) => Ok(()),
+        };
+        let result = match (result, watched) {
+            (Ok(()), Some((registers, stored))) => self.check_watchpoints(&registers, stored),
+            (result, _) => result,
+        };
 
+        // devices advance once per executed instruction
+        self.memory.mmio.tick();
+
+        result
+    }
+
+    /// latches device interrupt lines into Cause and enters the handler for an enabled pending interrupt
+    /// returns true if the handler was entered instead of executing the instruction at $pc
+    fn check_interrupts(&mut self) -> bool {
+        let hardware = self.memory.mmio.pending_interrupts() << HARDWARE_INTERRUPT_SHIFT;
+        let software = self.cp0[CP0_CAUSE] & (0x3 << 8);
+        self.cp0[CP0_CAUSE] = (self.cp0[CP0_CAUSE] & !INTERRUPT_MASK) | software | (hardware & INTERRUPT_MASK);
+
+        let status = self.cp0[CP0_STATUS];
+        let enabled = status & STATUS_IE != 0 && status & STATUS_EXL == 0;
+
+        if !enabled || self.cp0[CP0_CAUSE] & status & INTERRUPT_MASK == 0 || !self.has_exception_handler() {
+            return false;
+        }
+
+        self.enter_exception(EXC_INTERRUPT, None);
+        true
+    }
+
+    /// maps a runtime fault to its exception code and bad address (`None` for errors that are not exceptions)
+    fn exception_cause(error: &EmuError, insn: Option<&CoreInstruction>) -> Option<(u32, Option<u32>)> {
+        match error {
+            EmuError::UnalignedAccess(addr) | EmuError::InvalidJump(addr) => {
+                let is_store = matches!(insn, Some(
+                    CoreInstruction::Sw { .. } | CoreInstruction::Sh { .. } | CoreInstruction::Sb { .. } |
+                    CoreInstruction::Swl { .. } | CoreInstruction::Swr { .. } |
+                    CoreInstruction::Swc1 { .. } | CoreInstruction::Sdc1 { .. } | CoreInstruction::Sc { .. }
+                ));
+                let code = if is_store { EXC_ADDRESS_STORE } else { EXC_ADDRESS_LOAD };
+                Some((code, Some(*addr)))
+            },
+
+            EmuError::BreakInstruction { .. } => Some((EXC_BREAKPOINT, None)),
+
+            // MIPS has no divide exception, compilers check the divisor with `teq $rt, $zero, 7`, so a zero
+            // divisor is a trap exception like that check
+            EmuError::DivideByZero => Some((EXC_TRAP, None)),
+
+            EmuError::InvalidInstruction(_) => Some((EXC_RESERVED_INSTRUCTION, None)),
+
+            EmuError::ArithmeticOverflow { .. } => Some((EXC_OVERFLOW, None)),
+
+            EmuError::Trap { .. } => Some((EXC_TRAP, None)),
+
+            EmuError::SegmentationFault { addr, kind, .. } => {
+                let code = if kind.access() == Access::Store { EXC_ADDRESS_STORE } else { EXC_ADDRESS_LOAD };
+                Some((code, Some(*addr)))
+            },
+
+            _ => None
+        }
+    }
+
+    /// returns true if the program has kernel text at the exception handler address
+    pub fn has_exception_handler(&self) -> bool {
+        self.program.as_ref()
+            .is_some_and(|p| p.pc_to_index(EXCEPTION_HANDLER_ADDRESS).is_some())
+    }
+
+    /// transfers control to the exception handler for a fault raised by the instruction at $pc
+    /// the error is returned unchanged when there is no handler or the handler itself faults
+    fn raise_exception(&mut self, error: EmuError, insn: Option<&CoreInstruction>) -> Result<(), EmuError> {
+        let Some((code, bad_addr)) = Self::exception_cause(&error, insn) else {
+            return Err(error);
+        };
+
+        if self.cp0[CP0_STATUS] & STATUS_EXL != 0 || !self.has_exception_handler() {
+            return Err(error);
+        }
+
+        self.enter_exception(code, bad_addr);
         Ok(())
     }
 
+    /// saves the restart address in EPC, records the cause and jumps to the exception handler
+    fn enter_exception(&mut self, code: u32, bad_addr: Option<u32>) {
+        // an exception in a delay slot restarts at the branch
+        let in_delay_slot = self.delay_slot_target.take().is_some();
+        let bd = if in_delay_slot { CAUSE_BD } else { 0 };
+
+        self.cp0[CP0_EPC] = if in_delay_slot { self.pc - 4 } else { self.pc };
+        self.cp0[CP0_CAUSE] = (self.cp0[CP0_CAUSE] & !(CAUSE_BD | CAUSE_EXC_CODE_MASK)) | bd | (code << 2);
+        if let Some(addr) = bad_addr {
+            self.cp0[CP0_BADVADDR] = addr;
+        }
+
+        self.cp0[CP0_STATUS] |= STATUS_EXL;
+        self.pc = EXCEPTION_HANDLER_ADDRESS;
+    }
+
     /// launches the emulator instance and executes line-by-line using a `Program`
     pub fn run(&mut self) -> Result<(), EmuError> {
+        match self.run_until(|_| false) {
+            Err(EmuError::Termination) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// runs like `run` until `stop` returns true after an instruction (checked before the breakpoints)
+    /// returns `Err(EmuError::Termination)` if the program ends first
+    pub fn run_until(&mut self, stop: impl Fn(&CPU) -> bool) -> Result<(), EmuError> {
         let mut instruction_count: u64 = 0;
 
         loop {
@@ -523,31 +1781,81 @@ impl CPU {
                 return Err(EmuError::ExecutionLimitExceeded(instruction_count));
             }
 
-            match self.next() {
-                Ok(_) => {},
-                Err(EmuError::Termination) => break,
-                Err(EmuError::Breakpoint) => return Err(EmuError::Breakpoint),
-                Err(e) => return Err(e)
-            }
-
+            self.next()?;
             instruction_count += 1;
 
-            // check if the current instruction line contains a breakpoint in the set
-            if let Some(program) = self.program.as_ref() {
-                if let Some(index) = program.pc_to_index(self.pc) {
-                    if index < program.line_numbers.len() {
-                        let current_line = program.line_numbers[index] - 1;
+            if stop(self) {
+                return Ok(());
+            }
 
-                        if self.breakpoints.contains(&current_line) {
-                            return Err(EmuError::Breakpoint);
-                        }
-                    }
-                }
+            if self.breakpoint_reached() {
+                return Err(EmuError::Breakpoint);
             }
-        }   
+        }
+    }
+
+    /// executes one instruction, running a call it makes (`jal`, `jalr`, a taken `bgezal`/`bltzal`) until it returns
+    pub fn step_over(&mut self) -> Result<(), EmuError> {
+        let depth = self.call_depth();
+        self.next()?;
 
+        if self.call_depth() > depth {
+            // the call's first instruction can have a breakpoint too
+            if self.breakpoint_reached() {
+                return Err(EmuError::Breakpoint);
+            }
+            self.run_until(|cpu| cpu.returned_to(depth))?;
+        }
         Ok(())
-    }   
+    }
+
+    /// runs until the current call returns to its caller (to the end of the program outside of a call)
+    pub fn step_out(&mut self) -> Result<(), EmuError> {
+        let depth = self.call_depth();
+        self.run_until(|cpu| depth > 0 && cpu.returned_to(depth - 1))
+    }
+
+    /// returns true once at most `depth` calls are in progress and the delay slot of the return (if any) has run
+    fn returned_to(&self, depth: usize) -> bool {
+        self.call_depth() <= depth && self.delay_slot_target.is_none()
+    }
+
+    /// runs until $pc reaches the first instruction of a source line (counted from 0, like the breakpoints)
+    pub fn run_to_line(&mut self, line: usize) -> Result<(), EmuError> {
+        self.run_until(|cpu| cpu.breakpoint_line() == Some(line))
+    }
+
+    /// returns true if the instruction line at $pc contains a breakpoint in the set
+    pub fn at_breakpoint(&self) -> bool {
+        self.breakpoint_line().is_some_and(|line| self.breakpoints.contains_key(&line))
+    }
+
+    /// line (counted from 0, like the breakpoints) if $pc is at the first instruction of a source line
+    /// (the rest of an expanded pseudo-instruction does not reach its breakpoint again)
+    fn breakpoint_line(&self) -> Option<usize> {
+        let program = self.program.as_ref()?;
+        let index = program.pc_to_index(self.pc)?;
+        let line = *program.line_numbers.get(index)?;
+
+        (index == 0 || program.line_numbers[index - 1] != line).then(|| line - 1)
+    }
+
+    /// counts a hit of the breakpoint at $pc and returns true if it stops execution
+    /// (a logpoint writes its message to the console instead)
+    pub fn breakpoint_reached(&mut self) -> bool {
+        let Some(breakpoint) = self.breakpoint_line().and_then(|line| self.breakpoints.get_mut(&line)) else {
+            return false;
+        };
+
+        match breakpoint.hit(&self.registers, &mut self.memory) {
+            BreakpointAction::Continue => false,
+            BreakpointAction::Stop => true,
+            BreakpointAction::Log(message) => {
+                self.console.write(&format!("{}\n", message));
+                false
+            },
+        }
+    }
 
     /// used to run a multiline string directly 
     pub fn run_input(&mut self, source: &str) -> Result<(), EmuError> {
@@ -557,24 +1865,79 @@ impl CPU {
         self.run()
     }
 
+    /// sets plain breakpoints on the lines, keeping the settings and hit counts of lines that already had one
     pub fn set_breakpoints(&mut self, lines: Vec<usize>) {
-        self.breakpoints = lines.into_iter().collect();
+        let mut breakpoints = std::mem::take(&mut self.breakpoints);
+        self.breakpoints = lines.into_iter()
+            .map(|line| (line, breakpoints.remove(&line).unwrap_or_default()))
+            .collect();
+    }
+
+    /// sets or replaces the breakpoint on a line (its hit count starts over)
+    pub fn set_breakpoint(&mut self, line: usize, breakpoint: Breakpoint) {
+        self.breakpoints.insert(line, breakpoint);
+    }
+
+    /// copies the settings of another CPU (breakpoints, watchpoints, checks and execution modes) but none of its state
+    /// (used to configure the harts of a `Machine` like a single CPU)
+    pub fn copy_settings(&mut self, other: &CPU) {
+        self.breakpoints = other.breakpoints.clone();
+        self.watchpoints = other.watchpoints.clone();
+        self.next_watchpoint_id = other.next_watchpoint_id;
+        self.max_instructions = other.max_instructions;
+        self.fetch_from_memory = other.fetch_from_memory;
+        self.check_segments = other.check_segments;
+        self.check_heap = other.check_heap;
+        self.delayed_branching = other.delayed_branching;
+        self.history.set_limit(other.history.limit());
+        self.set_uninit_check(other.uninit_check);
     }
 
     // below functions are used for Web Assembly only
     pub fn reset(&mut self) {
-        self.registers = Self::create_register_map();
+        self.registers = Self::initial_registers();
 
-        // enable memory isolation by default
+        // segment permissions are configuration like `fetch_from_memory`, so they survive the reset
+        let segments = std::mem::take(&mut self.memory.segments);
         self.memory = Memory::new(); 
+        self.memory.segments = segments;
         self.pc = DEFAULT_TEXT_BASE_ADDRESS;
 
         self.lo = 0;
         self.hi = 0;
+        self.cp0 = [0; 32];
+        self.fpr = [0; 32];
+        self.fcsr = 0;
         self.program = None;
 
         self.breakpoints.clear();
-        self.validation_stack.clear();
+        self.call_stack.clear();
+
+        self.console = Box::new(BufferedConsole::new());
+        self.exit_code = None;
+        self.delay_slot_target = None;
+        self.reset_shadow_state();
+        self.history.clear();
+    }
+
+    /// turns on reporting of reads of registers and memory the program never wrote
+    /// (enable it before loading the program, so its `.data` counts as written)
+    pub fn set_uninit_check(&mut self, check: UninitCheck) {
+        self.uninit_check = check;
+        self.reset_shadow_state();
+    }
+
+    pub fn uninit_check(&self) -> UninitCheck {
+        self.uninit_check
+    }
+
+    fn reset_shadow_state(&mut self) {
+        let enabled = self.uninit_check != UninitCheck::Off;
+
+        self.defined_registers = if enabled { INITIALLY_DEFINED } else { u32::MAX };
+        self.defined_fp_registers = if enabled { 0 } else { u32::MAX };
+        self.memory.shadow = enabled.then(ShadowMemory::default);
+        self.uninit_warnings.clear();
     }
 
     pub fn snapshot(&self) -> Snapshot {
@@ -584,14 +1947,40 @@ impl CPU {
         };
 
         Snapshot {
-            registers: self.registers.clone(),
+            registers: REGISTER_NAMES.iter().zip(self.registers).map(|(name, value)| (name.to_string(), value)).collect(),
             memory_access_addr: addr,
             memory_access_size: size,
-            mmio: Some(self.memory.mmio.snapshot())
+            mmio: Some(self.memory.mmio.snapshot()),
+            cp0: HashMap::from([
+                ("BadVAddr".to_string(), self.cp0[CP0_BADVADDR]),
+                ("Status".to_string(), self.cp0[CP0_STATUS]),
+                ("Cause".to_string(), self.cp0[CP0_CAUSE]),
+                ("EPC".to_string(), self.cp0[CP0_EPC]),
+            ]),
+            fpr: (0..32).map(|n| (format!("$f{}", n), self.fpr[n])).collect(),
+            fcsr: self.fcsr,
+            hart: self.hart_id
         }
     }
 
     pub fn get_program(&self) -> Option<&Program> {
-        self.program.as_ref()
+        self.program.as_deref()
+    }
+
+    /// disassembles `count` words of memory starting at `start` using the program's labels
+    pub fn disassemble(&mut self, start: u32, count: usize) -> Vec<String> {
+        let empty = HashMap::new();
+        let symbols = self.program.as_ref().map(|p| &p.symbol_table).unwrap_or(&empty);
+
+        disassembler::disassemble(&mut self.memory, start, count, symbols)
+    }
+
+    /// disassembles the instruction at $pc (`None` if $pc is outside the program)
+    pub fn current_instruction(&mut self) -> Option<String> {
+        let program = self.program.as_ref()?;
+        program.pc_to_index(self.pc)?;
+
+        let word = self.memory.load_word(self.pc) as u32;
+        Some(disassembler::disassemble_word(word, self.pc, &program.symbol_table))
     }
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+use crate::encoding::decode;
+use crate::memory::Memory;
+use std::collections::HashMap;
+
+/// renders one machine word as `0x00400000: addi $t0, $zero, 5`
+/// words that do not decode to an instruction are shown as `.word` data
+pub fn disassemble_word(word: u32, address: u32, symbol_table: &HashMap<String, u32>) -> String {
+    match decode(word, address, symbol_table) {
+        Ok(insn) => format!("0x{:08x}: {}", address, insn),
+        Err(_) => format!("0x{:08x}: .word 0x{:08x}", address, word),
+    }
+}
+
+/// disassembles `count` words starting at `start` (aligned down to a word boundary)
+/// a `label:` line is emitted before any address that has labels in the symbol table
+pub fn disassemble(memory: &mut Memory, start: u32, count: usize, symbol_table: &HashMap<String, u32>) -> Vec<String> {
+    let mut lines = Vec::with_capacity(count);
+    let mut address = start & !3;
+
+    for _ in 0..count {
+        let mut labels: Vec<&String> = symbol_table.iter()
+            .filter(|(_, addr)| **addr == address)
+            .map(|(name, _)| name)
+            .collect();
+        labels.sort();
+
+        for label in labels {
+            lines.push(format!("{}:", label));
+        }
+
+        let word = memory.load_word(address) as u32;
+        lines.push(disassemble_word(word, address, symbol_table));
+
+        address = address.wrapping_add(4);
+    }
+
+    lines
+}
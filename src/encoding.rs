@@ -0,0 +1,665 @@
+use crate::instruction::{CoreInstruction, FpCondition, FpFormat, FpRegister, Register, TrapCondition};
+use crate::program::EmuError;
+use std::collections::HashMap;
+
+/// register names indexed by register number
+pub const REGISTER_NAMES: [&str; 32] = [
+    "$zero", "$at", "$v0", "$v1",
+    "$a0", "$a1", "$a2", "$a3",
+    "$t0", "$t1", "$t2", "$t3", "$t4", "$t5", "$t6", "$t7",
+    "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7",
+    "$t8", "$t9", "$k0", "$k1",
+    "$gp", "$sp", "$fp", "$ra",
+];
+
+// opcodes (bits 31..26)
+const OP_RTYPE: u32 = 0x00;
+const OP_REGIMM: u32 = 0x01;
+const OP_J: u32 = 0x02;
+const OP_JAL: u32 = 0x03;
+const OP_BEQ: u32 = 0x04;
+const OP_COP0: u32 = 0x10;
+const OP_COP1: u32 = 0x11;
+const OP_BNE: u32 = 0x05;
+const OP_BLEZ: u32 = 0x06;
+const OP_BGTZ: u32 = 0x07;
+const OP_ADDI: u32 = 0x08;
+const OP_ADDIU: u32 = 0x09;
+const OP_SLTI: u32 = 0x0A;
+const OP_SLTIU: u32 = 0x0B;
+const OP_ANDI: u32 = 0x0C;
+const OP_ORI: u32 = 0x0D;
+const OP_XORI: u32 = 0x0E;
+const OP_LUI: u32 = 0x0F;
+const OP_SPECIAL2: u32 = 0x1C;
+const OP_SPECIAL3: u32 = 0x1F;
+const OP_LB: u32 = 0x20;
+const OP_LH: u32 = 0x21;
+const OP_LWL: u32 = 0x22;
+const OP_LW: u32 = 0x23;
+const OP_LBU: u32 = 0x24;
+const OP_LHU: u32 = 0x25;
+const OP_LWR: u32 = 0x26;
+const OP_SB: u32 = 0x28;
+const OP_SH: u32 = 0x29;
+const OP_SWL: u32 = 0x2A;
+const OP_SW: u32 = 0x2B;
+const OP_SWR: u32 = 0x2E;
+const OP_LL: u32 = 0x30;
+const OP_SC: u32 = 0x38;
+const OP_LWC1: u32 = 0x31;
+const OP_LDC1: u32 = 0x35;
+const OP_SWC1: u32 = 0x39;
+const OP_SDC1: u32 = 0x3D;
+
+// function codes for R-type instructions (bits 5..0)
+const FN_SLL: u32 = 0x00;
+const FN_SRL: u32 = 0x02;
+const FN_SRA: u32 = 0x03;
+const FN_SLLV: u32 = 0x04;
+const FN_SRLV: u32 = 0x06;
+const FN_SRAV: u32 = 0x07;
+const FN_JR: u32 = 0x08;
+const FN_JALR: u32 = 0x09;
+const FN_MOVZ: u32 = 0x0A;
+const FN_MOVN: u32 = 0x0B;
+const FN_SYSCALL: u32 = 0x0C;
+const FN_BREAK: u32 = 0x0D;
+const FN_SYNC: u32 = 0x0F;
+const FN_MFHI: u32 = 0x10;
+const FN_MFLO: u32 = 0x12;
+const FN_MULT: u32 = 0x18;
+const FN_MULTU: u32 = 0x19;
+const FN_DIV: u32 = 0x1A;
+const FN_DIVU: u32 = 0x1B;
+const FN_ADD: u32 = 0x20;
+const FN_ADDU: u32 = 0x21;
+const FN_SUB: u32 = 0x22;
+const FN_SUBU: u32 = 0x23;
+const FN_AND: u32 = 0x24;
+const FN_OR: u32 = 0x25;
+const FN_XOR: u32 = 0x26;
+const FN_NOR: u32 = 0x27;
+const FN_SLT: u32 = 0x2A;
+const FN_SLTU: u32 = 0x2B;
+const FN_TGE: u32 = 0x30;
+const FN_TGEU: u32 = 0x31;
+const FN_TLT: u32 = 0x32;
+const FN_TLTU: u32 = 0x33;
+const FN_TEQ: u32 = 0x34;
+const FN_TNE: u32 = 0x36;
+
+// SPECIAL2 function codes
+const FN2_MADD: u32 = 0x00;
+const FN2_MADDU: u32 = 0x01;
+const FN2_MUL: u32 = 0x02;
+const FN2_MSUB: u32 = 0x04;
+const FN2_MSUBU: u32 = 0x05;
+const FN2_CLZ: u32 = 0x20;
+const FN2_CLO: u32 = 0x21;
+
+// SPECIAL3 function codes, BSHFL selects seb/seh through the sa field
+const FN3_EXT: u32 = 0x00;
+const FN3_INS: u32 = 0x04;
+const FN3_BSHFL: u32 = 0x20;
+const BSHFL_SEB: u32 = 0x10;
+const BSHFL_SEH: u32 = 0x18;
+
+// REGIMM rt field (bits 20..16) selecting the zero-compare branch or immediate trap
+const RT_BLTZ: u32 = 0x00;
+const RT_BGEZ: u32 = 0x01;
+const RT_TGEI: u32 = 0x08;
+const RT_TGEIU: u32 = 0x09;
+const RT_TLTI: u32 = 0x0A;
+const RT_TLTIU: u32 = 0x0B;
+const RT_TEQI: u32 = 0x0C;
+const RT_TNEI: u32 = 0x0E;
+const RT_BLTZAL: u32 = 0x10;
+const RT_BGEZAL: u32 = 0x11;
+
+// coprocessor 0 rs field (bits 25..21) and function codes
+const COP0_MF: u32 = 0x00;
+const COP0_MT: u32 = 0x04;
+const COP0_CO: u32 = 0x10;
+const FN_ERET: u32 = 0x18;
+
+// coprocessor 1 rs field: moves, branches on a condition code and the operand format
+const COP1_MF: u32 = 0x00;
+const COP1_MT: u32 = 0x04;
+const COP1_BC: u32 = 0x08;
+const FMT_S: u32 = 0x10;
+const FMT_D: u32 = 0x11;
+const FMT_W: u32 = 0x14;
+
+// coprocessor 1 function codes
+const FN_FP_ADD: u32 = 0x00;
+const FN_FP_SUB: u32 = 0x01;
+const FN_FP_MUL: u32 = 0x02;
+const FN_FP_DIV: u32 = 0x03;
+const FN_FP_SQRT: u32 = 0x04;
+const FN_FP_ABS: u32 = 0x05;
+const FN_FP_MOV: u32 = 0x06;
+const FN_FP_NEG: u32 = 0x07;
+const FN_CVT_S: u32 = 0x20;
+const FN_CVT_D: u32 = 0x21;
+const FN_CVT_W: u32 = 0x24;
+const FN_C_EQ: u32 = 0x32;
+const FN_C_LT: u32 = 0x3C;
+const FN_C_LE: u32 = 0x3E;
+
+/// returns the register number for a register name, number or alias (e.g. "$t0", "$8" -> 8 and "$s8" -> 30)
+pub fn register_number(name: &str) -> Option<u32> {
+    if let Some(i) = REGISTER_NAMES.iter().position(|r| *r == name) {
+        return Some(i as u32);
+    }
+
+    match name.strip_prefix('$')? {
+        // $fp doubles as a ninth saved register
+        "s8" => Some(30),
+        n if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => n.parse::<u32>().ok().filter(|n| *n < 32),
+        _ => None,
+    }
+}
+
+/// returns the register name for a register number (e.g. 8 -> "$t0")
+pub fn register_name(number: u32) -> String {
+    REGISTER_NAMES[(number & 0x1F) as usize].to_string()
+}
+
+/// returns the number of a floating-point register name (e.g. "$f12" -> 12)
+pub fn fp_register_number(name: &str) -> Option<u32> {
+    name.strip_prefix("$f")
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|n| *n < 32)
+}
+
+/// returns the floating-point register name for a register number (e.g. 12 -> "$f12")
+pub fn fp_register_name(number: u32) -> String {
+    format!("$f{}", number & 0x1F)
+}
+
+fn fmt_field(fmt: FpFormat) -> u32 {
+    match fmt {
+        FpFormat::S => FMT_S,
+        FpFormat::D => FMT_D,
+        FpFormat::W => FMT_W,
+    }
+}
+
+fn cop1(fmt: FpFormat, ft: u32, fs: u32, fd: u32, funct: u32) -> u32 {
+    (OP_COP1 << 26) | (fmt_field(fmt) << 21) | (ft << 16) | (fs << 11) | (fd << 6) | funct
+}
+
+/// bc1t/bc1f with the condition code and true/false bit in the rt field
+fn branch_cop1(cc: u32, on_true: bool, offset: u32) -> u32 {
+    (OP_COP1 << 26) | (COP1_BC << 21) | (((cc & 0x7) << 2 | on_true as u32) << 16) | offset
+}
+
+fn r_type(rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) -> u32 {
+    (OP_RTYPE << 26) | (rs << 21) | (rt << 16) | (rd << 11) | (shamt << 6) | funct
+}
+
+fn i_type(opcode: u32, rs: u32, rt: u32, imm: u32) -> u32 {
+    (opcode << 26) | (rs << 21) | (rt << 16) | (imm & 0xFFFF)
+}
+
+/// checks that a sign-extended immediate fits in 16 bits
+fn signed_imm(imm: i32) -> Result<u32, EmuError> {
+    if imm >= i16::MIN as i32 && imm <= i16::MAX as i32 {
+        Ok(imm as u32 & 0xFFFF)
+    } else {
+        Err(EmuError::InvalidImm(format!("immediate {} does not fit in 16 signed bits", imm)))
+    }
+}
+
+/// checks that a zero-extended immediate fits in 16 bits
+fn unsigned_imm(imm: u32) -> Result<u32, EmuError> {
+    if imm <= u16::MAX as u32 {
+        Ok(imm)
+    } else {
+        Err(EmuError::InvalidImm(format!("immediate {} does not fit in 16 unsigned bits", imm)))
+    }
+}
+
+fn shift_amount(sa: u32) -> Result<u32, EmuError> {
+    if sa < 32 {
+        Ok(sa)
+    } else {
+        Err(EmuError::InvalidImm(format!("shift amount {} must be between 0 and 31", sa)))
+    }
+}
+
+fn special2(rs: u32, rt: u32, rd: u32, funct: u32) -> u32 {
+    (OP_SPECIAL2 << 26) | (rs << 21) | (rt << 16) | (rd << 11) | funct
+}
+
+fn special3(rs: u32, rt: u32, rd: u32, sa: u32, funct: u32) -> u32 {
+    (OP_SPECIAL3 << 26) | (rs << 21) | (rt << 16) | (rd << 11) | (sa << 6) | funct
+}
+
+/// checks the bit field of ext/ins (pos + size must stay within the word)
+fn bit_field(pos: u32, size: u32) -> Result<(u32, u32), EmuError> {
+    if pos < 32 && size >= 1 && pos + size <= 32 {
+        Ok((pos, size))
+    } else {
+        Err(EmuError::InvalidImm(format!("bit field at {} of size {} does not fit in 32 bits", pos, size)))
+    }
+}
+
+/// checks that a trap or break code fits in its `bits`-wide field
+fn trap_code(code: u32, bits: u32) -> Result<u32, EmuError> {
+    if code < (1 << bits) {
+        Ok(code)
+    } else {
+        Err(EmuError::InvalidImm(format!("code {} does not fit in {} bits", code, bits)))
+    }
+}
+
+fn label_address(label: &str, symbol_table: &HashMap<String, u32>) -> Result<u32, EmuError> {
+    symbol_table.get(label).copied().ok_or(EmuError::UndefinedLabel(label.to_string()))
+}
+
+/// PC-relative word offset used by branches (relative to the delay slot at PC+4)
+fn branch_offset(pc: u32, label: &str, symbol_table: &HashMap<String, u32>) -> Result<u32, EmuError> {
+    let target = label_address(label, symbol_table)?;
+    let offset = (target.wrapping_sub(pc.wrapping_add(4)) as i32) >> 2;
+
+    signed_imm(offset).map_err(|_| EmuError::InvalidJump(target))
+}
+
+/// pseudo-direct word address used by j/jal (must stay in the same 256MB region)
+fn jump_index(pc: u32, label: &str, symbol_table: &HashMap<String, u32>) -> Result<u32, EmuError> {
+    let target = label_address(label, symbol_table)?;
+
+    if (target & 0xF000_0000) != (pc.wrapping_add(4) & 0xF000_0000) || target % 4 != 0 {
+        return Err(EmuError::InvalidJump(target));
+    }
+
+    Ok((target >> 2) & 0x03FF_FFFF)
+}
+
+/// encodes an instruction located at `pc` as a 32-bit MIPS32 machine word
+pub fn encode(insn: &CoreInstruction, pc: u32, symbol_table: &HashMap<String, u32>) -> Result<u32, EmuError> {
+    let word = match insn {
+        CoreInstruction::Add { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_ADD),
+        CoreInstruction::Addu { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_ADDU),
+        CoreInstruction::Sub { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SUB),
+        CoreInstruction::Subu { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SUBU),
+        CoreInstruction::And { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_AND),
+        CoreInstruction::Or { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_OR),
+        CoreInstruction::Xor { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_XOR),
+        CoreInstruction::Nor { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_NOR),
+        CoreInstruction::Slt { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SLT),
+        CoreInstruction::Sltu { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SLTU),
+
+        CoreInstruction::Mult { rs, rt } => r_type(rs.number(), rt.number(), 0, 0, FN_MULT),
+        CoreInstruction::Multu { rs, rt } => r_type(rs.number(), rt.number(), 0, 0, FN_MULTU),
+        CoreInstruction::Div { rs, rt } => r_type(rs.number(), rt.number(), 0, 0, FN_DIV),
+        CoreInstruction::Divu { rs, rt } => r_type(rs.number(), rt.number(), 0, 0, FN_DIVU),
+        CoreInstruction::Mfhi { rd } => r_type(0, 0, rd.number(), 0, FN_MFHI),
+        CoreInstruction::Mflo { rd } => r_type(0, 0, rd.number(), 0, FN_MFLO),
+
+        CoreInstruction::Sll { rd, rt, sa } => r_type(0, rt.number(), rd.number(), shift_amount(*sa)?, FN_SLL),
+        CoreInstruction::Srl { rd, rt, sa } => r_type(0, rt.number(), rd.number(), shift_amount(*sa)?, FN_SRL),
+        CoreInstruction::Sra { rd, rt, imm } => r_type(0, rt.number(), rd.number(), shift_amount(*imm as u32)?, FN_SRA),
+        CoreInstruction::Movz { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_MOVZ),
+        CoreInstruction::Movn { rd, rs, rt } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_MOVN),
+
+        CoreInstruction::Mul { rd, rs, rt } => special2(rs.number(), rt.number(), rd.number(), FN2_MUL),
+        CoreInstruction::Madd { rs, rt } => special2(rs.number(), rt.number(), 0, FN2_MADD),
+        CoreInstruction::Maddu { rs, rt } => special2(rs.number(), rt.number(), 0, FN2_MADDU),
+        CoreInstruction::Msub { rs, rt } => special2(rs.number(), rt.number(), 0, FN2_MSUB),
+        CoreInstruction::Msubu { rs, rt } => special2(rs.number(), rt.number(), 0, FN2_MSUBU),
+
+        // clz/clo repeat rd in the rt field
+        CoreInstruction::Clz { rd, rs } => special2(rs.number(), rd.number(), rd.number(), FN2_CLZ),
+        CoreInstruction::Clo { rd, rs } => special2(rs.number(), rd.number(), rd.number(), FN2_CLO),
+
+        CoreInstruction::Seb { rd, rt } => special3(0, rt.number(), rd.number(), BSHFL_SEB, FN3_BSHFL),
+        CoreInstruction::Seh { rd, rt } => special3(0, rt.number(), rd.number(), BSHFL_SEH, FN3_BSHFL),
+        CoreInstruction::Ext { rt, rs, pos, size } => {
+            let (pos, size) = bit_field(*pos, *size)?;
+            special3(rs.number(), rt.number(), size - 1, pos, FN3_EXT)
+        },
+        CoreInstruction::Ins { rt, rs, pos, size } => {
+            let (pos, size) = bit_field(*pos, *size)?;
+            special3(rs.number(), rt.number(), pos + size - 1, pos, FN3_INS)
+        },
+
+        CoreInstruction::Sllv { rd, rt, rs } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SLLV),
+        CoreInstruction::Srlv { rd, rt, rs } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SRLV),
+        CoreInstruction::Srav { rd, rt, rs } => r_type(rs.number(), rt.number(), rd.number(), 0, FN_SRAV),
+
+        CoreInstruction::Jr { rs } => r_type(rs.number(), 0, 0, 0, FN_JR),
+        CoreInstruction::Syscall => r_type(0, 0, 0, 0, FN_SYSCALL),
+        CoreInstruction::Sync => r_type(0, 0, 0, 0, FN_SYNC),
+
+        // register traps keep a 10-bit code in bits 15..6, break a 20-bit code in bits 25..6
+        CoreInstruction::Trap { cond, rs, rt, code } => {
+            let funct = match cond {
+                TrapCondition::Eq => FN_TEQ,
+                TrapCondition::Ne => FN_TNE,
+                TrapCondition::Ge => FN_TGE,
+                TrapCondition::Geu => FN_TGEU,
+                TrapCondition::Lt => FN_TLT,
+                TrapCondition::Ltu => FN_TLTU,
+            };
+            (rs.number() << 21) | (rt.number() << 16) | (trap_code(*code, 10)? << 6) | funct
+        },
+        CoreInstruction::TrapImm { cond, rs, imm } => {
+            let selector = match cond {
+                TrapCondition::Eq => RT_TEQI,
+                TrapCondition::Ne => RT_TNEI,
+                TrapCondition::Ge => RT_TGEI,
+                TrapCondition::Geu => RT_TGEIU,
+                TrapCondition::Lt => RT_TLTI,
+                TrapCondition::Ltu => RT_TLTIU,
+            };
+            i_type(OP_REGIMM, rs.number(), selector, signed_imm(*imm)?)
+        },
+        CoreInstruction::Break { code } => (trap_code(*code, 20)? << 6) | FN_BREAK,
+
+        CoreInstruction::Addi { rt, rs, imm } => i_type(OP_ADDI, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Addiu { rt, rs, imm } => i_type(OP_ADDIU, rs.number(), rt.number(), signed_imm(*imm as i32)?),
+        CoreInstruction::Slti { rt, rs, imm } => i_type(OP_SLTI, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Sltiu { rt, rs, imm } => i_type(OP_SLTIU, rs.number(), rt.number(), signed_imm(*imm as i32)?),
+        CoreInstruction::Andi { rt, rs, imm } => i_type(OP_ANDI, rs.number(), rt.number(), unsigned_imm(*imm)?),
+        CoreInstruction::Ori { rt, rs, imm } => i_type(OP_ORI, rs.number(), rt.number(), unsigned_imm(*imm)?),
+        CoreInstruction::Xori { rt, rs, imm } => i_type(OP_XORI, rs.number(), rt.number(), unsigned_imm(*imm)?),
+        // bits shifted out of the register by `lui` are dropped, so only the low half is kept
+        CoreInstruction::Lui { rt, imm } => i_type(OP_LUI, 0, rt.number(), *imm & 0xFFFF),
+
+        CoreInstruction::Lb { rt, rs, imm } => i_type(OP_LB, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Lh { rt, rs, imm } => i_type(OP_LH, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Lw { rt, rs, imm } => i_type(OP_LW, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Sb { rt, rs, imm } => i_type(OP_SB, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Sh { rt, rs, imm } => i_type(OP_SH, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Sw { rt, rs, imm } => i_type(OP_SW, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Lbu { rt, rs, imm } => i_type(OP_LBU, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Lhu { rt, rs, imm } => i_type(OP_LHU, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Lwl { rt, rs, imm } => i_type(OP_LWL, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Lwr { rt, rs, imm } => i_type(OP_LWR, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Swl { rt, rs, imm } => i_type(OP_SWL, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Swr { rt, rs, imm } => i_type(OP_SWR, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Ll { rt, rs, imm } => i_type(OP_LL, rs.number(), rt.number(), signed_imm(*imm)?),
+        CoreInstruction::Sc { rt, rs, imm } => i_type(OP_SC, rs.number(), rt.number(), signed_imm(*imm)?),
+
+        CoreInstruction::Beq { rs, rt, label } => i_type(OP_BEQ, rs.number(), rt.number(), branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bne { rs, rt, label } => i_type(OP_BNE, rs.number(), rt.number(), branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Blez { rs, label } => i_type(OP_BLEZ, rs.number(), 0, branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bgtz { rs, label } => i_type(OP_BGTZ, rs.number(), 0, branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bltz { rs, label } => i_type(OP_REGIMM, rs.number(), RT_BLTZ, branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bgez { rs, label } => i_type(OP_REGIMM, rs.number(), RT_BGEZ, branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bltzal { rs, label } => i_type(OP_REGIMM, rs.number(), RT_BLTZAL, branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bgezal { rs, label } => i_type(OP_REGIMM, rs.number(), RT_BGEZAL, branch_offset(pc, label, symbol_table)?),
+
+        CoreInstruction::J { label } => (OP_J << 26) | jump_index(pc, label, symbol_table)?,
+        CoreInstruction::Jal { label } => (OP_JAL << 26) | jump_index(pc, label, symbol_table)?,
+        CoreInstruction::Jalr { rd, rs } => r_type(rs.number(), 0, rd.number(), 0, FN_JALR),
+
+        CoreInstruction::Mfc0 { rt, rd } => (OP_COP0 << 26) | (COP0_MF << 21) | (rt.number() << 16) | (rd << 11),
+        CoreInstruction::Mtc0 { rt, rd } => (OP_COP0 << 26) | (COP0_MT << 21) | (rt.number() << 16) | (rd << 11),
+        CoreInstruction::Eret => (OP_COP0 << 26) | (COP0_CO << 21) | FN_ERET,
+
+        CoreInstruction::AddF { fmt, fd, fs, ft } => cop1(*fmt, ft.number(), fs.number(), fd.number(), FN_FP_ADD),
+        CoreInstruction::SubF { fmt, fd, fs, ft } => cop1(*fmt, ft.number(), fs.number(), fd.number(), FN_FP_SUB),
+        CoreInstruction::MulF { fmt, fd, fs, ft } => cop1(*fmt, ft.number(), fs.number(), fd.number(), FN_FP_MUL),
+        CoreInstruction::DivF { fmt, fd, fs, ft } => cop1(*fmt, ft.number(), fs.number(), fd.number(), FN_FP_DIV),
+        CoreInstruction::SqrtF { fmt, fd, fs } => cop1(*fmt, 0, fs.number(), fd.number(), FN_FP_SQRT),
+        CoreInstruction::AbsF { fmt, fd, fs } => cop1(*fmt, 0, fs.number(), fd.number(), FN_FP_ABS),
+        CoreInstruction::MovF { fmt, fd, fs } => cop1(*fmt, 0, fs.number(), fd.number(), FN_FP_MOV),
+        CoreInstruction::NegF { fmt, fd, fs } => cop1(*fmt, 0, fs.number(), fd.number(), FN_FP_NEG),
+
+        // the format field holds the source format, the function code the destination
+        CoreInstruction::Cvt { to, from, fd, fs } => {
+            let funct = match to {
+                FpFormat::S => FN_CVT_S,
+                FpFormat::D => FN_CVT_D,
+                FpFormat::W => FN_CVT_W,
+            };
+            cop1(*from, 0, fs.number(), fd.number(), funct)
+        },
+
+        // the condition code is stored in the upper 3 bits of the fd field
+        CoreInstruction::CompareF { cond, fmt, cc, fs, ft } => {
+            let funct = match cond {
+                FpCondition::Eq => FN_C_EQ,
+                FpCondition::Lt => FN_C_LT,
+                FpCondition::Le => FN_C_LE,
+            };
+            cop1(*fmt, ft.number(), fs.number(), (cc & 0x7) << 2, funct)
+        },
+
+        CoreInstruction::Bc1t { cc, label } => branch_cop1(*cc, true, branch_offset(pc, label, symbol_table)?),
+        CoreInstruction::Bc1f { cc, label } => branch_cop1(*cc, false, branch_offset(pc, label, symbol_table)?),
+
+        CoreInstruction::Lwc1 { ft, rs, imm } => i_type(OP_LWC1, rs.number(), ft.number(), signed_imm(*imm)?),
+        CoreInstruction::Swc1 { ft, rs, imm } => i_type(OP_SWC1, rs.number(), ft.number(), signed_imm(*imm)?),
+        CoreInstruction::Ldc1 { ft, rs, imm } => i_type(OP_LDC1, rs.number(), ft.number(), signed_imm(*imm)?),
+        CoreInstruction::Sdc1 { ft, rs, imm } => i_type(OP_SDC1, rs.number(), ft.number(), signed_imm(*imm)?),
+
+        CoreInstruction::Mfc1 { rt, fs } => (OP_COP1 << 26) | (COP1_MF << 21) | (rt.number() << 16) | (fs.number() << 11),
+        CoreInstruction::Mtc1 { rt, fs } => (OP_COP1 << 26) | (COP1_MT << 21) | (rt.number() << 16) | (fs.number() << 11),
+    };
+
+    Ok(word)
+}
+
+/// finds a label for a text address, falling back to the hex address when there is none
+pub fn label_for_address(address: u32, symbol_table: &HashMap<String, u32>) -> String {
+    symbol_table.iter()
+        .filter(|(_, addr)| **addr == address)
+        .map(|(name, _)| name)
+        .min()
+        .cloned()
+        .unwrap_or_else(|| format!("0x{:08x}", address))
+}
+
+/// decodes a 32-bit MIPS32 machine word located at `pc` back into an instruction
+pub fn decode(word: u32, pc: u32, symbol_table: &HashMap<String, u32>) -> Result<CoreInstruction, EmuError> {
+    let opcode = word >> 26;
+    let rs = Register::from_number(word >> 21);
+    let rt = Register::from_number(word >> 16);
+    let rd = Register::from_number(word >> 11);
+    let shamt = (word >> 6) & 0x1F;
+    let funct = word & 0x3F;
+
+    let zero_imm = word & 0xFFFF;
+    let sign_imm = (word & 0xFFFF) as u16 as i16 as i32;
+
+    let branch_label = || {
+        let target = pc.wrapping_add(4).wrapping_add((sign_imm << 2) as u32);
+        label_for_address(target, symbol_table)
+    };
+
+    let jump_label = || {
+        let target = (pc.wrapping_add(4) & 0xF000_0000) | ((word & 0x03FF_FFFF) << 2);
+        label_for_address(target, symbol_table)
+    };
+
+    let insn = match opcode {
+        OP_RTYPE => match funct {
+            FN_ADD => CoreInstruction::Add { rd, rs, rt },
+            FN_ADDU => CoreInstruction::Addu { rd, rs, rt },
+            FN_SUB => CoreInstruction::Sub { rd, rs, rt },
+            FN_SUBU => CoreInstruction::Subu { rd, rs, rt },
+            FN_AND => CoreInstruction::And { rd, rs, rt },
+            FN_OR => CoreInstruction::Or { rd, rs, rt },
+            FN_XOR => CoreInstruction::Xor { rd, rs, rt },
+            FN_NOR => CoreInstruction::Nor { rd, rs, rt },
+            FN_SLT => CoreInstruction::Slt { rd, rs, rt },
+            FN_SLTU => CoreInstruction::Sltu { rd, rs, rt },
+            FN_MULT => CoreInstruction::Mult { rs, rt },
+            FN_MULTU => CoreInstruction::Multu { rs, rt },
+            FN_DIV => CoreInstruction::Div { rs, rt },
+            FN_DIVU => CoreInstruction::Divu { rs, rt },
+            FN_MFHI => CoreInstruction::Mfhi { rd },
+            FN_MFLO => CoreInstruction::Mflo { rd },
+            FN_SLL => CoreInstruction::Sll { rd, rt, sa: shamt },
+            FN_SRL => CoreInstruction::Srl { rd, rt, sa: shamt },
+            FN_SRA => CoreInstruction::Sra { rd, rt, imm: shamt as i32 },
+            FN_SLLV => CoreInstruction::Sllv { rd, rt, rs },
+            FN_SRLV => CoreInstruction::Srlv { rd, rt, rs },
+            FN_SRAV => CoreInstruction::Srav { rd, rt, rs },
+            FN_JR => CoreInstruction::Jr { rs },
+            FN_JALR => CoreInstruction::Jalr { rd, rs },
+            FN_MOVZ => CoreInstruction::Movz { rd, rs, rt },
+            FN_MOVN => CoreInstruction::Movn { rd, rs, rt },
+            FN_SYSCALL => CoreInstruction::Syscall,
+            FN_SYNC => CoreInstruction::Sync,
+            FN_BREAK => CoreInstruction::Break { code: (word >> 6) & 0xF_FFFF },
+            FN_TEQ => CoreInstruction::Trap { cond: TrapCondition::Eq, rs, rt, code: (word >> 6) & 0x3FF },
+            FN_TNE => CoreInstruction::Trap { cond: TrapCondition::Ne, rs, rt, code: (word >> 6) & 0x3FF },
+            FN_TGE => CoreInstruction::Trap { cond: TrapCondition::Ge, rs, rt, code: (word >> 6) & 0x3FF },
+            FN_TGEU => CoreInstruction::Trap { cond: TrapCondition::Geu, rs, rt, code: (word >> 6) & 0x3FF },
+            FN_TLT => CoreInstruction::Trap { cond: TrapCondition::Lt, rs, rt, code: (word >> 6) & 0x3FF },
+            FN_TLTU => CoreInstruction::Trap { cond: TrapCondition::Ltu, rs, rt, code: (word >> 6) & 0x3FF },
+            _ => return Err(EmuError::InvalidInstruction(word)),
+        },
+
+        OP_ADDI => CoreInstruction::Addi { rt, rs, imm: sign_imm },
+        OP_ADDIU => CoreInstruction::Addiu { rt, rs, imm: sign_imm as u32 },
+        OP_SLTI => CoreInstruction::Slti { rt, rs, imm: sign_imm },
+        OP_SLTIU => CoreInstruction::Sltiu { rt, rs, imm: sign_imm as u32 },
+        OP_ANDI => CoreInstruction::Andi { rt, rs, imm: zero_imm },
+        OP_ORI => CoreInstruction::Ori { rt, rs, imm: zero_imm },
+        OP_XORI => CoreInstruction::Xori { rt, rs, imm: zero_imm },
+        OP_LUI => CoreInstruction::Lui { rt, imm: zero_imm },
+
+        OP_LB => CoreInstruction::Lb { rt, rs, imm: sign_imm },
+        OP_LH => CoreInstruction::Lh { rt, rs, imm: sign_imm },
+        OP_LW => CoreInstruction::Lw { rt, rs, imm: sign_imm },
+        OP_SB => CoreInstruction::Sb { rt, rs, imm: sign_imm },
+        OP_SH => CoreInstruction::Sh { rt, rs, imm: sign_imm },
+        OP_SW => CoreInstruction::Sw { rt, rs, imm: sign_imm },
+        OP_LBU => CoreInstruction::Lbu { rt, rs, imm: sign_imm },
+        OP_LHU => CoreInstruction::Lhu { rt, rs, imm: sign_imm },
+        OP_LWL => CoreInstruction::Lwl { rt, rs, imm: sign_imm },
+        OP_LWR => CoreInstruction::Lwr { rt, rs, imm: sign_imm },
+        OP_SWL => CoreInstruction::Swl { rt, rs, imm: sign_imm },
+        OP_SWR => CoreInstruction::Swr { rt, rs, imm: sign_imm },
+        OP_LL => CoreInstruction::Ll { rt, rs, imm: sign_imm },
+        OP_SC => CoreInstruction::Sc { rt, rs, imm: sign_imm },
+
+        OP_BEQ => CoreInstruction::Beq { rs, rt, label: branch_label() },
+        OP_BNE => CoreInstruction::Bne { rs, rt, label: branch_label() },
+        OP_BLEZ => CoreInstruction::Blez { rs, label: branch_label() },
+        OP_BGTZ => CoreInstruction::Bgtz { rs, label: branch_label() },
+
+        OP_REGIMM => match (word >> 16) & 0x1F {
+            RT_BLTZ => CoreInstruction::Bltz { rs, label: branch_label() },
+            RT_BGEZ => CoreInstruction::Bgez { rs, label: branch_label() },
+            RT_BLTZAL => CoreInstruction::Bltzal { rs, label: branch_label() },
+            RT_BGEZAL => CoreInstruction::Bgezal { rs, label: branch_label() },
+            RT_TEQI => CoreInstruction::TrapImm { cond: TrapCondition::Eq, rs, imm: sign_imm },
+            RT_TNEI => CoreInstruction::TrapImm { cond: TrapCondition::Ne, rs, imm: sign_imm },
+            RT_TGEI => CoreInstruction::TrapImm { cond: TrapCondition::Ge, rs, imm: sign_imm },
+            RT_TGEIU => CoreInstruction::TrapImm { cond: TrapCondition::Geu, rs, imm: sign_imm },
+            RT_TLTI => CoreInstruction::TrapImm { cond: TrapCondition::Lt, rs, imm: sign_imm },
+            RT_TLTIU => CoreInstruction::TrapImm { cond: TrapCondition::Ltu, rs, imm: sign_imm },
+            _ => return Err(EmuError::InvalidInstruction(word)),
+        },
+
+        OP_J => CoreInstruction::J { label: jump_label() },
+        OP_JAL => CoreInstruction::Jal { label: jump_label() },
+
+        OP_SPECIAL2 => match funct {
+            FN2_MUL => CoreInstruction::Mul { rd, rs, rt },
+            FN2_MADD => CoreInstruction::Madd { rs, rt },
+            FN2_MADDU => CoreInstruction::Maddu { rs, rt },
+            FN2_MSUB => CoreInstruction::Msub { rs, rt },
+            FN2_MSUBU => CoreInstruction::Msubu { rs, rt },
+            FN2_CLZ => CoreInstruction::Clz { rd, rs },
+            FN2_CLO => CoreInstruction::Clo { rd, rs },
+            _ => return Err(EmuError::InvalidInstruction(word)),
+        },
+
+        OP_SPECIAL3 => {
+            let field = (word >> 11) & 0x1F;
+
+            match (funct, shamt) {
+                (FN3_EXT, _) if shamt + field < 32 => CoreInstruction::Ext { rt, rs, pos: shamt, size: field + 1 },
+                (FN3_INS, _) if field >= shamt => CoreInstruction::Ins { rt, rs, pos: shamt, size: field - shamt + 1 },
+                (FN3_BSHFL, BSHFL_SEB) => CoreInstruction::Seb { rd, rt },
+                (FN3_BSHFL, BSHFL_SEH) => CoreInstruction::Seh { rd, rt },
+                _ => return Err(EmuError::InvalidInstruction(word)),
+            }
+        },
+
+        OP_COP0 => match (word >> 21) & 0x1F {
+            COP0_MF => CoreInstruction::Mfc0 { rt, rd: (word >> 11) & 0x1F },
+            COP0_MT => CoreInstruction::Mtc0 { rt, rd: (word >> 11) & 0x1F },
+            COP0_CO if funct == FN_ERET => CoreInstruction::Eret,
+            _ => return Err(EmuError::InvalidInstruction(word)),
+        },
+
+        OP_COP1 => decode_cop1(word, branch_label)?,
+
+        OP_LWC1 => CoreInstruction::Lwc1 { ft: FpRegister::from_number(word >> 16), rs, imm: sign_imm },
+        OP_SWC1 => CoreInstruction::Swc1 { ft: FpRegister::from_number(word >> 16), rs, imm: sign_imm },
+        OP_LDC1 => CoreInstruction::Ldc1 { ft: FpRegister::from_number(word >> 16), rs, imm: sign_imm },
+        OP_SDC1 => CoreInstruction::Sdc1 { ft: FpRegister::from_number(word >> 16), rs, imm: sign_imm },
+
+        _ => return Err(EmuError::InvalidInstruction(word)),
+    };
+
+    Ok(insn)
+}
+
+/// decodes a coprocessor 1 (floating-point) instruction word
+fn decode_cop1(word: u32, branch_label: impl Fn() -> String) -> Result<CoreInstruction, EmuError> {
+    let rt = Register::from_number(word >> 16);
+    let ft = FpRegister::from_number(word >> 16);
+    let fs = FpRegister::from_number(word >> 11);
+    let fd = FpRegister::from_number(word >> 6);
+    let funct = word & 0x3F;
+
+    let fmt = match (word >> 21) & 0x1F {
+        COP1_MF => return Ok(CoreInstruction::Mfc1 { rt, fs }),
+        COP1_MT => return Ok(CoreInstruction::Mtc1 { rt, fs }),
+        COP1_BC => {
+            let cc = (word >> 18) & 0x7;
+            let label = branch_label();
+
+            return Ok(if word & (1 << 16) != 0 {
+                CoreInstruction::Bc1t { cc, label }
+            } else {
+                CoreInstruction::Bc1f { cc, label }
+            });
+        },
+        FMT_S => FpFormat::S,
+        FMT_D => FpFormat::D,
+        FMT_W => FpFormat::W,
+        _ => return Err(EmuError::InvalidInstruction(word)),
+    };
+
+    // words can only be converted, every other operation is single or double precision
+    let insn = match (funct, fmt) {
+        (FN_CVT_S, FpFormat::D | FpFormat::W) => CoreInstruction::Cvt { to: FpFormat::S, from: fmt, fd, fs },
+        (FN_CVT_D, FpFormat::S | FpFormat::W) => CoreInstruction::Cvt { to: FpFormat::D, from: fmt, fd, fs },
+        (FN_CVT_W, FpFormat::S | FpFormat::D) => CoreInstruction::Cvt { to: FpFormat::W, from: fmt, fd, fs },
+        (_, FpFormat::W) => return Err(EmuError::InvalidInstruction(word)),
+
+        (FN_FP_ADD, _) => CoreInstruction::AddF { fmt, fd, fs, ft },
+        (FN_FP_SUB, _) => CoreInstruction::SubF { fmt, fd, fs, ft },
+        (FN_FP_MUL, _) => CoreInstruction::MulF { fmt, fd, fs, ft },
+        (FN_FP_DIV, _) => CoreInstruction::DivF { fmt, fd, fs, ft },
+        (FN_FP_SQRT, _) => CoreInstruction::SqrtF { fmt, fd, fs },
+        (FN_FP_ABS, _) => CoreInstruction::AbsF { fmt, fd, fs },
+        (FN_FP_MOV, _) => CoreInstruction::MovF { fmt, fd, fs },
+        (FN_FP_NEG, _) => CoreInstruction::NegF { fmt, fd, fs },
+
+        (FN_C_EQ | FN_C_LT | FN_C_LE, _) => {
+            let cond = match funct {
+                FN_C_EQ => FpCondition::Eq,
+                FN_C_LT => FpCondition::Lt,
+                _ => FpCondition::Le,
+            };
+            CoreInstruction::CompareF { cond, fmt, cc: (word >> 8) & 0x7, fs, ft }
+        },
+
+        _ => return Err(EmuError::InvalidInstruction(word)),
+    };
+
+    Ok(insn)
+}
@@ -0,0 +1,212 @@
+use crate::instruction::Register;
+use crate::memory::Memory;
+use crate::watch::Comparison;
+
+/// an expression over registers and memory, as written in breakpoint conditions and log messages
+/// (e.g. `$t0 == 10 && mem[$sp+4] > 0`, where `mem[...]` is the word at an address)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i32),
+    Register(Register),
+    Memory(Box<Expr>),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Compare(Comparison),
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i32),
+    Register(Register),
+    Mem,
+    Symbol(&'static str),
+}
+
+// longest symbols first so "<=" is not read as "<" then "="
+const SYMBOLS: [&str; 16] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "!", "(", ")", "[", "]"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let word_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '$' || c == '_')).unwrap_or(rest.len());
+        let (word, after) = rest.split_at(word_len);
+
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if word.starts_with('$') {
+            let register = Register::from_name(word).ok_or_else(|| format!("unknown register {}", word))?;
+            tokens.push(Token::Register(register));
+            rest = after;
+        } else if word == "mem" {
+            tokens.push(Token::Mem);
+            rest = after;
+        } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+            let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).map(|v| v as i32).ok(),
+                None => word.parse::<i32>().ok(),
+            };
+            tokens.push(Token::Number(value.ok_or_else(|| format!("invalid number {}", word))?));
+            rest = after;
+        } else {
+            let unexpected = if word.is_empty() { &rest[..rest.chars().next().unwrap().len_utf8()] } else { word };
+            return Err(format!("unexpected '{}'", unexpected));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// recursive descent over the tokens, from the loosest operator (`||`) to the tightest (unary `-` and `!`)
+struct ExprParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl ExprParser {
+    fn peek_symbol(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(symbol)) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.peek_symbol() {
+            Some(s) if s == symbol => {
+                self.position += 1;
+                Ok(())
+            },
+            _ => Err(format!("expected '{}'", symbol)),
+        }
+    }
+
+    /// parses a left-associative chain of the operators `ops` between operands parsed by `operand`
+    fn chain(&mut self, ops: &[(&str, BinaryOp)], operand: fn(&mut Self) -> Result<Expr, String>) -> Result<Expr, String> {
+        let mut left = operand(self)?;
+
+        while let Some(op) = self.peek_symbol().and_then(|s| ops.iter().find(|(symbol, _)| *symbol == s)) {
+            self.position += 1;
+            let right = operand(self)?;
+            left = Expr::Binary(op.1, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.chain(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.chain(&[("&&", BinaryOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+
+        match self.peek_symbol().and_then(Comparison::from_symbol) {
+            Some(comparison) => {
+                self.position += 1;
+                let right = self.sum()?;
+                Ok(Expr::Binary(BinaryOp::Compare(comparison), Box::new(left), Box::new(right)))
+            },
+            None => Ok(left),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        self.chain(&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        self.chain(&[("*", BinaryOp::Mul)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek_symbol() {
+            Some("-") => {
+                self.position += 1;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            },
+            Some("!") => {
+                self.position += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            },
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("unexpected end of expression")?;
+        self.position += 1;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Register(register) => Ok(Expr::Register(register)),
+            Token::Mem => {
+                self.expect("[")?;
+                let address = self.or()?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(address)))
+            },
+            Token::Symbol("(") => {
+                let inner = self.or()?;
+                self.expect(")")?;
+                Ok(inner)
+            },
+            Token::Symbol(symbol) => Err(format!("unexpected '{}'", symbol)),
+        }
+    }
+}
+
+impl Expr {
+    /// parses an expression (the error describes the first problem, e.g. "unknown register $t10")
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let mut parser = ExprParser { tokens: tokenize(text)?, position: 0 };
+        let expr = parser.or()?;
+
+        match parser.tokens.get(parser.position) {
+            None => Ok(expr),
+            Some(_) => Err("unexpected text after the expression".to_string()),
+        }
+    }
+
+    /// evaluates with 32-bit wrapping arithmetic, signed comparisons and 1/0 for true/false
+    pub fn eval(&self, registers: &[u32; 32], memory: &mut Memory) -> i32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => registers[register.index()] as i32,
+            Expr::Memory(address) => {
+                let address = address.eval(registers, memory) as u32;
+                let bytes = memory.get_memory_slice(address, 4);
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            },
+            Expr::Negate(inner) => inner.eval(registers, memory).wrapping_neg(),
+            Expr::Not(inner) => (inner.eval(registers, memory) == 0) as i32,
+            Expr::Binary(BinaryOp::And, left, right) => (left.eval(registers, memory) != 0 && right.eval(registers, memory) != 0) as i32,
+            Expr::Binary(BinaryOp::Or, left, right) => (left.eval(registers, memory) != 0 || right.eval(registers, memory) != 0) as i32,
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(registers, memory), right.eval(registers, memory));
+                match op {
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    BinaryOp::Compare(comparison) => comparison.holds(left, right) as i32,
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use crate::cpu::CallFrame;
+use crate::memory::HeapAllocation;
+
+/// registers an instruction can change, saved before it runs and compared afterwards to build its `UndoEntry`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterFile {
+    pub pc: u32,
+    pub hi: u32,
+    pub lo: u32,
+    pub fcsr: u32,
+    pub registers: [u32; 32],
+    pub fpr: [u32; 32],
+    pub cp0: [u32; 32],
+    pub delay_slot_target: Option<u32>,
+    pub exit_code: Option<i32>,
+    pub defined_registers: u32,
+    pub defined_fp_registers: u32,
+
+    /// number of calls in progress
+    pub call_depth: usize,
+
+    pub heap_break: u32,
+}
+
+/// values one instruction overwrote, which `CPU::step_back` puts back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoEntry {
+    pub pc: u32,
+    pub hi: u32,
+    pub lo: u32,
+    pub fcsr: u32,
+    pub delay_slot_target: Option<u32>,
+    pub exit_code: Option<i32>,
+    pub defined_registers: u32,
+    pub defined_fp_registers: u32,
+
+    /// (register number, old value) of the general-purpose, floating-point and coprocessor 0 registers it wrote
+    pub registers: Vec<(u8, u32)>,
+    pub fp_registers: Vec<(u8, u32)>,
+    pub cp0_registers: Vec<(u8, u32)>,
+
+    /// (address, old byte) of every byte it stored, in store order
+    pub memory: Vec<(u32, u8)>,
+
+    /// number of calls in progress before it ran, and the calls it returned from
+    pub call_depth: usize,
+    pub returned_calls: Vec<CallFrame>,
+
+    /// program break and allocations before an `sbrk` moved the break
+    pub heap: Option<(u32, Vec<HeapAllocation>)>,
+}
+
+/// (index, old value) of every register that differs between the two files
+fn changed(before: &[u32; 32], after: &[u32; 32]) -> Vec<(u8, u32)> {
+    (0..32u8).filter(|i| before[*i as usize] != after[*i as usize]).map(|i| (i, before[i as usize])).collect()
+}
+
+impl UndoEntry {
+    /// builds the entry for an instruction from the registers around it, the bytes it overwrote, the calls it
+    /// returned from and the heap allocations before it ran (only needed when it can move the break)
+    /// returns `None` if it changed nothing, e.g. the program had already terminated
+    pub fn new(before: &RegisterFile, after: &RegisterFile, memory: Vec<(u32, u8)>, returned_calls: Vec<CallFrame>,
+               allocations: Option<Vec<HeapAllocation>>) -> Option<UndoEntry> {
+        if before == after && memory.is_empty() {
+            return None;
+        }
+
+        Some(UndoEntry {
+            pc: before.pc,
+            hi: before.hi,
+            lo: before.lo,
+            fcsr: before.fcsr,
+            delay_slot_target: before.delay_slot_target,
+            exit_code: before.exit_code,
+            defined_registers: before.defined_registers,
+            defined_fp_registers: before.defined_fp_registers,
+            registers: changed(&before.registers, &after.registers),
+            fp_registers: changed(&before.fpr, &after.fpr),
+            cp0_registers: changed(&before.cp0, &after.cp0),
+            memory,
+            call_depth: before.call_depth,
+            returned_calls,
+            heap: (after.heap_break != before.heap_break).then(|| (before.heap_break, allocations.unwrap_or_default())),
+        })
+    }
+}
+
+/// undo log of the most recent instructions (the oldest entry is dropped once `limit` are recorded)
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: VecDeque<UndoEntry>,
+    limit: usize,
+}
+
+impl History {
+    /// history of up to `limit` instructions (0 records nothing)
+    pub fn new(limit: usize) -> Self {
+        History { entries: VecDeque::new(), limit }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// changes the limit, dropping the oldest entries that no longer fit
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.entries.len() > limit {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn push(&mut self, entry: UndoEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
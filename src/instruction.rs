@@ -1,47 +1,232 @@
+use crate::encoding::{fp_register_number, register_number, REGISTER_NAMES};
 use crate::program::EmuError;
+use std::fmt;
+
+/// a general purpose register, resolved to its number when the program is parsed (e.g. $t0 is 8)
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Register(pub u8);
+
+impl Register {
+    pub const ZERO: Register = Register(0);
+    pub const AT: Register = Register(1);
+    pub const V0: Register = Register(2);
+    pub const A0: Register = Register(4);
+    pub const A1: Register = Register(5);
+    pub const GP: Register = Register(28);
+    pub const SP: Register = Register(29);
+    pub const FP: Register = Register(30);
+    pub const RA: Register = Register(31);
+
+    /// looks up a register by name (e.g. "$t0")
+    pub fn from_name(name: &str) -> Option<Register> {
+        register_number(name).map(|n| Register(n as u8))
+    }
+
+    /// register for the low 5 bits of an instruction field
+    pub fn from_number(number: u32) -> Register {
+        Register((number & 0x1F) as u8)
+    }
+
+    pub fn number(self) -> u32 {
+        self.0 as u32
+    }
+
+    /// index into the register file
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        REGISTER_NAMES[self.index()]
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Debug for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// a floating-point register $f0-$f31, resolved to its number when the program is parsed
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FpRegister(pub u8);
+
+impl FpRegister {
+    /// looks up a floating-point register by name (e.g. "$f12")
+    pub fn from_name(name: &str) -> Option<FpRegister> {
+        fp_register_number(name).map(|n| FpRegister(n as u8))
+    }
+
+    /// register for the low 5 bits of an instruction field
+    pub fn from_number(number: u32) -> FpRegister {
+        FpRegister((number & 0x1F) as u8)
+    }
+
+    pub fn number(self) -> u32 {
+        self.0 as u32
+    }
+
+    /// index into the floating-point register file
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for FpRegister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$f{}", self.0)
+    }
+}
+
+impl fmt::Debug for FpRegister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$f{}", self.0)
+    }
+}
+
+/// operand format of a coprocessor 1 instruction (the `.s`, `.d` or `.w` suffix)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FpFormat {
+    /// single precision
+    S,
+    /// double precision (an even/odd register pair)
+    D,
+    /// 32-bit integer word
+    W,
+}
+
+impl fmt::Display for FpFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FpFormat::S => write!(f, "s"),
+            FpFormat::D => write!(f, "d"),
+            FpFormat::W => write!(f, "w"),
+        }
+    }
+}
+
+/// condition tested by the `c.cond.fmt` compare instructions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FpCondition {
+    Eq,
+    Lt,
+    Le,
+}
+
+impl fmt::Display for FpCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FpCondition::Eq => write!(f, "eq"),
+            FpCondition::Lt => write!(f, "lt"),
+            FpCondition::Le => write!(f, "le"),
+        }
+    }
+}
+
+/// comparison tested by the conditional trap instructions (`teq`, `tgeiu`, ...)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrapCondition {
+    Eq,
+    Ne,
+    Ge,
+    Geu,
+    Lt,
+    Ltu,
+}
+
+impl TrapCondition {
+    /// returns true if the trap fires for the two operands
+    pub fn holds(&self, a: u32, b: u32) -> bool {
+        match self {
+            TrapCondition::Eq => a == b,
+            TrapCondition::Ne => a != b,
+            TrapCondition::Ge => a as i32 >= b as i32,
+            TrapCondition::Geu => a >= b,
+            TrapCondition::Lt => (a as i32) < b as i32,
+            TrapCondition::Ltu => a < b,
+        }
+    }
+}
+
+impl fmt::Display for TrapCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapCondition::Eq => write!(f, "eq"),
+            TrapCondition::Ne => write!(f, "ne"),
+            TrapCondition::Ge => write!(f, "ge"),
+            TrapCondition::Geu => write!(f, "geu"),
+            TrapCondition::Lt => write!(f, "lt"),
+            TrapCondition::Ltu => write!(f, "ltu"),
+        }
+    }
+}
 
 /// enum used to represent each MIPS instruction
 #[derive(Debug, Clone)]
 pub enum CoreInstruction {
     /// R[rd] = R[rs] + R[rt] 
-    Add { rd: String, rs: String, rt: String },
+    Add { rd: Register, rs: Register, rt: Register },
 
     /// R[rd] = R[rs] + R[rt]
-    Addu { rd: String, rs: String, rt: String },
+    Addu { rd: Register, rs: Register, rt: Register },
 
     /// R[rt] = R[rs] + SignExtImm 
-    Addi { rt: String, rs: String, imm: i32 },
+    Addi { rt: Register, rs: Register, imm: i32 },
 
 
     /// R[rt] = R[rs] + SignExtImm
-    Addiu { rt: String, rs: String, imm: u32 },
+    Addiu { rt: Register, rs: Register, imm: u32 },
 
     /// R[rd] = R[rs] - R[rt]
-    Sub { rd: String, rs: String, rt: String },
+    Sub { rd: Register, rs: Register, rt: Register },
 
     /// R[rd] = R[rs] - R[rt] 
-    Subu { rd: String, rs: String, rt: String },
+    Subu { rd: Register, rs: Register, rt: Register },
 
     /// R[rt] = imm << 16
-    Lui { rt: String, imm: u32 },
+    Lui { rt: Register, imm: u32 },
 
     /// R[rt] = M[R[rs]+SignExtImm]
-    Sb { rt: String, rs: String, imm: i32 },
+    Sb { rt: Register, rs: Register, imm: i32 },
 
     /// R[rt] = M[R[rs]+SignExtImm]
-    Lb { rt: String, rs: String, imm: i32 },
+    Lb { rt: Register, rs: Register, imm: i32 },
 
     /// R[rt] = M[R[rs]+SignExtImm]
-    Sh { rt: String, rs: String, imm: i32 },
+    Sh { rt: Register, rs: Register, imm: i32 },
 
     /// R[rt] = M[R[rs]+SignExtImm]
-    Lh { rt: String, rs: String, imm: i32 },
+    Lh { rt: Register, rs: Register, imm: i32 },
 
     /// M[R[rs]+SignExtImm] = R[rt] 
-    Sw { rs: String, imm: i32, rt: String },
+    Sw { rs: Register, imm: i32, rt: Register },
 
     /// R[rt] = M[R[rs]+SignExtImm]
-    Lw { rt: String, rs: String, imm: i32 },
+    Lw { rt: Register, rs: Register, imm: i32 },
+
+    /// R[rt] = {24'b0, M[R[rs]+SignExtImm](7:0)}
+    Lbu { rt: Register, rs: Register, imm: i32 },
+
+    /// R[rt] = {16'b0, M[R[rs]+SignExtImm](15:0)}
+    Lhu { rt: Register, rs: Register, imm: i32 },
+
+    /// merge the upper bytes of R[rt] with the unaligned word ending at R[rs]+SignExtImm
+    Lwl { rt: Register, rs: Register, imm: i32 },
+
+    /// merge the lower bytes of R[rt] with the unaligned word starting at R[rs]+SignExtImm
+    Lwr { rt: Register, rs: Register, imm: i32 },
+
+    /// store the upper bytes of R[rt] to the unaligned word ending at R[rs]+SignExtImm
+    Swl { rt: Register, rs: Register, imm: i32 },
+
+    /// store the lower bytes of R[rt] to the unaligned word starting at R[rs]+SignExtImm
+    Swr { rt: Register, rs: Register, imm: i32 },
 
     /// PC=JumpAddr
     J { label: String },
@@ -50,87 +235,396 @@ pub enum CoreInstruction {
     Jal { label: String },
 
     /// PC=R[rs] 
-    Jr { rs: String },
+    Jr { rs: Register },
+
+    /// R[rd]=PC+8;PC=R[rs]
+    Jalr { rd: Register, rs: Register },
 
     /// R[rd] = R[rs] | R[rt]
-    Or { rd: String, rs: String, rt: String },
+    Or { rd: Register, rs: Register, rt: Register },
 
     /// R[rt] = R[rs] | ZeroExtImm
-    Ori { rt: String, rs: String, imm: u32 },
+    Ori { rt: Register, rs: Register, imm: u32 },
 
     /// R[rd] = R[rs] & R[rt] 
-    And { rd: String, rs: String, rt: String },
+    And { rd: Register, rs: Register, rt: Register },
 
     /// R[rt] = R[rs] & ZeroExtImm
-    Andi { rt: String, rs: String, imm: u32 },
+    Andi { rt: Register, rs: Register, imm: u32 },
 
     /// if(R[rs] == R[rt]) PC=JumpAddr
-    Beq { rs: String, rt: String, label: String },
+    Beq { rs: Register, rt: Register, label: String },
 
     /// if(R[rs] != R[rt]) PC=JumpAddr
-    Bne { rs: String, rt: String, label: String },
+    Bne { rs: Register, rt: Register, label: String },
+
+    /// if(R[rs] >= 0) PC=BranchAddr
+    Bgez { rs: Register, label: String },
+
+    /// if(R[rs] > 0) PC=BranchAddr
+    Bgtz { rs: Register, label: String },
+
+    /// if(R[rs] <= 0) PC=BranchAddr
+    Blez { rs: Register, label: String },
+
+    /// if(R[rs] < 0) PC=BranchAddr
+    Bltz { rs: Register, label: String },
+
+    /// R[$ra]=PC+8; if(R[rs] >= 0) PC=BranchAddr
+    Bgezal { rs: Register, label: String },
+
+    /// R[$ra]=PC+8; if(R[rs] < 0) PC=BranchAddr
+    Bltzal { rs: Register, label: String },
     
     // R[rd] = (R[rs] < R[rt]) ? 1 : 0
-    Slt { rd: String, rs: String, rt: String },
+    Slt { rd: Register, rs: Register, rt: Register },
     
     ///
-    Slti { rt: String, rs: String, imm: i32 },
+    Slti { rt: Register, rs: Register, imm: i32 },
 
     ///
-    Sltiu { rt: String, rs:String,imm:u32 },
+    Sltiu { rt: Register, rs:Register,imm:u32 },
 
     ///
-    Sltu{ rd:String, rs:String, rt:String},
+    Sltu{ rd: Register, rs: Register, rt:Register},
 
     /// {Hi,Lo} = R[rs] * R[rt] 
-    Mult { rs: String, rt: String },
+    Mult { rs: Register, rt: Register },
 
     /// R[rd] = Hi
-    Mfhi { rd: String },
+    Mfhi { rd: Register },
 
     /// R[rd] = Lo 
-    Mflo { rd: String } ,
+    Mflo { rd: Register } ,
 
     /// R[rd] = R[rs] ^ R[rt] 
-    Xor { rd: String, rs: String, rt: String },
+    Xor { rd: Register, rs: Register, rt: Register },
 
     /// R[rt] = R[rs] ^ ZeroExtImm
-    Xori { rt: String, rs: String, imm: u32 },
+    Xori { rt: Register, rs: Register, imm: u32 },
 
     /// {Lo, Hi} = R[rs] / R[rt]
-    Div {rs:String, rt:String },
+    Div {rs:Register, rt:Register },
 
     /// R[rd] = ~(R[rs] | R[rt])
-    Nor {rd: String, rs: String, rt: String},
+    Nor {rd: Register, rs: Register, rt: Register},
+
+    Sll {rd: Register, rt: Register, sa: u32},
+
+    Srl {rd: Register, rt: Register, sa: u32},
 
-    Sll {rd: String, rt: String, sa: u32},
+    Sra { rd: Register, rt: Register, imm: i32 }, // added on sponsor request 
 
-    Srl {rd: String, rt: String, sa: u32},
+    /// R[rd] = R[rt] << R[rs](4:0)
+    Sllv { rd: Register, rt: Register, rs: Register },
 
-    Sra { rd: String, rt: String, imm: i32 }, // added on sponsor request 
+    /// R[rd] = R[rt] >>> R[rs](4:0)
+    Srlv { rd: Register, rt: Register, rs: Register },
 
-    Multu { rs: String, rt: String },
+    /// R[rd] = R[rt] >> R[rs](4:0) (sign-extending)
+    Srav { rd: Register, rt: Register, rs: Register },
+
+    Multu { rs: Register, rt: Register },
     
-    Divu { rs: String, rt: String }
+    Divu { rs: Register, rt: Register },
+
+    /// R[rd] = (R[rs] * R[rt])(31:0), Hi and Lo are not modified
+    Mul { rd: Register, rs: Register, rt: Register },
+
+    /// {Hi,Lo} = {Hi,Lo} + R[rs] * R[rt]
+    Madd { rs: Register, rt: Register },
+
+    /// {Hi,Lo} = {Hi,Lo} + R[rs] * R[rt] (unsigned)
+    Maddu { rs: Register, rt: Register },
+
+    /// {Hi,Lo} = {Hi,Lo} - R[rs] * R[rt]
+    Msub { rs: Register, rt: Register },
+
+    /// {Hi,Lo} = {Hi,Lo} - R[rs] * R[rt] (unsigned)
+    Msubu { rs: Register, rt: Register },
+
+    /// R[rd] = number of leading zeros in R[rs]
+    Clz { rd: Register, rs: Register },
+
+    /// R[rd] = number of leading ones in R[rs]
+    Clo { rd: Register, rs: Register },
+
+    /// if(R[rt] != 0) R[rd] = R[rs]
+    Movn { rd: Register, rs: Register, rt: Register },
+
+    /// if(R[rt] == 0) R[rd] = R[rs]
+    Movz { rd: Register, rs: Register, rt: Register },
+
+    /// R[rd] = SignExt(R[rt](7:0))
+    Seb { rd: Register, rt: Register },
+
+    /// R[rd] = SignExt(R[rt](15:0))
+    Seh { rd: Register, rt: Register },
+
+    /// R[rt] = ZeroExt(R[rs](pos+size-1:pos))
+    Ext { rt: Register, rs: Register, pos: u32, size: u32 },
+
+    /// R[rt](pos+size-1:pos) = R[rs](size-1:0)
+    Ins { rt: Register, rs: Register, pos: u32, size: u32 },
+
+    /// R[rt] = M[R[rs]+SignExtImm], reserve the word for `sc`
+    Ll { rt: Register, rs: Register, imm: i32 },
+
+    /// if(reservation held) { M[R[rs]+SignExtImm] = R[rt]; R[rt] = 1 } else R[rt] = 0
+    Sc { rt: Register, rs: Register, imm: i32 },
+
+    /// order loads and stores (memory is always sequentially consistent in the emulator)
+    Sync,
+
+    /// system call, service number in R[$v0]
+    Syscall,
+
+    /// if(R[rs] cond R[rt]) raise a trap exception, `code` is free for the handler to inspect
+    Trap { cond: TrapCondition, rs: Register, rt: Register, code: u32 },
+
+    /// if(R[rs] cond SignExtImm) raise a trap exception
+    TrapImm { cond: TrapCondition, rs: Register, imm: i32 },
+
+    /// raise a breakpoint exception, `code` is free for the handler to inspect
+    Break { code: u32 },
+
+    /// R[rt] = CP0[rd]
+    Mfc0 { rt: Register, rd: u32 },
+
+    /// CP0[rd] = R[rt]
+    Mtc0 { rt: Register, rd: u32 },
+
+    /// PC = CP0[EPC], leave exception level
+    Eret,
+
+    /// F[fd] = F[fs] + F[ft]
+    AddF { fmt: FpFormat, fd: FpRegister, fs: FpRegister, ft: FpRegister },
+
+    /// F[fd] = F[fs] - F[ft]
+    SubF { fmt: FpFormat, fd: FpRegister, fs: FpRegister, ft: FpRegister },
+
+    /// F[fd] = F[fs] * F[ft]
+    MulF { fmt: FpFormat, fd: FpRegister, fs: FpRegister, ft: FpRegister },
+
+    /// F[fd] = F[fs] / F[ft]
+    DivF { fmt: FpFormat, fd: FpRegister, fs: FpRegister, ft: FpRegister },
+
+    /// F[fd] = sqrt(F[fs])
+    SqrtF { fmt: FpFormat, fd: FpRegister, fs: FpRegister },
+
+    /// F[fd] = |F[fs]|
+    AbsF { fmt: FpFormat, fd: FpRegister, fs: FpRegister },
+
+    /// F[fd] = F[fs]
+    MovF { fmt: FpFormat, fd: FpRegister, fs: FpRegister },
+
+    /// F[fd] = -F[fs]
+    NegF { fmt: FpFormat, fd: FpRegister, fs: FpRegister },
+
+    /// F[fd] = convert F[fs] from `from` to `to` (e.g. cvt.s.w)
+    Cvt { to: FpFormat, from: FpFormat, fd: FpRegister, fs: FpRegister },
+
+    /// FPcond[cc] = F[fs] cond F[ft]
+    CompareF { cond: FpCondition, fmt: FpFormat, cc: u32, fs: FpRegister, ft: FpRegister },
+
+    /// if(FPcond[cc]) PC=BranchAddr
+    Bc1t { cc: u32, label: String },
+
+    /// if(!FPcond[cc]) PC=BranchAddr
+    Bc1f { cc: u32, label: String },
+
+    /// F[ft] = M[R[rs]+SignExtImm]
+    Lwc1 { ft: FpRegister, rs: Register, imm: i32 },
+
+    /// M[R[rs]+SignExtImm] = F[ft]
+    Swc1 { ft: FpRegister, rs: Register, imm: i32 },
+
+    /// {F[ft+1], F[ft]} = M[R[rs]+SignExtImm] (8 bytes)
+    Ldc1 { ft: FpRegister, rs: Register, imm: i32 },
+
+    /// M[R[rs]+SignExtImm] = {F[ft+1], F[ft]} (8 bytes)
+    Sdc1 { ft: FpRegister, rs: Register, imm: i32 },
+
+    /// F[fs] = R[rt]
+    Mtc1 { rt: Register, fs: FpRegister },
+
+    /// R[rt] = F[fs]
+    Mfc1 { rt: Register, fs: FpRegister }
+}
+
+/// formats the instruction in assembly syntax (e.g. `addi $t0, $zero, 5`)
+impl fmt::Display for CoreInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreInstruction::Add { rd, rs, rt } => write!(f, "add {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Addu { rd, rs, rt } => write!(f, "addu {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Sub { rd, rs, rt } => write!(f, "sub {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Subu { rd, rs, rt } => write!(f, "subu {}, {}, {}", rd, rs, rt),
+            CoreInstruction::And { rd, rs, rt } => write!(f, "and {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Or { rd, rs, rt } => write!(f, "or {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Xor { rd, rs, rt } => write!(f, "xor {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Nor { rd, rs, rt } => write!(f, "nor {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Slt { rd, rs, rt } => write!(f, "slt {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Sltu { rd, rs, rt } => write!(f, "sltu {}, {}, {}", rd, rs, rt),
+
+            CoreInstruction::Mult { rs, rt } => write!(f, "mult {}, {}", rs, rt),
+            CoreInstruction::Multu { rs, rt } => write!(f, "multu {}, {}", rs, rt),
+            CoreInstruction::Div { rs, rt } => write!(f, "div {}, {}", rs, rt),
+            CoreInstruction::Divu { rs, rt } => write!(f, "divu {}, {}", rs, rt),
+            CoreInstruction::Mfhi { rd } => write!(f, "mfhi {}", rd),
+            CoreInstruction::Mflo { rd } => write!(f, "mflo {}", rd),
+
+            CoreInstruction::Sll { rd, rt, sa } => write!(f, "sll {}, {}, {}", rd, rt, sa),
+            CoreInstruction::Srl { rd, rt, sa } => write!(f, "srl {}, {}, {}", rd, rt, sa),
+            CoreInstruction::Sra { rd, rt, imm } => write!(f, "sra {}, {}, {}", rd, rt, imm),
+            CoreInstruction::Sllv { rd, rt, rs } => write!(f, "sllv {}, {}, {}", rd, rt, rs),
+            CoreInstruction::Srlv { rd, rt, rs } => write!(f, "srlv {}, {}, {}", rd, rt, rs),
+            CoreInstruction::Srav { rd, rt, rs } => write!(f, "srav {}, {}, {}", rd, rt, rs),
+
+            CoreInstruction::Addi { rt, rs, imm } => write!(f, "addi {}, {}, {}", rt, rs, imm),
+            CoreInstruction::Addiu { rt, rs, imm } => write!(f, "addiu {}, {}, {}", rt, rs, *imm as i32),
+            CoreInstruction::Slti { rt, rs, imm } => write!(f, "slti {}, {}, {}", rt, rs, imm),
+            CoreInstruction::Sltiu { rt, rs, imm } => write!(f, "sltiu {}, {}, {}", rt, rs, *imm as i32),
+            CoreInstruction::Andi { rt, rs, imm } => write!(f, "andi {}, {}, 0x{:x}", rt, rs, imm),
+            CoreInstruction::Ori { rt, rs, imm } => write!(f, "ori {}, {}, 0x{:x}", rt, rs, imm),
+            CoreInstruction::Xori { rt, rs, imm } => write!(f, "xori {}, {}, 0x{:x}", rt, rs, imm),
+            CoreInstruction::Lui { rt, imm } => write!(f, "lui {}, 0x{:x}", rt, imm),
+
+            CoreInstruction::Lb { rt, rs, imm } => write!(f, "lb {}, {}({})", rt, imm, rs),
+            CoreInstruction::Lh { rt, rs, imm } => write!(f, "lh {}, {}({})", rt, imm, rs),
+            CoreInstruction::Lw { rt, rs, imm } => write!(f, "lw {}, {}({})", rt, imm, rs),
+            CoreInstruction::Sb { rt, rs, imm } => write!(f, "sb {}, {}({})", rt, imm, rs),
+            CoreInstruction::Sh { rt, rs, imm } => write!(f, "sh {}, {}({})", rt, imm, rs),
+            CoreInstruction::Sw { rt, rs, imm } => write!(f, "sw {}, {}({})", rt, imm, rs),
+            CoreInstruction::Lbu { rt, rs, imm } => write!(f, "lbu {}, {}({})", rt, imm, rs),
+            CoreInstruction::Lhu { rt, rs, imm } => write!(f, "lhu {}, {}({})", rt, imm, rs),
+            CoreInstruction::Lwl { rt, rs, imm } => write!(f, "lwl {}, {}({})", rt, imm, rs),
+            CoreInstruction::Lwr { rt, rs, imm } => write!(f, "lwr {}, {}({})", rt, imm, rs),
+            CoreInstruction::Swl { rt, rs, imm } => write!(f, "swl {}, {}({})", rt, imm, rs),
+            CoreInstruction::Swr { rt, rs, imm } => write!(f, "swr {}, {}({})", rt, imm, rs),
+
+            CoreInstruction::Beq { rs, rt, label } => write!(f, "beq {}, {}, {}", rs, rt, label),
+            CoreInstruction::Bne { rs, rt, label } => write!(f, "bne {}, {}, {}", rs, rt, label),
+            CoreInstruction::J { label } => write!(f, "j {}", label),
+            CoreInstruction::Jal { label } => write!(f, "jal {}", label),
+            CoreInstruction::Jr { rs } => write!(f, "jr {}", rs),
+            CoreInstruction::Jalr { rd, rs } => write!(f, "jalr {}, {}", rd, rs),
+            CoreInstruction::Bgez { rs, label } => write!(f, "bgez {}, {}", rs, label),
+            CoreInstruction::Bgtz { rs, label } => write!(f, "bgtz {}, {}", rs, label),
+            CoreInstruction::Blez { rs, label } => write!(f, "blez {}, {}", rs, label),
+            CoreInstruction::Bltz { rs, label } => write!(f, "bltz {}, {}", rs, label),
+            CoreInstruction::Bgezal { rs, label } => write!(f, "bgezal {}, {}", rs, label),
+            CoreInstruction::Bltzal { rs, label } => write!(f, "bltzal {}, {}", rs, label),
+
+            CoreInstruction::Mul { rd, rs, rt } => write!(f, "mul {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Madd { rs, rt } => write!(f, "madd {}, {}", rs, rt),
+            CoreInstruction::Maddu { rs, rt } => write!(f, "maddu {}, {}", rs, rt),
+            CoreInstruction::Msub { rs, rt } => write!(f, "msub {}, {}", rs, rt),
+            CoreInstruction::Msubu { rs, rt } => write!(f, "msubu {}, {}", rs, rt),
+            CoreInstruction::Clz { rd, rs } => write!(f, "clz {}, {}", rd, rs),
+            CoreInstruction::Clo { rd, rs } => write!(f, "clo {}, {}", rd, rs),
+            CoreInstruction::Movn { rd, rs, rt } => write!(f, "movn {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Movz { rd, rs, rt } => write!(f, "movz {}, {}, {}", rd, rs, rt),
+            CoreInstruction::Seb { rd, rt } => write!(f, "seb {}, {}", rd, rt),
+            CoreInstruction::Seh { rd, rt } => write!(f, "seh {}, {}", rd, rt),
+            CoreInstruction::Ext { rt, rs, pos, size } => write!(f, "ext {}, {}, {}, {}", rt, rs, pos, size),
+            CoreInstruction::Ins { rt, rs, pos, size } => write!(f, "ins {}, {}, {}, {}", rt, rs, pos, size),
+            CoreInstruction::Ll { rt, rs, imm } => write!(f, "ll {}, {}({})", rt, imm, rs),
+            CoreInstruction::Sc { rt, rs, imm } => write!(f, "sc {}, {}({})", rt, imm, rs),
+            CoreInstruction::Sync => write!(f, "sync"),
+            CoreInstruction::Syscall => write!(f, "syscall"),
+            CoreInstruction::Trap { cond, rs, rt, code: 0 } => write!(f, "t{} {}, {}", cond, rs, rt),
+            CoreInstruction::Trap { cond, rs, rt, code } => write!(f, "t{} {}, {}, {}", cond, rs, rt, code),
+            CoreInstruction::TrapImm { cond, rs, imm } => write!(f, "t{}i {}, {}", cond, rs, imm),
+            CoreInstruction::Break { code: 0 } => write!(f, "break"),
+            CoreInstruction::Break { code } => write!(f, "break {}", code),
+            CoreInstruction::Mfc0 { rt, rd } => write!(f, "mfc0 {}, ${}", rt, rd),
+            CoreInstruction::Mtc0 { rt, rd } => write!(f, "mtc0 {}, ${}", rt, rd),
+            CoreInstruction::Eret => write!(f, "eret"),
+
+            CoreInstruction::AddF { fmt, fd, fs, ft } => write!(f, "add.{} {}, {}, {}", fmt, fd, fs, ft),
+            CoreInstruction::SubF { fmt, fd, fs, ft } => write!(f, "sub.{} {}, {}, {}", fmt, fd, fs, ft),
+            CoreInstruction::MulF { fmt, fd, fs, ft } => write!(f, "mul.{} {}, {}, {}", fmt, fd, fs, ft),
+            CoreInstruction::DivF { fmt, fd, fs, ft } => write!(f, "div.{} {}, {}, {}", fmt, fd, fs, ft),
+            CoreInstruction::SqrtF { fmt, fd, fs } => write!(f, "sqrt.{} {}, {}", fmt, fd, fs),
+            CoreInstruction::AbsF { fmt, fd, fs } => write!(f, "abs.{} {}, {}", fmt, fd, fs),
+            CoreInstruction::MovF { fmt, fd, fs } => write!(f, "mov.{} {}, {}", fmt, fd, fs),
+            CoreInstruction::NegF { fmt, fd, fs } => write!(f, "neg.{} {}, {}", fmt, fd, fs),
+            CoreInstruction::Cvt { to, from, fd, fs } => write!(f, "cvt.{}.{} {}, {}", to, from, fd, fs),
+            CoreInstruction::CompareF { cond, fmt, cc: 0, fs, ft } => write!(f, "c.{}.{} {}, {}", cond, fmt, fs, ft),
+            CoreInstruction::CompareF { cond, fmt, cc, fs, ft } => write!(f, "c.{}.{} {}, {}, {}", cond, fmt, cc, fs, ft),
+            CoreInstruction::Bc1t { cc: 0, label } => write!(f, "bc1t {}", label),
+            CoreInstruction::Bc1t { cc, label } => write!(f, "bc1t {}, {}", cc, label),
+            CoreInstruction::Bc1f { cc: 0, label } => write!(f, "bc1f {}", label),
+            CoreInstruction::Bc1f { cc, label } => write!(f, "bc1f {}, {}", cc, label),
+            CoreInstruction::Lwc1 { ft, rs, imm } => write!(f, "lwc1 {}, {}({})", ft, imm, rs),
+            CoreInstruction::Swc1 { ft, rs, imm } => write!(f, "swc1 {}, {}({})", ft, imm, rs),
+            CoreInstruction::Ldc1 { ft, rs, imm } => write!(f, "ldc1 {}, {}({})", ft, imm, rs),
+            CoreInstruction::Sdc1 { ft, rs, imm } => write!(f, "sdc1 {}, {}({})", ft, imm, rs),
+            CoreInstruction::Mtc1 { rt, fs } => write!(f, "mtc1 {}, {}", rt, fs),
+            CoreInstruction::Mfc1 { rt, fs } => write!(f, "mfc1 {}, {}", rt, fs),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum PseudoInstruction {
-    Lw { rt: String, label: String },
-    La { rt: String, label: String },
-    Move { rd:String, rs:String },
+    Lw { rt: Register, label: String },
+    La { rt: Register, label: String },
+    Move { rd: Register, rs: Register },
 
     /// R[rd] = immediate
-    Li { rd: String, imm: u32 },
+    Li { rd: Register, imm: u32 },
 
     // if(R[rs] < R[rt]) PC=label
-    Blt { rs: String, rt: String, label: String },
+    Blt { rs: Register, rt: Register, label: String },
     // if(R[rs] > R[rt]) PC=label
-    Bgt { rs: String, rt: String, label: String },
+    Bgt { rs: Register, rt: Register, label: String },
     // if(R[rs] <= R[rt]) PC=label
-    Ble { rs: String, rt: String, label: String },
+    Ble { rs: Register, rt: Register, label: String },
     // if(R[rs] >= R[rt]) PC=label
-    Bge { rs: String, rt: String, label: String },
+    Bge { rs: Register, rt: Register, label: String },
+
+    // if(R[rs] < R[rt]) PC=label (unsigned)
+    Bltu { rs: Register, rt: Register, label: String },
+    // if(R[rs] > R[rt]) PC=label (unsigned)
+    Bgtu { rs: Register, rt: Register, label: String },
+    // if(R[rs] <= R[rt]) PC=label (unsigned)
+    Bleu { rs: Register, rt: Register, label: String },
+    // if(R[rs] >= R[rt]) PC=label (unsigned)
+    Bgeu { rs: Register, rt: Register, label: String },
+
+    // if(R[rs] == 0) PC=label
+    Beqz { rs: Register, label: String },
+    // if(R[rs] != 0) PC=label
+    Bnez { rs: Register, label: String },
+    // PC=label
+    B { label: String },
+    // R[$ra]=PC+8;PC=label
+    Bal { label: String },
+
+    /// branch comparing R[rs] with an immediate (e.g. `blt $t0, 5, label`)
+    /// `op` is the mnemonic of the register form (beq, bne, blt, ..., bgeu)
+    BranchImm { op: String, rs: Register, imm: u32, label: String },
+
+    // R[rd] = (R[rs] * imm)(31:0)
+    MulImm { rd: Register, rs: Register, imm: u32 },
+    // R[rd] = R[rs] % R[rt]
+    Rem { rd: Register, rs: Register, rt: Register },
+    // R[rd] = -R[rs]
+    Neg { rd: Register, rs: Register },
+    // R[rd] = ~R[rs]
+    Not { rd: Register, rs: Register },
+    // R[rd] = |R[rs]|
+    Abs { rd: Register, rs: Register },
+    // R[rd] = (R[rs] == R[rt]) ? 1 : 0
+    Seq { rd: Register, rs: Register, rt: Register },
+    // R[rd] = (R[rs] != R[rt]) ? 1 : 0
+    Sne { rd: Register, rs: Register, rt: Register },
+    // R[rd] = (R[rs] >= R[rt]) ? 1 : 0
+    Sge { rd: Register, rs: Register, rt: Register },
+    // R[rd] = (R[rs] > R[rt]) ? 1 : 0
+    Sgt { rd: Register, rs: Register, rt: Register },
 }
 
 #[derive(Debug, Clone)]
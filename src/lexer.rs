@@ -171,9 +171,12 @@ impl Lexer {
                 if c == '$' {
                     let mut register_end = consumeTilPuncAndWs(i + 1, line);
                     let possible_register = &line[i..register_end];
+
+                    // `$8` names a register by number, anything else (`$t0`, `$f2`) by name
+                    let is_number = possible_register.len() > 1 && possible_register[1..].bytes().all(|b| b.is_ascii_digit());
                     let new_token = Token {
                         lexeme: possible_register.to_string(),
-                        token_type: TokenType::RegisterName, // For simplicity, treat all as RegisterName
+                        token_type: if is_number { TokenType::RegisterNumber } else { TokenType::RegisterName },
                         line_number: self.line_number,
                     };
                     self.tokens.push_back(new_token);
@@ -268,10 +271,18 @@ impl Lexer {
                 if c.is_ascii_alphabetic() || c == '_' {
                     let mut end = consumeTilPuncAndWs(i + 1, line);
 
+                    // floating-point mnemonics contain dots (e.g. add.s, cvt.d.w, c.eq.s)
+                    let dotted_end = consume_dotted_mnemonic(end, line);
+                    if dotted_end != end && match_mnemonic(&line[i..dotted_end]) {
+                        end = dotted_end;
+                    }
+
                     
                     let lexeme = &line[i..end];
                     
-                    let mut match_mnemonic = match_mnemonic(&line[i..end]);
+                    // a word followed by ':' is a label even if it is also a mnemonic (e.g. "b:" or "not:")
+                    let is_label = line[end..].starts_with(':');
+                    let match_mnemonic = match_mnemonic(&line[i..end]) && !is_label;
                     let mut is_identifier = isIdentifier(line, end);
 
                     if match_mnemonic {
@@ -361,6 +372,7 @@ fn matchDirective(s: &str) -> bool {
     let directives = vec![
         ".data",
         ".text",
+        ".ktext",
         ".globl",
         ".ascii",
         ".asciiz",
@@ -386,7 +398,20 @@ fn match_mnemonic(s: &str) -> bool {
             "addi" | "addiu" | "lb" | "sb" | "lh" | "sh" | "lw" | "sw" | "ori" | "beq" | "bne" | "andi" | "la" | "lui" | 
             "move" | "mult" | "multu" | "mflo" | "mfhi" | "xor" | "xori" | "div" | "divu" | "nor" | "sll" | "srl" | "sra" |
             "slt" | "slti" | "sltiu" |
-            "blt" | "bgt" | "ble" | "bge" => true,
+            "blt" | "bgt" | "ble" | "bge" | "syscall" | "nop" |
+            "mfc0" | "mtc0" | "eret" |
+            "jalr" | "bgez" | "bgtz" | "blez" | "bltz" | "bgezal" | "bltzal" |
+            "beqz" | "bnez" | "b" | "bal" | "bltu" | "bgtu" | "bleu" | "bgeu" |
+            "lbu" | "lhu" | "sllv" | "srlv" | "srav" | "lwl" | "lwr" | "swl" | "swr" |
+            "mul" | "madd" | "maddu" | "msub" | "msubu" | "clz" | "clo" | "movn" | "movz" | "seb" | "seh" | "ext" | "ins" |
+            "rem" | "neg" | "not" | "abs" | "seq" | "sne" | "sge" | "sgt" |
+            "add.s" | "add.d" | "sub.s" | "sub.d" | "mul.s" | "mul.d" | "div.s" | "div.d" |
+            "abs.s" | "abs.d" | "neg.s" | "neg.d" | "mov.s" | "mov.d" | "sqrt.s" | "sqrt.d" |
+            "cvt.s.d" | "cvt.s.w" | "cvt.d.s" | "cvt.d.w" | "cvt.w.s" | "cvt.w.d" |
+            "c.eq.s" | "c.eq.d" | "c.lt.s" | "c.lt.d" | "c.le.s" | "c.le.d" | "bc1t" | "bc1f" |
+            "lwc1" | "swc1" | "ldc1" | "sdc1" | "mtc1" | "mfc1" |
+            "ll" | "sc" | "sync" |
+            "teq" | "tne" | "tge" | "tgeu" | "tlt" | "tltu" | "teqi" | "tnei" | "tgei" | "tgeiu" | "tlti" | "tltiu" | "break" => true,
             _ => false,
         }
 }
@@ -404,6 +429,15 @@ fn isIdentifier(s: &str, end: usize) -> bool {
     return false;
 }
 
+// extends a word through any ".suffix" parts (returns `end` unchanged if there are none)
+fn consume_dotted_mnemonic(end: usize, s: &str) -> usize {
+    let mut index = end;
+    while s[index..].starts_with('.') && s[index + 1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+        index = consumeTilPuncAndWs(index + 1, s);
+    }
+    index
+}
+
 fn consumeTilPuncAndWs(i: usize, s: &str) -> usize {
     let mut index = i;
     for c in s.chars().skip(i) {
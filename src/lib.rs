@@ -1,19 +1,36 @@
 pub mod assembler;
+pub mod backend;
+pub mod breakpoint;
+pub mod console;
 pub mod cpu;
+pub mod disassembler;
+pub mod encoding;
+pub mod expr;
+pub mod history;
 pub mod instruction;
 pub mod lexer;
+pub mod machine;
 pub mod memory;
 pub mod parser;
 pub mod program;
+pub mod segments;
+pub mod shadow;
+pub mod watch;
 pub mod mmio;
 
 use cpu::CPU;
+use machine::{Machine, Scheduler, MAX_HARTS};
 use program::Program;
 use wasm_bindgen::prelude::*;
 use serde::{Serialize, Deserialize};
 use program::EmuError;
 use std::collections::{HashMap, HashSet};
 use mmio::DeviceState; 
+use segments::Permissions;
+use breakpoint::Breakpoint;
+use shadow::UninitCheck;
+use watch::{Comparison, WatchAccess, WatchTarget, Watchpoint};
+use instruction::Register;
 
 //https://github.com/insou22/mipsy partial code used since its a rough outline of the code 
 // only li add and sub; shows register history as lineis entered (as changed) 
@@ -23,18 +40,108 @@ pub struct Snapshot {
     pub registers: HashMap<String, u32>,
     pub memory_access_addr: Option<u32>,
     pub memory_access_size: Option<u32>,
-    pub mmio: Option<HashMap<u32, DeviceState>> 
+    pub mmio: Option<HashMap<u32, DeviceState>>,
+
+    /// coprocessor 0 registers (BadVAddr, Status, Cause and EPC)
+    pub cp0: HashMap<String, u32>,
+
+    /// raw bits of the floating-point registers $f0-$f31
+    pub fpr: HashMap<String, u32>,
+
+    /// floating-point control/status register (condition codes)
+    pub fcsr: u32,
+
+    /// index of the hart this snapshot belongs to (0 unless several harts are running)
+    pub hart: usize
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct WasmResult {
     error: String,
     snapshot: Option<Snapshot>,
+
+    /// console output produced since the last call
+    output: String,
+
+    /// the trap or `break` that stopped the program, if any
+    #[serde(default)]
+    trap: Option<TrapReport>,
+
+    /// the watchpoint that stopped the program, if any
+    #[serde(default)]
+    watchpoint: Option<WatchReport>,
+}
+
+/// which trap or `break` instruction stopped the program (lets the grader report a failed self-check)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrapReport {
+    /// "trap" for the conditional traps, "break" for `break`
+    pub kind: String,
+
+    /// the instruction that fired (e.g. "teq $t0, $zero")
+    pub instruction: String,
+    pub code: u32,
+    pub pc: u32,
+
+    /// source line of the instruction (1-based)
+    pub line: usize,
+}
+
+impl TrapReport {
+    /// builds the report for a `Trap` or `BreakInstruction` error (`None` for any other error)
+    pub fn from_error(error: &EmuError) -> Option<TrapReport> {
+        match error {
+            EmuError::Trap { insn, code, pc, line } => Some(TrapReport {
+                kind: "trap".to_string(),
+                instruction: insn.clone(),
+                code: *code,
+                pc: *pc,
+                line: *line,
+            }),
+            EmuError::BreakInstruction { code, pc, line } => Some(TrapReport {
+                kind: "break".to_string(),
+                instruction: if *code == 0 { "break".to_string() } else { format!("break {}", code) },
+                code: *code,
+                pc: *pc,
+                line: *line,
+            }),
+            _ => None
+        }
+    }
+}
+
+/// which watchpoint stopped the program and what changed (the IDE highlights the register or memory cell)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchReport {
+    pub id: usize,
+
+    /// the register name ("$s0") or the accessed address ("0x10010000")
+    pub location: String,
+    pub old: u32,
+    pub new: u32,
+}
+
+impl WatchReport {
+    /// builds the report for a `Watchpoint` error (`None` for any other error)
+    pub fn from_error(error: &EmuError, watchpoints: &[Watchpoint]) -> Option<WatchReport> {
+        let EmuError::Watchpoint { id, addr, old, new } = error else {
+            return None;
+        };
+
+        let location = match watchpoints.iter().find(|w| w.id == *id).map(|w| w.target) {
+            Some(WatchTarget::Register(register)) => register.to_string(),
+            _ => format!("0x{:08x}", addr),
+        };
+        Some(WatchReport { id: *id, location, old: *old, new: *new })
+    }
 }
 
 #[wasm_bindgen]
 pub struct WasmCPU {
     cpu: CPU,
+
+    // harts sharing one memory, set by `load_source_harts` (the single `cpu` is unused while present)
+    harts: Option<Machine>,
 }
 
 #[wasm_bindgen]
@@ -43,18 +150,21 @@ impl WasmCPU {
     pub fn new() -> Self {
         Self {
             cpu: CPU::new(),
+            harts: None,
         }
     }
 //added a reset method so it lets the frontend reset the emulator without remaking the WasmCPU
     #[wasm_bindgen]
     pub fn reset(&mut self) {
-        self.cpu.reset()
+        self.cpu.reset();
+        self.harts = None;
     }
 
     //loads t he strings in mips source code as a program(resets the cpu before loading the new program)
     #[wasm_bindgen]
     pub fn load_source(&mut self, source: &str) -> JsValue {
         self.cpu.reset();
+        self.harts = None;
 
         // parse the program then run using provided code from HTML
         match Program::parse(source, &mut self.cpu.memory) {
@@ -63,13 +173,19 @@ impl WasmCPU {
                 //added in 2nd file the old one just did "self.cpu.load_program(program);"
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: String::new(),
-                    snapshot: None
+                    snapshot: None,
+                    output: String::new(),
+                    trap: None,
+                    watchpoint: None
                 }).unwrap()
             },
             Err(e) => {
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: format!("Syntax Error -- {:?}", e),
-                    snapshot: None
+                    snapshot: None,
+                    output: String::new(),
+                    trap: None,
+                    watchpoint: None
                 }).unwrap()
             }
         }
@@ -77,37 +193,174 @@ impl WasmCPU {
 
     #[wasm_bindgen]
     pub fn set_breakpoints(&mut self, lines: Vec<usize>) {
-        self.cpu.set_breakpoints(lines);
+        self.configure(|cpu| cpu.set_breakpoints(lines.clone()));
+    }
+
+    // sets the breakpoint on a line with an optional condition (e.g. "$t0 == 10 && mem[$sp+4] > 0"), a number
+    // of hits to ignore, and a log message ("i = {$t0}") printed instead of stopping
+    // returns an empty string, or the error in the condition or message
+    #[wasm_bindgen]
+    pub fn set_breakpoint(&mut self, line: usize, condition: Option<String>, ignore_count: u32, log_message: Option<String>) -> String {
+        match Breakpoint::new(condition.as_deref(), ignore_count, log_message.as_deref()) {
+            Ok(breakpoint) => {
+                self.configure(|cpu| cpu.set_breakpoint(line, breakpoint.clone()));
+                String::new()
+            },
+            Err(e) => e,
+        }
+    }
+
+    // stops run/step after an instruction reads ("read"), writes ("write") or accesses ("both") any of the `len` bytes
+    // from `address`, with a comparison ("==", "!=", "<", "<=", ">", ">=") only once the value satisfies it
+    // returns the watchpoint id (undefined for a bad access or comparison), watchpoints are kept across program loads
+    #[wasm_bindgen]
+    pub fn watch_memory(&mut self, address: u32, len: u32, access: &str, comparison: Option<String>, value: Option<i32>) -> Option<usize> {
+        let access = WatchAccess::from_name(access)?;
+        let condition = Self::watch_condition(comparison, value)?;
+        Some(self.configure(|cpu| cpu.add_watchpoint(WatchTarget::Memory { start: address, len, access }, condition)))
+    }
+
+    // stops run/step after an instruction changes the register (e.g. "$s0"), optionally once the comparison holds
+    #[wasm_bindgen]
+    pub fn watch_register(&mut self, name: &str, comparison: Option<String>, value: Option<i32>) -> Option<usize> {
+        let register = Register::from_name(name)?;
+        let condition = Self::watch_condition(comparison, value)?;
+        Some(self.configure(|cpu| cpu.add_watchpoint(WatchTarget::Register(register), condition)))
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.configure(|cpu| cpu.remove_watchpoint(id))
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_watchpoints(&mut self) {
+        self.configure(|cpu| cpu.watchpoints.clear());
+    }
+
+    // applies a setting to the CPU and, after load_source_harts, to every hart (returns the CPU's result)
+    fn configure<T>(&mut self, set: impl Fn(&mut CPU) -> T) -> T {
+        if let Some(machine) = self.harts.as_mut() {
+            machine.harts.iter_mut().for_each(|hart| { set(hart); });
+        }
+        set(&mut self.cpu)
+    }
+
+    // parses an optional watchpoint condition (`None` if only one half is given or the comparison is unknown)
+    fn watch_condition(comparison: Option<String>, value: Option<i32>) -> Option<Option<(Comparison, i32)>> {
+        match (comparison, value) {
+            (Some(symbol), Some(value)) => Comparison::from_symbol(&symbol).map(|c| Some((c, value))),
+            (None, None) => Some(None),
+            _ => None,
+        }
+    }
+
+    // queue console input for the read syscalls (after a step/run returned "InputRequired")
+    #[wasm_bindgen]
+    pub fn push_input(&mut self, text: &str) {
+        match self.harts.as_mut() {
+            Some(machine) => machine.console.push_input(text),
+            None => self.cpu.console.push_input(text),
+        }
+    }
+
+    // exit status set by the exit/exit2 syscalls (undefined if the program has not exited)
+    #[wasm_bindgen]
+    pub fn exit_code(&self) -> Option<i32> {
+        self.current_cpu().exit_code
     }
 
     //emulate a single instruction using the MIPS CPU
     #[wasm_bindgen]
     pub fn step(&mut self) -> JsValue {
-        match self.cpu.next() {
+        let result = self.cpu.next();
+        self.step_result(result)
+    }
+
+    // steps over a call: a `jal` (or `jalr`, taken `bgezal`/`bltzal`) runs until the function returns
+    // stops early at a breakpoint or watchpoint inside the call
+    #[wasm_bindgen]
+    pub fn step_over(&mut self) -> JsValue {
+        let result = self.cpu.step_over();
+        self.step_result(result)
+    }
+
+    // runs until the current function returns to its caller (like run outside of a function)
+    #[wasm_bindgen]
+    pub fn step_out(&mut self) -> JsValue {
+        let result = self.cpu.step_out();
+        self.step_result(result)
+    }
+
+    // runs until execution reaches the line (counted from 0, like the breakpoints), e.g. the editor's cursor
+    #[wasm_bindgen]
+    pub fn run_to_line(&mut self, line: usize) -> JsValue {
+        let result = self.cpu.run_to_line(line);
+        self.step_result(result)
+    }
+
+    // number of function calls in progress
+    #[wasm_bindgen]
+    pub fn call_depth(&self) -> usize {
+        self.cpu.call_depth()
+    }
+
+    fn step_result(&mut self, result: Result<(), EmuError>) -> JsValue {
+        match result {
             Ok(_) => {
                 let snap = self.cpu.snapshot();
 
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: String::new(),
                     snapshot: Some(snap),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
                 }).unwrap()
             }
             Err(EmuError::Termination) => {
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: "Termination".to_string(),
                     snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
+                }).unwrap()
+            },
+            Err(EmuError::InputRequired) => {
+                serde_wasm_bindgen::to_value(&WasmResult {
+                    error: "InputRequired".to_string(),
+                    snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
                 }).unwrap()
             },
             Err(EmuError::Breakpoint) => {  
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: "Breakpoint".to_string(),
                     snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
+                }).unwrap()
+            }
+            Err(e @ EmuError::Watchpoint { .. }) => {
+                serde_wasm_bindgen::to_value(&WasmResult {
+                    error: "Watchpoint".to_string(),
+                    snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: WatchReport::from_error(&e, &self.cpu.watchpoints)
                 }).unwrap()
             }
             Err(e) => {
                 serde_wasm_bindgen::to_value(&WasmResult {
-                    error: format!("Runtime Error -- {:?}", e),
+                    error: format!("Runtime Error -- {}", self.cpu.error_message(&e)),
                     snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: TrapReport::from_error(&e),
+                    watchpoint: None
                 }).unwrap()
             }
         }
@@ -121,53 +374,113 @@ impl WasmCPU {
                 let snapshot = self.cpu.snapshot();
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: "Termination".to_string(),
-                    snapshot: Some(snapshot)
+                    snapshot: Some(snapshot),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
+                }).unwrap()
+            },
+            Err(EmuError::InputRequired) => {
+                serde_wasm_bindgen::to_value(&WasmResult {
+                    error: "InputRequired".to_string(),
+                    snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
                 }).unwrap()
             },
             Err(EmuError::Breakpoint) => {
                 serde_wasm_bindgen::to_value(&WasmResult {
                     error: "Breakpoint".to_string(),
                     snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: None
+                }).unwrap()
+            }
+            Err(e @ EmuError::Watchpoint { .. }) => {
+                serde_wasm_bindgen::to_value(&WasmResult {
+                    error: "Watchpoint".to_string(),
+                    snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: None,
+                    watchpoint: WatchReport::from_error(&e, &self.cpu.watchpoints)
                 }).unwrap()
             }
             Err(e) => {
                 serde_wasm_bindgen::to_value(&WasmResult {
-                    error: format!("Runtime Error -- {:?}", e),
+                    error: format!("Runtime Error -- {}", self.cpu.error_message(&e)),
                     snapshot: Some(self.cpu.snapshot()),
+                    output: self.cpu.console.take_output(),
+                    trap: TrapReport::from_error(&e),
+                    watchpoint: None
                 })
                 .unwrap()
             }
         }
     }
 
+    // keeps an undo log of the last `size` instructions for step_back (0 turns it off), kept across program loads
+    #[wasm_bindgen]
+    pub fn set_history_size(&mut self, size: usize) {
+        self.configure(|cpu| cpu.history.set_limit(size));
+    }
+
+    // number of instructions step_back can currently undo
+    #[wasm_bindgen]
+    pub fn history_length(&self) -> usize {
+        self.cpu.history.len()
+    }
+
+    // undoes the last instruction (error "NoHistory" once the recorded history is used up)
+    // console output and device state are not rolled back
+    #[wasm_bindgen]
+    pub fn step_back(&mut self) -> JsValue {
+        let error = if self.cpu.step_back() { String::new() } else { "NoHistory".to_string() };
+        self.history_result(error)
+    }
+
+    // steps back until a breakpoint whose condition holds (error "Breakpoint"), or "NoHistory" at the oldest recorded state
+    #[wasm_bindgen]
+    pub fn run_back_to_breakpoint(&mut self) -> JsValue {
+        let error = if self.cpu.run_back_to_breakpoint() { "Breakpoint" } else { "NoHistory" };
+        self.history_result(error.to_string())
+    }
+
+    fn history_result(&mut self, error: String) -> JsValue {
+        serde_wasm_bindgen::to_value(&WasmResult {
+            error,
+            snapshot: Some(self.cpu.snapshot()),
+            output: String::new(),
+            trap: None,
+            watchpoint: None
+        }).unwrap()
+    }
+
     /// returns next instruction to be emulated as a string 
     /// this is to provide some additional context in the console (although could be replaced with just $PC register)
     #[wasm_bindgen]
-    pub fn next_instruction(&self) -> String {
-        let pc = self.cpu.pc;
-        if let Some(program) = self.cpu.get_program() {
-            if let Some(index) = program.pc_to_index(pc) {
-                if index < program.core_instructions.len() {
-                    return format!("0x{:08x}: {:?}", pc, program.core_instructions[index]);
-                }
-            }
-        }
-        
+    pub fn next_instruction(&mut self) -> String {
         //to help the UI know when the next instruction is empty
-        "---".to_string()
+        self.with_current_cpu(|cpu| cpu.current_instruction()).unwrap_or_else(|| "---".to_string())
+    }
+
+    // disassemble `count` words of memory starting at an address (one line per instruction, plus label lines)
+    #[wasm_bindgen]
+    pub fn disassemble(&mut self, start_address: u32, count: usize) -> Vec<String> {
+        self.with_current_cpu(|cpu| cpu.disassemble(start_address, count))
     }
 
     // gets the current line number using $PC register (due to mapping)
     #[wasm_bindgen]
     pub fn get_current_line(&self) -> i32 {
-        let pc = self.cpu.pc;
+        let cpu = self.current_cpu();
+        let pc = cpu.pc;
 
-        if let Some(program) = self.cpu.get_program() {
-            if let Some(index) = program.pc_to_index(pc) {
-                if index < program.line_numbers.len() {
-                    return (program.line_numbers[index] as i32) - 1;
-                }
-            }
+        if let Some(program) = cpu.get_program()
+            && let Some(index) = program.pc_to_index(pc)
+            && index < program.line_numbers.len() {
+            return (program.line_numbers[index] as i32) - 1;
         }
 
         -1
@@ -176,22 +489,254 @@ impl WasmCPU {
     // get a slice of memory from the memory module using the CPU
     #[wasm_bindgen]
     pub fn get_memory(&mut self, start_address: u32, size: usize) -> Vec<u8> {
-        self.cpu.memory.get_memory_slice(start_address, size)
+        self.memory_mut().get_memory_slice(start_address, size)
     }
 
     #[wasm_bindgen]
     pub fn get_mmio_state(&self) -> JsValue {
-        serde_wasm_bindgen::to_value(&self.cpu.memory.mmio.snapshot()).unwrap()
+        serde_wasm_bindgen::to_value(&self.memory().mmio.snapshot()).unwrap()
     }
 
     #[wasm_bindgen]
     pub fn set_register(&mut self, name: &str, value: u32) {
-        self.cpu.set_reg(name, value);
+        self.with_current_cpu(|cpu| cpu.set_reg(name, value));
     }
 
     #[wasm_bindgen]
     pub fn set_memory_word(&mut self, address: u32, value: i32) {
-        self.cpu.memory.set_word(address, value);
+        self.memory_mut().set_word(address, value);
+    }
+
+    // segment map as [{name, start, last, permissions: {read, write, execute}}] for the memory view
+    #[wasm_bindgen]
+    pub fn segments(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.memory().segments.segments).unwrap()
+    }
+
+    // changes a segment's permissions with flags like "rw-" or "r-x" (false for an unknown segment or bad flags)
+    // the permissions are kept when a new program is loaded
+    #[wasm_bindgen]
+    pub fn set_segment_permissions(&mut self, name: &str, flags: &str) -> bool {
+        match Permissions::from_flags(flags) {
+            Some(permissions) => {
+                if let Some(machine) = self.harts.as_mut() {
+                    machine.memory.segments.set_permissions(name, permissions);
+                }
+                self.cpu.memory.segments.set_permissions(name, permissions)
+            },
+            None => false,
+        }
+    }
+
+    // heap base, break, limit and the blocks handed out by sbrk for the heap view
+    #[wasm_bindgen]
+    pub fn heap(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.memory().heap_view()).unwrap()
+    }
+
+    // memory of the running program (shared by the harts after load_source_harts)
+    fn memory(&self) -> &memory::Memory {
+        match self.harts.as_ref() {
+            Some(machine) => &machine.memory,
+            None => &self.cpu.memory,
+        }
+    }
+
+    fn memory_mut(&mut self) -> &mut memory::Memory {
+        match self.harts.as_mut() {
+            Some(machine) => &mut machine.memory,
+            None => &mut self.cpu.memory,
+        }
+    }
+
+    // CPU the IDE shows: the hart that executed the last instruction after load_source_harts
+    fn current_cpu(&self) -> &CPU {
+        match self.harts.as_ref() {
+            Some(machine) => &machine.harts[machine.current_hart],
+            None => &self.cpu,
+        }
+    }
+
+    // runs `f` on the CPU the IDE shows (a hart borrows the shared memory while it runs)
+    fn with_current_cpu<T>(&mut self, f: impl FnOnce(&mut CPU) -> T) -> T {
+        match self.harts.as_mut() {
+            Some(machine) => {
+                let hart = machine.current_hart;
+                machine.with_shared(hart, f)
+            },
+            None => f(&mut self.cpu),
+        }
+    }
+
+    // limits the heap to `bytes` past its base (sbrk beyond it fails with OutOfMemory), kept across program loads
+    #[wasm_bindgen]
+    pub fn set_heap_limit(&mut self, bytes: u32) {
+        if let Some(machine) = self.harts.as_mut() {
+            machine.memory.set_heap_limit(bytes);
+        }
+        self.cpu.memory.set_heap_limit(bytes);
+    }
+
+    // fault on accesses outside the segment map or against its permissions (e.g. a store to the text), kept across program loads
+    #[wasm_bindgen]
+    pub fn set_segment_checking(&mut self, enabled: bool) {
+        self.configure(|cpu| cpu.check_segments = enabled);
+    }
+
+    // fault on loads and stores to heap memory above the program break (memory sbrk has not handed out)
+    #[wasm_bindgen]
+    pub fn set_heap_checking(&mut self, enabled: bool) {
+        self.configure(|cpu| cpu.check_heap = enabled);
+    }
+
+    // reports reads of registers and memory the program never wrote: "off", "warn" or "strict" (false for an unknown mode)
+    // takes effect from the next load_source, in strict mode the read stops the program with UninitializedRead
+    #[wasm_bindgen]
+    pub fn set_uninitialized_check(&mut self, mode: &str) -> bool {
+        match UninitCheck::from_name(mode) {
+            Some(check) => {
+                self.cpu.set_uninit_check(check);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // uninitialized reads found so far in warn mode as [{location, pc, line}]
+    #[wasm_bindgen]
+    pub fn uninitialized_reads(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.cpu.uninit_warnings).unwrap()
+    }
+
+    // execute instructions fetched from text memory (for stored-program / self-modifying code demos)
+    #[wasm_bindgen]
+    pub fn set_fetch_from_memory(&mut self, enabled: bool) {
+        self.configure(|cpu| cpu.fetch_from_memory = enabled);
+    }
+
+    // emulate branch delay slots (MARS "delayed branching" setting)
+    #[wasm_bindgen]
+    pub fn set_delayed_branching(&mut self, enabled: bool) {
+        self.configure(|cpu| cpu.delayed_branching = enabled);
+    }
+
+    // loads the program on `hart_count` harts (at most 16, each gets 64KB of the stack segment) sharing one memory
+    // every hart starts with the CPU's settings (breakpoints, watchpoints, checks, ...) and the setters apply to all of them
+    // with a seed the scheduler interleaves instructions pseudo-randomly, otherwise round-robin one instruction at a time
+    #[wasm_bindgen]
+    pub fn load_source_harts(&mut self, source: &str, hart_count: usize, seed: Option<u64>) -> JsValue {
+        let scheduler = match seed {
+            Some(seed) => Scheduler::Random { seed },
+            None => Scheduler::RoundRobin { quantum: 1 },
+        };
+        let mut machine = Machine::new(hart_count, scheduler);
+        machine.configure(&self.cpu);
+
+        let result = if hart_count > MAX_HARTS {
+            Err(format!("Error -- at most {} harts fit in the stack segment", MAX_HARTS))
+        } else {
+            machine.load_source(source).map_err(|e| format!("Syntax Error -- {:?}", e))
+        };
+
+        // reset the single CPU like load_source, but keep its breakpoints in step with the harts' (the setters edit both)
+        let breakpoints = std::mem::take(&mut self.cpu.breakpoints);
+        self.cpu.reset();
+        self.cpu.breakpoints = breakpoints;
+        self.harts = result.is_ok().then_some(machine);
+        let error = result.err().unwrap_or_default();
+
+        serde_wasm_bindgen::to_value(&WasmResult {
+            error,
+            snapshot: None,
+            output: String::new(),
+            trap: None,
+            watchpoint: None
+        }).unwrap()
+    }
+
+    #[wasm_bindgen]
+    pub fn hart_count(&self) -> usize {
+        self.harts.as_ref().map_or(1, |m| m.harts.len())
+    }
+
+    // hart that executed the last instruction (also reported as `snapshot.hart`)
+    #[wasm_bindgen]
+    pub fn current_hart(&self) -> usize {
+        self.harts.as_ref().map_or(0, |m| m.current_hart)
+    }
+
+    // executes one instruction on a specific hart
+    #[wasm_bindgen]
+    pub fn step_hart(&mut self, hart: usize) -> JsValue {
+        let Some(machine) = self.harts.as_mut() else { return self.step() };
+        if hart >= machine.harts.len() {
+            return Self::hart_result(machine, hart, Err(EmuError::ParsingError(format!("no hart {}", hart))));
+        }
+
+        let result = machine.step_hart(hart);
+        Self::hart_result(machine, hart, result)
+    }
+
+    // executes one instruction on the hart chosen by the scheduler
+    #[wasm_bindgen]
+    pub fn step_harts(&mut self) -> JsValue {
+        let Some(machine) = self.harts.as_mut() else { return self.step() };
+
+        let result = machine.step();
+        let hart = machine.current_hart;
+        Self::hart_result(machine, hart, result.map(|_| ()))
+    }
+
+    // runs every hart until they finish, one faults or reaches a breakpoint
+    #[wasm_bindgen]
+    pub fn run_harts(&mut self) -> JsValue {
+        let Some(machine) = self.harts.as_mut() else { return self.run() };
+
+        let result = machine.run().and(Err(EmuError::Termination));
+        let hart = machine.current_hart;
+        Self::hart_result(machine, hart, result)
+    }
+
+    #[wasm_bindgen]
+    pub fn hart_snapshot(&mut self, hart: usize) -> JsValue {
+        match self.harts.as_mut() {
+            Some(machine) if hart < machine.harts.len() => serde_wasm_bindgen::to_value(&machine.snapshot(hart)).unwrap(),
+            Some(_) => JsValue::NULL,
+            None => serde_wasm_bindgen::to_value(&self.cpu.snapshot()).unwrap(),
+        }
+    }
+
+    fn hart_result(machine: &mut Machine, hart: usize, result: Result<(), EmuError>) -> JsValue {
+        serde_wasm_bindgen::to_value(&Self::hart_report(machine, hart, result)).unwrap()
+    }
+
+    // the result of a hart's step or run, reported like `step_result` does for the single CPU
+    fn hart_report(machine: &mut Machine, hart: usize, result: Result<(), EmuError>) -> WasmResult {
+        let trap = result.as_ref().err().and_then(TrapReport::from_error);
+        let mut watchpoint = None;
+        let error = match result {
+            Ok(()) => String::new(),
+            Err(EmuError::Termination) => "Termination".to_string(),
+            Err(EmuError::InputRequired) => "InputRequired".to_string(),
+            Err(EmuError::Breakpoint) => "Breakpoint".to_string(),
+            Err(e @ EmuError::Watchpoint { .. }) => {
+                watchpoint = WatchReport::from_error(&e, &machine.harts[hart].watchpoints);
+                "Watchpoint".to_string()
+            },
+            Err(e) => match machine.harts.get(hart) {
+                Some(cpu) => format!("Runtime Error -- {}", cpu.error_message(&e)),
+                None => format!("Runtime Error -- {:?}", e),
+            },
+        };
+        let snapshot = (hart < machine.harts.len()).then(|| machine.snapshot(hart));
+
+        WasmResult {
+            error,
+            snapshot,
+            output: machine.console.take_output(),
+            trap,
+            watchpoint
+        }
     }
 
     #[wasm_bindgen]
@@ -811,4 +1356,3256 @@ mod tests {
     }
 
 
+    #[cfg(test)]
+    mod tests_syscall {
+        use super::CPU;
+        use crate::console::BufferedConsole;
+        use crate::program::EmuError;
+
+        #[test]
+        fn print_int_string_char_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+                msg: .asciiz "sum = "
+                .text
+                la $a0, msg
+                li $v0, 4
+                syscall
+                li $a0, -42
+                li $v0, 1
+                syscall
+                li $a0, 10
+                li $v0, 11
+                syscall
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.console.take_output(), "sum = -42\n");
+        }
+
+        #[test]
+        fn read_int_test() {
+            let mut cpu = CPU::new();
+            cpu.console = Box::new(BufferedConsole::with_input("17\n25\n"));
+            let program = r#"
+                li $v0, 5
+                syscall
+                move $t0, $v0
+                li $v0, 5
+                syscall
+                add $t1, $t0, $v0
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 42);
+        }
+
+        #[test]
+        fn read_string_test() {
+            let mut cpu = CPU::new();
+            cpu.console = Box::new(BufferedConsole::with_input("hello world\n"));
+            let program = r#"
+                .data
+                buf: .space 8
+                .text
+                la $a0, buf
+                li $a1, 8
+                li $v0, 8
+                syscall
+                li $v0, 4
+                syscall
+            "#;
+
+            cpu.run_input(program).unwrap();
+            // at most $a1 - 1 characters are read, followed by a null terminator
+            assert_eq!(cpu.console.take_output(), "hello w");
+        }
+
+        #[test]
+        fn read_char_test() {
+            let mut cpu = CPU::new();
+            cpu.console = Box::new(BufferedConsole::with_input("xy"));
+            let program = r#"
+                li $v0, 12
+                syscall
+                move $t0, $v0
+                li $v0, 12
+                syscall
+                move $t1, $v0
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 'x' as u32);
+            assert_eq!(cpu.get_reg("$t1"), 'y' as u32);
+        }
+
+        #[test]
+        fn read_without_input_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $v0, 5
+                syscall
+                move $t0, $v0
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(EmuError::InputRequired)));
+
+            // the syscall is retried once input is provided
+            cpu.console.push_input("9\n");
+            cpu.run().unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 9);
+        }
+
+        #[test]
+        fn exit_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 1
+                li $v0, 10
+                syscall
+                li $t0, 2
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 1);
+            assert_eq!(cpu.exit_code, Some(0));
+        }
+
+        #[test]
+        fn exit2_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $a0, 3
+                li $v0, 17
+                syscall
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.exit_code, Some(3));
+            assert!(matches!(cpu.next(), Err(EmuError::Termination)));
+        }
+
+        #[test]
+        fn sbrk_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $a0, 10
+                li $v0, 9
+                syscall
+                move $t0, $v0
+                li $a0, 4
+                li $v0, 9
+                syscall
+                move $t1, $v0
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), crate::memory::DEFAULT_HEAP_BASE_ADDRESS);
+            assert_eq!(cpu.get_reg("$t1"), crate::memory::DEFAULT_HEAP_BASE_ADDRESS + 12);
+        }
+
+        #[test]
+        fn invalid_syscall_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $v0, 99
+                syscall
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(EmuError::InvalidSyscall(99))));
+        }
+    }
+    #[cfg(test)]
+    mod tests_encoding {
+        use super::CPU;
+        use crate::encoding::{encode, decode};
+        use crate::instruction::{CoreInstruction, Register};
+        use crate::memory::DEFAULT_TEXT_BASE_ADDRESS;
+        use std::collections::HashMap;
+
+        fn reg(name: &str) -> Register {
+            Register::from_name(name).unwrap()
+        }
+
+        #[test]
+        fn encode_green_sheet_test() {
+            let symbols = HashMap::new();
+            let add = CoreInstruction::Add { rd: reg("$t0"), rs: reg("$s1"), rt: reg("$s2") };
+            let addi = CoreInstruction::Addi { rt: reg("$t0"), rs: reg("$zero"), imm: -1 };
+            let lw = CoreInstruction::Lw { rt: reg("$t0"), rs: reg("$sp"), imm: 4 };
+            let sll = CoreInstruction::Sll { rd: reg("$t1"), rt: reg("$t0"), sa: 2 };
+
+            assert_eq!(encode(&add, DEFAULT_TEXT_BASE_ADDRESS, &symbols).unwrap(), 0x0232_4020);
+            assert_eq!(encode(&addi, DEFAULT_TEXT_BASE_ADDRESS, &symbols).unwrap(), 0x2008_FFFF);
+            assert_eq!(encode(&lw, DEFAULT_TEXT_BASE_ADDRESS, &symbols).unwrap(), 0x8FA8_0004);
+            assert_eq!(encode(&sll, DEFAULT_TEXT_BASE_ADDRESS, &symbols).unwrap(), 0x0008_4880);
+            assert_eq!(encode(&CoreInstruction::Syscall, DEFAULT_TEXT_BASE_ADDRESS, &symbols).unwrap(), 0x0000_000C);
+        }
+
+        #[test]
+        fn encode_out_of_range_immediate_test() {
+            let symbols = HashMap::new();
+            let addi = CoreInstruction::Addi { rt: reg("$t0"), rs: reg("$zero"), imm: 40000 };
+
+            assert!(encode(&addi, DEFAULT_TEXT_BASE_ADDRESS, &symbols).is_err());
+        }
+
+        #[test]
+        fn encode_branch_and_jump_test() {
+            let mut symbols = HashMap::new();
+            symbols.insert("loop".to_string(), DEFAULT_TEXT_BASE_ADDRESS);
+            let beq = CoreInstruction::Beq { rs: reg("$t0"), rt: reg("$zero"), label: "loop".to_string() };
+            let j = CoreInstruction::J { label: "loop".to_string() };
+
+            // branch back over itself: offset -2 words from PC+4
+            assert_eq!(encode(&beq, DEFAULT_TEXT_BASE_ADDRESS + 4, &symbols).unwrap(), 0x1100_FFFE);
+            assert_eq!(encode(&j, DEFAULT_TEXT_BASE_ADDRESS + 8, &symbols).unwrap(), 0x0810_0000);
+        }
+
+        #[test]
+        fn program_in_text_memory_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                main:
+                addi $t0, $zero, 5
+                loop:
+                addi $t0, $t0, -1
+                bne $t0, $zero, loop
+                jal main
+            "#;
+
+            let parsed = crate::program::Program::parse(program, &mut cpu.memory).unwrap();
+
+            for (i, insn) in parsed.core_instructions.iter().enumerate() {
+                let pc = parsed.index_to_pc(i);
+                let word = cpu.memory.load_word(pc) as u32;
+                let decoded = decode(word, pc, &parsed.symbol_table).unwrap();
+
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", insn));
+            }
+
+            assert_eq!(cpu.memory.load_word(DEFAULT_TEXT_BASE_ADDRESS) as u32, 0x2008_0005);
+        }
+
+        #[test]
+        fn out_of_range_immediates_are_expanded_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            value: .word 77
+                .text
+                addi $t0, $zero, 40000
+                ori $t1, $zero, 0x12345
+                andi $t2, $t1, 0x10305
+                la $t5, value
+                addi $t5, $t5, 40000
+                lw $t4, -40000($t5)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 40000);
+            assert_eq!(cpu.get_reg("$t1"), 0x12345);
+            assert_eq!(cpu.get_reg("$t2"), 0x10305);
+            assert_eq!(cpu.get_reg("$t4"), 77);
+
+            // every expanded instruction has a machine word
+            let program = cpu.get_program().unwrap().clone();
+            for (i, insn) in program.core_instructions.iter().enumerate() {
+                let pc = program.index_to_pc(i);
+                let decoded = decode(cpu.memory.load_word(pc) as u32, pc, &program.symbol_table).unwrap();
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", insn));
+            }
+        }
+    }
+    #[cfg(test)]
+    mod tests_disassembler {
+        use super::CPU;
+        use crate::program::Program;
+        use crate::memory::DEFAULT_TEXT_BASE_ADDRESS;
+
+        #[test]
+        fn disassemble_program_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                main:
+                addi $t0, $zero, 5
+                loop:
+                addi $t0, $t0, -1
+                lw $t1, 4($sp)
+                bne $t0, $zero, loop
+                jr $ra
+            "#;
+
+            let parsed = Program::parse(program, &mut cpu.memory).unwrap();
+            cpu.load_program(parsed);
+
+            let lines = cpu.disassemble(DEFAULT_TEXT_BASE_ADDRESS, 5);
+            assert_eq!(lines, vec![
+                "main:",
+                "0x00400000: addi $t0, $zero, 5",
+                "loop:",
+                "0x00400004: addi $t0, $t0, -1",
+                "0x00400008: lw $t1, 4($sp)",
+                "0x0040000c: bne $t0, $zero, loop",
+                "0x00400010: jr $ra",
+            ]);
+        }
+
+        #[test]
+        fn current_instruction_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 70000
+                syscall
+            "#;
+
+            let parsed = Program::parse(program, &mut cpu.memory).unwrap();
+            cpu.load_program(parsed);
+
+            assert_eq!(cpu.current_instruction().unwrap(), "0x00400000: lui $at, 0x1");
+            cpu.next().unwrap();
+            assert_eq!(cpu.current_instruction().unwrap(), "0x00400004: ori $t0, $at, 0x1170");
+
+            cpu.pc = DEFAULT_TEXT_BASE_ADDRESS + 12;
+            assert!(cpu.current_instruction().is_none());
+        }
+
+        #[test]
+        fn disassemble_data_word_test() {
+            let mut cpu = CPU::new();
+            cpu.memory.set_word(0x1000_0000, -1);
+
+            assert_eq!(cpu.disassemble(0x1000_0000, 1), vec!["0x10000000: .word 0xffffffff"]);
+        }
+    }
+    #[cfg(test)]
+    mod tests_fetch_from_memory {
+        use super::CPU;
+
+        // overwrites the instruction at 0x00400014 with `addi $t0, $zero, 42` before it runs
+        const SELF_MODIFYING: &str = r#"
+            li $t1, 0x2008002A
+            li $t2, 0x00400014
+            sw $t1, 0($t2)
+            addi $t0, $zero, 1
+        "#;
+
+        #[test]
+        fn self_modifying_code_test() {
+            let mut cpu = CPU::new();
+            cpu.fetch_from_memory = true;
+
+            cpu.run_input(SELF_MODIFYING).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 42);
+        }
+
+        #[test]
+        fn instruction_list_ignores_stores_test() {
+            let mut cpu = CPU::new();
+
+            cpu.run_input(SELF_MODIFYING).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 1);
+        }
+
+        #[test]
+        fn branches_and_calls_from_memory_test() {
+            let mut cpu = CPU::new();
+            cpu.fetch_from_memory = true;
+            let program = r#"
+                li $t0, 0
+                li $t1, 5
+            loop:
+                jal inc
+                bne $t0, $t1, loop
+                j done
+            inc:
+                addi $t0, $t0, 1
+                jr $ra
+            done:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 5);
+        }
+
+        #[test]
+        fn code_stored_outside_the_program_runs_test() {
+            // stores `addi $t0, $zero, 7` and `jr $ra` past the end of the program and calls them
+            let program = r#"
+                li $t2, 0x00400100
+                li $t1, 0x20080007
+                sw $t1, 0($t2)
+                li $t1, 0x03E00008
+                sw $t1, 4($t2)
+                jalr $t2
+                addi $t0, $t0, 1
+            "#;
+
+            let mut cpu = CPU::new();
+            cpu.fetch_from_memory = true;
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 8);
+
+            // the assembled instruction list has nothing at that address
+            let mut cpu = CPU::new();
+            assert!(matches!(cpu.run_input(program), Err(crate::program::EmuError::InvalidJump(0x0040_0100))));
+        }
+
+        #[test]
+        fn invalid_instruction_word_test() {
+            let mut cpu = CPU::new();
+            cpu.fetch_from_memory = true;
+            let program = r#"
+                li $t1, -1
+                li $t2, 0x00400014
+                sw $t1, 0($t2)
+                addi $t0, $zero, 1
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(crate::program::EmuError::InvalidInstruction(0xFFFF_FFFF))));
+        }
+    }
+    #[cfg(test)]
+    mod tests_delayed_branching {
+        use super::CPU;
+
+        const DELAY_SLOT: &str = r#"
+                li $t0, 0
+                j skip
+                addi $t0, $t0, 1
+                addi $t0, $t0, 10
+            skip:
+                addi $t0, $t0, 100
+        "#;
+
+        #[test]
+        fn delay_slot_executes_test() {
+            let mut cpu = CPU::new();
+            cpu.delayed_branching = true;
+
+            cpu.run_input(DELAY_SLOT).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 101);
+        }
+
+        #[test]
+        fn no_delay_slot_by_default_test() {
+            let mut cpu = CPU::new();
+
+            cpu.run_input(DELAY_SLOT).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 100);
+        }
+
+        #[test]
+        fn jal_links_past_delay_slot_test() {
+            let mut cpu = CPU::new();
+            cpu.delayed_branching = true;
+            let program = r#"
+                jal func
+                li $t1, 7
+                addi $t2, $t1, 1
+                j end
+                nop
+            func:
+                jr $ra
+                nop
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            // $ra skips the delay slot (li expands to two instructions, so only its first half is in the slot)
+            assert_eq!(cpu.get_reg("$ra"), crate::memory::DEFAULT_TEXT_BASE_ADDRESS + 8);
+            assert_eq!(cpu.get_reg("$t2"), 8);
+        }
+
+        #[test]
+        fn loop_with_delay_slot_test() {
+            let mut cpu = CPU::new();
+            cpu.delayed_branching = true;
+            let program = r#"
+                li $t0, 3
+                li $t1, 0
+            loop:
+                addi $t0, $t0, -1
+                bne $t0, $zero, loop
+                addi $t1, $t1, 1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            // the increment in the delay slot runs on every iteration, including the last
+            assert_eq!(cpu.get_reg("$t0"), 0);
+            assert_eq!(cpu.get_reg("$t1"), 3);
+        }
+
+        #[test]
+        fn branch_as_last_instruction_test() {
+            let mut cpu = CPU::new();
+            cpu.delayed_branching = true;
+            let program = r#"
+                li $t0, 2
+            loop:
+                addi $t0, $t0, -1
+                bne $t0, $zero, loop
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 0);
+        }
+    }
+
+    mod tests_exceptions {
+        use super::CPU;
+        use crate::cpu::{CP0_BADVADDR, CP0_CAUSE, CP0_EPC, CP0_STATUS, EXC_TRAP};
+        use crate::encoding::{decode, encode};
+        use crate::instruction::{CoreInstruction, Register};
+        use crate::memory::{DEFAULT_TEXT_BASE_ADDRESS, EXCEPTION_HANDLER_ADDRESS};
+        use crate::program::EmuError;
+        use std::collections::HashMap;
+
+        fn reg(name: &str) -> Register {
+            Register::from_name(name).unwrap()
+        }
+
+        // handler that records Cause/BadVAddr and resumes after the faulting instruction
+        const HANDLER: &str = r#"
+            .ktext 0x80000180
+                mfc0 $k0, $13
+                srl $s0, $k0, 2
+                andi $s0, $s0, 0x1f
+                mfc0 $s1, $8
+                mfc0 $k0, $14
+                addi $k0, $k0, 4
+                mtc0 $k0, $14
+                eret
+        "#;
+
+        #[test]
+        fn unaligned_load_vectors_to_handler_test() {
+            let mut cpu = CPU::new();
+            let program = format!(r#"
+                .text
+                li $t0, 1
+                lw $t1, 2($t0)
+                li $t2, 5
+                {}"#, HANDLER);
+
+            cpu.run_input(&program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 5);
+            assert_eq!(cpu.get_reg("$s0"), 4);
+            assert_eq!(cpu.get_reg("$s1"), 3);
+            assert_eq!(cpu.get_cp0(CP0_EPC), DEFAULT_TEXT_BASE_ADDRESS + 12);
+
+            // eret leaves exception level
+            assert_eq!(cpu.get_cp0(CP0_STATUS) & 0x2, 0);
+        }
+
+        #[test]
+        fn unaligned_store_cause_test() {
+            let mut cpu = CPU::new();
+            let program = format!(r#"
+                li $t0, 0x10010001
+                sw $t0, 0($t0)
+                {}"#, HANDLER);
+
+            cpu.run_input(&program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 5);
+            assert_eq!(cpu.get_reg("$s1"), 0x1001_0001);
+        }
+
+        #[test]
+        fn divide_by_zero_vectors_to_handler_test() {
+            let mut cpu = CPU::new();
+            let program = format!(r#"
+                li $t0, 7
+                div $t0, $zero
+                li $t2, 1
+                {}"#, HANDLER);
+
+            cpu.run_input(&program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), EXC_TRAP);
+            assert_eq!(cpu.get_reg("$t2"), 1);
+            assert_eq!(cpu.get_cp0(CP0_CAUSE) >> 2 & 0x1F, EXC_TRAP);
+        }
+
+        #[test]
+        fn fault_without_handler_is_error_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 1
+                lw $t1, 2($t0)
+                li $t2, 5
+            "#;
+
+            let result = cpu.run_input(program);
+            assert!(matches!(result, Err(EmuError::UnalignedAccess(3))));
+            assert_eq!(cpu.get_reg("$t2"), 0);
+            assert_eq!(cpu.get_cp0(CP0_BADVADDR), 0);
+        }
+
+        #[test]
+        fn fault_inside_handler_is_error_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                div $t0, $zero
+            .ktext
+                lw $k0, 1($zero)
+                eret
+            "#;
+
+            let result = cpu.run_input(program);
+            assert!(matches!(result, Err(EmuError::UnalignedAccess(1))));
+            assert_eq!(cpu.pc, EXCEPTION_HANDLER_ADDRESS);
+        }
+
+        #[test]
+        fn kernel_labels_and_text_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .text
+            main:
+                li $t0, 1
+            .ktext
+            handler:
+                eret
+            .text
+            done:
+                li $t1, 2
+            "#;
+
+            cpu.run_input(program).unwrap();
+            let symbols = &cpu.get_program().unwrap().symbol_table;
+            assert_eq!(symbols["main"], DEFAULT_TEXT_BASE_ADDRESS);
+            assert_eq!(symbols["done"], DEFAULT_TEXT_BASE_ADDRESS + 8);
+            assert_eq!(symbols["handler"], EXCEPTION_HANDLER_ADDRESS);
+
+            // user code runs straight through without entering the kernel segment
+            assert_eq!(cpu.get_reg("$t1"), 2);
+            assert_eq!(cpu.memory.load_word(EXCEPTION_HANDLER_ADDRESS) as u32, 0x4200_0018);
+        }
+
+        #[test]
+        fn cop0_encoding_round_trip_test() {
+            let symbols = HashMap::new();
+            let insns = [
+                CoreInstruction::Mfc0 { rt: reg("$k0"), rd: 13 },
+                CoreInstruction::Mtc0 { rt: reg("$t1"), rd: 14 },
+                CoreInstruction::Eret,
+            ];
+
+            assert_eq!(encode(&insns[0], 0, &symbols).unwrap(), 0x401A_6800);
+            for insn in &insns {
+                let word = encode(insn, 0, &symbols).unwrap();
+                assert_eq!(decode(word, 0, &symbols).unwrap().to_string(), insn.to_string());
+            }
+        }
+    }
+
+    mod tests_interrupts {
+        use super::CPU;
+        use crate::cpu::CP0_CAUSE;
+        use crate::mmio::{IoDevice, MmioBus, TimerDevice, TIMER_INTERRUPT_LINE};
+
+        // arms the timer to fire after 20 instructions, then waits for the handler to set $s0
+        const WAIT_FOR_TIMER: &str = r#"
+                li $t1, 0xFFFF0014
+                li $t2, 20
+                sw $t2, 0($t1)
+                li $t3, {status}
+                mtc0 $t3, $12
+            loop:
+                addi $t0, $t0, 1
+                slti $t4, $t0, 100
+                beq $t4, $zero, done
+                beq $s0, $zero, loop
+            done:
+        "#;
+
+        const TIMER_HANDLER: &str = r#"
+            .ktext
+                mfc0 $k0, $13
+                srl $s1, $k0, 2
+                andi $s1, $s1, 0x1f
+                addi $s0, $zero, 1
+                lui $k1, 0xFFFF
+                sw $zero, 20($k1)
+                eret
+        "#;
+
+        #[test]
+        fn timer_interrupt_vectors_to_handler_test() {
+            let mut cpu = CPU::new();
+            let program = WAIT_FOR_TIMER.replace("{status}", "0x8001") + TIMER_HANDLER;
+
+            cpu.run_input(&program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 1);
+            assert_eq!(cpu.get_reg("$s1"), 0);
+            assert!(cpu.get_reg("$t0") < 100);
+
+            // writing compare acknowledged the interrupt
+            assert_eq!(cpu.get_cp0(CP0_CAUSE) & (1 << 15), 0);
+        }
+
+        #[test]
+        fn masked_interrupt_stays_pending_test() {
+            let mut cpu = CPU::new();
+
+            // interrupts enabled but the timer line (IM7) is masked
+            let program = WAIT_FOR_TIMER.replace("{status}", "0x0001") + TIMER_HANDLER;
+
+            cpu.run_input(&program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 0);
+            assert_eq!(cpu.get_reg("$t0"), 100);
+            assert_ne!(cpu.get_cp0(CP0_CAUSE) & (1 << 15), 0);
+        }
+
+        #[test]
+        fn interrupt_without_handler_is_ignored_test() {
+            let mut cpu = CPU::new();
+            let program = WAIT_FOR_TIMER.replace("{status}", "0x8001");
+
+            cpu.run_input(&program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 100);
+        }
+
+        #[test]
+        fn timer_device_test() {
+            let mut bus = MmioBus::new();
+            bus.register(0xFFFF_0010, 8, Box::new(TimerDevice::new()));
+            bus.store(0xFFFF_0014, 3);
+
+            bus.tick();
+            bus.tick();
+            assert_eq!(bus.pending_interrupts(), 0);
+
+            bus.tick();
+            assert_eq!(bus.load(0xFFFF_0010), 3);
+            assert_eq!(bus.pending_interrupts(), 1 << TIMER_INTERRUPT_LINE);
+
+            // writing compare clears the interrupt
+            bus.store(0xFFFF_0014, 0);
+            assert_eq!(bus.pending_interrupts(), 0);
+
+            let mut timer = TimerDevice::new();
+            timer.tick();
+            assert_eq!(timer.interrupt(), None);
+        }
+    }
+
+    mod tests_overflow {
+        use super::CPU;
+        use crate::memory::DEFAULT_TEXT_BASE_ADDRESS;
+        use crate::program::EmuError;
+
+        #[test]
+        fn add_overflow_traps_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x7FFFFFFF
+                li $t1, 1
+                add $t2, $t0, $t1
+            "#;
+
+            let result = cpu.run_input(program);
+            match result {
+                Err(EmuError::ArithmeticOverflow { pc, line }) => {
+                    assert_eq!(pc, DEFAULT_TEXT_BASE_ADDRESS + 16);
+                    assert_eq!(line, 4);
+                },
+                other => panic!("expected overflow, got {:?}", other),
+            }
+
+            // the destination register is not written
+            assert_eq!(cpu.get_reg("$t2"), 0);
+        }
+
+        #[test]
+        fn addu_wraps_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x7FFFFFFF
+                li $t1, 1
+                addu $t2, $t0, $t1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 0x8000_0000);
+        }
+
+        #[test]
+        fn addi_overflow_traps_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x7FFFFFFF
+                addi $t1, $t0, 1
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(EmuError::ArithmeticOverflow { .. })));
+            assert_eq!(cpu.get_reg("$t1"), 0);
+        }
+
+        #[test]
+        fn addiu_wraps_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x7FFFFFFF
+                addiu $t1, $t0, 1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 0x8000_0000);
+        }
+
+        #[test]
+        fn sub_overflow_traps_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x80000000
+                li $t1, 1
+                sub $t2, $t0, $t1
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(EmuError::ArithmeticOverflow { .. })));
+        }
+
+        #[test]
+        fn subu_wraps_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x80000000
+                li $t1, 1
+                subu $t2, $t0, $t1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 0x7FFF_FFFF);
+        }
+
+        #[test]
+        fn signed_add_without_overflow_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, -5
+                li $t1, 3
+                add $t2, $t0, $t1
+                sub $t3, $t0, $t1
+                addi $t4, $t0, -10
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2") as i32, -2);
+            assert_eq!(cpu.get_reg("$t3") as i32, -8);
+            assert_eq!(cpu.get_reg("$t4") as i32, -15);
+        }
+
+        #[test]
+        fn overflow_vectors_to_handler_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x7FFFFFFF
+                add $t1, $t0, $t0
+                li $t2, 1
+            .ktext
+                mfc0 $k0, $13
+                srl $s0, $k0, 2
+                andi $s0, $s0, 0x1f
+                mfc0 $k0, $14
+                addiu $k0, $k0, 4
+                mtc0 $k0, $14
+                eret
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 12);
+            assert_eq!(cpu.get_reg("$t1"), 0);
+            assert_eq!(cpu.get_reg("$t2"), 1);
+        }
+    }
+
+    mod tests_branches {
+        use super::CPU;
+        use crate::memory::DEFAULT_TEXT_BASE_ADDRESS;
+        use crate::program::Program;
+
+        /// runs `branch $t0, taken` for each value and returns which ones branched
+        fn taken_for(branch: &str, values: &[i32]) -> Vec<bool> {
+            values.iter().map(|v| {
+                let mut cpu = CPU::new();
+                let program = format!(r#"
+                    li $t0, {}
+                    {} $t0, taken
+                    li $t1, 0
+                    j end
+                taken:
+                    li $t1, 1
+                end:
+                "#, v, branch);
+
+                cpu.run_input(&program).unwrap();
+                cpu.get_reg("$t1") == 1
+            }).collect()
+        }
+
+        #[test]
+        fn zero_compare_branches_test() {
+            let values = [-3, 0, 4];
+
+            assert_eq!(taken_for("bgez", &values), vec![false, true, true]);
+            assert_eq!(taken_for("bgtz", &values), vec![false, false, true]);
+            assert_eq!(taken_for("blez", &values), vec![true, true, false]);
+            assert_eq!(taken_for("bltz", &values), vec![true, false, false]);
+            assert_eq!(taken_for("beqz", &values), vec![false, true, false]);
+            assert_eq!(taken_for("bnez", &values), vec![true, false, true]);
+        }
+
+        #[test]
+        fn branch_and_link_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, -1
+                bgezal $t0, func
+                bltzal $t0, func
+                j end
+            func:
+                addi $t1, $t1, 1
+                jr $ra
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            // only bltzal branches, but both link
+            assert_eq!(cpu.get_reg("$t1"), 1);
+            assert_eq!(cpu.get_reg("$ra"), DEFAULT_TEXT_BASE_ADDRESS + 16);
+        }
+
+        #[test]
+        fn b_and_bal_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                bal func
+                b end
+                li $t0, 99
+            func:
+                li $t1, 7
+                jr $ra
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 0);
+            assert_eq!(cpu.get_reg("$t1"), 7);
+        }
+
+        #[test]
+        fn unsigned_branches_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, -1
+                li $t1, 1
+                bltu $t0, $t1, wrong
+                bgtu $t0, $t1, next1
+                j wrong
+            next1:
+                bleu $t1, $t0, next2
+                j wrong
+            next2:
+                bgeu $t0, $t0, next3
+                j wrong
+            next3:
+                blt $t0, $t1, ok
+            wrong:
+                li $s0, 1
+                j end
+            ok:
+                li $s1, 1
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 0);
+            assert_eq!(cpu.get_reg("$s1"), 1);
+        }
+
+        #[test]
+        fn immediate_branches_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0
+            loop:
+                addi $t0, $t0, 1
+                blt $t0, 5, loop
+                beq $t0, 5, five
+                j end
+            five:
+                li $t1, 1
+                bgeu $t0, 0x80000000, end
+                bne $t0, -1, done
+                j end
+            done:
+                li $t2, 1
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 5);
+            assert_eq!(cpu.get_reg("$t1"), 1);
+            assert_eq!(cpu.get_reg("$t2"), 1);
+        }
+
+        #[test]
+        fn jalr_function_pointer_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                la $t0, double
+                li $a0, 21
+                jalr $t0
+                move $s0, $v0
+                la $t1, square
+                li $a0, 5
+                jalr $s1, $t1
+                j end
+            double:
+                add $v0, $a0, $a0
+                jr $ra
+            square:
+                mult $a0, $a0
+                mflo $v0
+                jr $s1
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 42);
+            assert_eq!(cpu.get_reg("$v0"), 25);
+        }
+
+        #[test]
+        fn jump_table_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            table: .word 0, 0
+                .text
+                la $t0, table
+                la $t1, case0
+                sw $t1, 0($t0)
+                la $t1, case1
+                sw $t1, 4($t0)
+                li $t2, 1
+                sll $t2, $t2, 2
+                add $t2, $t2, $t0
+                lw $t3, 0($t2)
+                jr $t3
+            case0:
+                li $v0, 10
+                j end
+            case1:
+                li $v0, 11
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$v0"), 11);
+        }
+
+        #[test]
+        fn branch_encoding_round_trip_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+            top:
+                bgez $t0, top
+                bltzal $t1, top
+                blez $t2, top
+                jalr $t9
+            "#;
+
+            let program = Program::parse(program, &mut cpu.memory).unwrap();
+            cpu.load_program(program);
+            let lines = cpu.disassemble(DEFAULT_TEXT_BASE_ADDRESS, 4);
+            assert_eq!(lines[1], "0x00400000: bgez $t0, top");
+            assert_eq!(lines[2], "0x00400004: bltzal $t1, top");
+            assert_eq!(lines[3], "0x00400008: blez $t2, top");
+            assert_eq!(lines[4], "0x0040000c: jalr $ra, $t9");
+        }
+
+        #[test]
+        fn mnemonic_named_labels_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            b: .word 7
+                .text
+                la $t0, b
+                lw $t1, 0($t0)
+                j not
+                li $t1, 0
+            not:
+                addi $t1, $t1, 1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 8);
+        }
+    }
+
+    mod tests_unsigned_loads_and_shifts {
+        use super::CPU;
+
+        #[test]
+        fn lbu_zero_extends_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            bytes: .byte 0xE9, 0x41
+                .text
+                li $t0, 0x10000000
+                lb $t1, 0($t0)
+                lbu $t2, 0($t0)
+                lbu $t3, 1($t0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 0xFFFF_FFE9);
+            assert_eq!(cpu.get_reg("$t2"), 0xE9);
+            assert_eq!(cpu.get_reg("$t3"), 0x41);
+        }
+
+        #[test]
+        fn lhu_zero_extends_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            half: .half 0x8001
+                .text
+                li $t0, 0x10000000
+                lh $t1, 0($t0)
+                lhu $t2, 0($t0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 0xFFFF_8001);
+            assert_eq!(cpu.get_reg("$t2"), 0x8001);
+        }
+
+        #[test]
+        fn variable_shifts_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x80000010
+                li $t1, 4
+                sllv $t2, $t0, $t1
+                srlv $t3, $t0, $t1
+                srav $t4, $t0, $t1
+                li $t1, 36
+                sllv $t5, $t0, $t1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 0x0000_0100);
+            assert_eq!(cpu.get_reg("$t3"), 0x0800_0001);
+            assert_eq!(cpu.get_reg("$t4"), 0xF800_0001);
+
+            // only the low 5 bits of the shift register are used
+            assert_eq!(cpu.get_reg("$t5"), 0x0000_0100);
+        }
+
+        #[test]
+        fn unaligned_load_idiom_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            bytes: .byte 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88
+                .text
+                li $a0, 0x10000001
+                lwr $t0, 0($a0)
+                lwl $t0, 3($a0)
+                li $a0, 0x10000003
+                lwr $t1, 0($a0)
+                lwl $t1, 3($a0)
+                li $a0, 0x10000004
+                lwr $t2, 0($a0)
+                lwl $t2, 3($a0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 0x5544_3322);
+            assert_eq!(cpu.get_reg("$t1"), 0x7766_5544);
+            assert_eq!(cpu.get_reg("$t2"), 0x8877_6655);
+        }
+
+        #[test]
+        fn unaligned_store_idiom_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0xAABBCCDD
+                li $a0, 0x10000002
+                swr $t0, 0($a0)
+                swl $t0, 3($a0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.memory.load_word(0x1000_0000) as u32, 0xCCDD_0000);
+            assert_eq!(cpu.memory.load_word(0x1000_0004) as u32, 0x0000_AABB);
+        }
+
+        #[test]
+        fn unaligned_lw_still_faults_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $a0, 0x10000001
+                lw $t0, 0($a0)
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(crate::program::EmuError::UnalignedAccess(0x1000_0001))));
+        }
+    }
+
+    mod tests_release2_arithmetic {
+        use super::CPU;
+        use crate::encoding::decode;
+
+        #[test]
+        fn mul_leaves_hi_lo_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 7
+                li $t1, 9
+                mult $t0, $t1
+                li $t2, -3
+                mul $t3, $t2, $t0
+                mul $t4, $t0, 1000
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t3") as i32, -21);
+            assert_eq!(cpu.get_reg("$t4"), 7000);
+            assert_eq!(cpu.get_lo(), 63);
+            assert_eq!(cpu.get_hi(), 0);
+        }
+
+        #[test]
+        fn multiply_accumulate_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x10000
+                li $t1, 0x10000
+                mult $zero, $zero
+                madd $t0, $t1
+                madd $t0, $t1
+                li $t2, -1
+                li $t3, 1
+                madd $t2, $t3
+                mfhi $s0
+                mflo $s1
+                msub $t0, $t1
+                mfhi $s2
+                mflo $s3
+                mult $zero, $zero
+                maddu $t2, $t3
+                mfhi $s4
+                mflo $s5
+                msubu $t2, $t3
+                mfhi $s6
+                mflo $s7
+            "#;
+
+            cpu.run_input(program).unwrap();
+            // 2 * 2^32 - 1
+            assert_eq!(cpu.get_reg("$s0"), 1);
+            assert_eq!(cpu.get_reg("$s1"), 0xFFFF_FFFF);
+            assert_eq!(cpu.get_reg("$s2"), 0);
+            assert_eq!(cpu.get_reg("$s3"), 0xFFFF_FFFF);
+
+            // unsigned: $t2 is 0xFFFFFFFF
+            assert_eq!(cpu.get_reg("$s4"), 0);
+            assert_eq!(cpu.get_reg("$s5"), 0xFFFF_FFFF);
+            assert_eq!(cpu.get_reg("$s6"), 0);
+            assert_eq!(cpu.get_reg("$s7"), 0);
+        }
+
+        #[test]
+        fn count_leading_bits_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x00F00000
+                clz $t1, $t0
+                clz $t2, $zero
+                li $t0, 0xFFF0000F
+                clo $t3, $t0
+                clo $t4, $zero
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 8);
+            assert_eq!(cpu.get_reg("$t2"), 32);
+            assert_eq!(cpu.get_reg("$t3"), 12);
+            assert_eq!(cpu.get_reg("$t4"), 0);
+        }
+
+        #[test]
+        fn conditional_move_and_sign_extend_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 5
+                li $t1, 1
+                li $t2, 9
+                li $t3, 9
+                movn $t2, $t0, $t1
+                movz $t3, $t0, $t1
+                li $t4, 0x1234FF80
+                seb $t5, $t4
+                seh $t6, $t4
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 5);
+            assert_eq!(cpu.get_reg("$t3"), 9);
+            assert_eq!(cpu.get_reg("$t5"), 0xFFFF_FF80);
+            assert_eq!(cpu.get_reg("$t6"), 0xFFFF_FF80);
+        }
+
+        #[test]
+        fn bit_field_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0xABCD1234
+                ext $t1, $t0, 8, 12
+                ext $t2, $t0, 0, 32
+                li $t3, 0xFFFFFFFF
+                li $t4, 0x5
+                ins $t3, $t4, 4, 8
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 0xD12);
+            assert_eq!(cpu.get_reg("$t2"), 0xABCD_1234);
+            assert_eq!(cpu.get_reg("$t3"), 0xFFFF_F05F);
+        }
+
+        #[test]
+        fn invalid_bit_field_test() {
+            let mut cpu = CPU::new();
+            assert!(cpu.run_input("ext $t1, $t0, 20, 16").is_err());
+        }
+
+        #[test]
+        fn arithmetic_pseudo_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, -17
+                li $t1, 5
+                rem $s0, $t0, $t1
+                neg $s1, $t1
+                not $s2, $t1
+                abs $s3, $t0
+                abs $s4, $t1
+                seq $s5, $t1, $t1
+                sne $s6, $t1, $t1
+                sge $s7, $t1, $t0
+                sgt $t8, $t0, $t1
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s0") as i32, -2);
+            assert_eq!(cpu.get_reg("$s1") as i32, -5);
+            assert_eq!(cpu.get_reg("$s2"), !5);
+            assert_eq!(cpu.get_reg("$s3"), 17);
+            assert_eq!(cpu.get_reg("$s4"), 5);
+            assert_eq!(cpu.get_reg("$s5"), 1);
+            assert_eq!(cpu.get_reg("$s6"), 0);
+            assert_eq!(cpu.get_reg("$s7"), 1);
+            assert_eq!(cpu.get_reg("$t8"), 0);
+        }
+
+        #[test]
+        fn release2_encoding_round_trip_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                mul $t0, $t1, $t2
+                madd $t0, $t1
+                maddu $t0, $t1
+                msub $t0, $t1
+                msubu $t0, $t1
+                clz $t0, $t1
+                clo $t0, $t1
+                movn $t0, $t1, $t2
+                movz $t0, $t1, $t2
+                seb $t0, $t1
+                seh $t0, $t1
+                ext $t0, $t1, 3, 7
+                ins $t0, $t1, 3, 7
+            "#;
+
+            let parsed = crate::program::Program::parse(program, &mut cpu.memory).unwrap();
+
+            for (i, insn) in parsed.core_instructions.iter().enumerate() {
+                let pc = parsed.index_to_pc(i);
+                let word = cpu.memory.load_word(pc) as u32;
+                let decoded = decode(word, pc, &parsed.symbol_table).unwrap();
+
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", insn));
+            }
+
+            // mul $t0, $t1, $t2 on the green sheet
+            assert_eq!(cpu.memory.load_word(parsed.index_to_pc(0)) as u32, 0x712A_4002);
+        }
+    }
+
+    mod tests_fpu {
+        use super::CPU;
+        use crate::console::BufferedConsole;
+        use crate::encoding::decode;
+
+        #[test]
+        fn single_precision_arithmetic_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            fa: .float 1.5
+            fb: .float -4.0
+                .text
+                li $t0, 0x10000000
+                lwc1 $f0, 0($t0)
+                lwc1 $f1, 4($t0)
+                add.s $f2, $f0, $f1
+                sub.s $f3, $f0, $f1
+                mul.s $f4, $f0, $f1
+                div.s $f5, $f1, $f0
+                abs.s $f6, $f1
+                neg.s $f7, $f0
+                mov.s $f8, $f1
+                sqrt.s $f9, $f6
+                swc1 $f2, 8($t0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_float("$f2"), -2.5);
+            assert_eq!(cpu.get_float("$f3"), 5.5);
+            assert_eq!(cpu.get_float("$f4"), -6.0);
+            assert_eq!(cpu.get_float("$f5"), -4.0 / 1.5);
+            assert_eq!(cpu.get_float("$f6"), 4.0);
+            assert_eq!(cpu.get_float("$f7"), -1.5);
+            assert_eq!(cpu.get_float("$f8"), -4.0);
+            assert_eq!(cpu.get_float("$f9"), 2.0);
+            assert_eq!(cpu.memory.load_float(0x1000_0008), -2.5);
+        }
+
+        #[test]
+        fn double_precision_arithmetic_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                .data
+            x: .double 0.1
+            y: .double 0.2
+                .text
+                li $t0, 0x10000000
+                ldc1 $f0, 0($t0)
+                ldc1 $f2, 8($t0)
+                add.d $f4, $f0, $f2
+                mul.d $f6, $f4, $f4
+                neg.d $f8, $f6
+                sdc1 $f4, 16($t0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_double("$f4"), 0.1 + 0.2);
+            assert_eq!(cpu.get_double("$f6"), (0.1 + 0.2) * (0.1 + 0.2));
+            assert_eq!(cpu.get_double("$f8"), -(0.1 + 0.2) * (0.1 + 0.2));
+            assert_eq!(cpu.memory.load_double(0x1000_0010), 0.1 + 0.2);
+
+            // the low word is in the even register
+            assert_eq!(cpu.get_fpr("$f4"), (0.1f64 + 0.2).to_bits() as u32);
+            assert_eq!(cpu.get_fpr("$f5"), ((0.1f64 + 0.2).to_bits() >> 32) as u32);
+        }
+
+        #[test]
+        fn conversion_and_moves_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, -7
+                mtc1 $t0, $f0
+                cvt.s.w $f1, $f0
+                cvt.d.w $f2, $f0
+                cvt.d.s $f4, $f1
+                li $t1, 0x40300000
+                mtc1 $t1, $f6
+                cvt.w.s $f7, $f6
+                mfc1 $t2, $f7
+                cvt.s.d $f8, $f2
+                cvt.w.d $f9, $f2
+                mfc1 $t3, $f9
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_float("$f1"), -7.0);
+            assert_eq!(cpu.get_double("$f2"), -7.0);
+            assert_eq!(cpu.get_double("$f4"), -7.0);
+            assert_eq!(cpu.get_float("$f8"), -7.0);
+
+            // 2.75 rounds to the nearest integer
+            assert_eq!(cpu.get_reg("$t2"), 3);
+            assert_eq!(cpu.get_reg("$t3") as i32, -7);
+        }
+
+        #[test]
+        fn compare_and_branch_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x3F800000
+                li $t1, 0x40000000
+                mtc1 $t0, $f0
+                mtc1 $t1, $f1
+                c.lt.s $f0, $f1
+                bc1f skip1
+                li $s0, 1
+            skip1:
+                c.eq.s $f0, $f1
+                bc1t skip2
+                li $s1, 1
+            skip2:
+                c.le.s 3, $f1, $f1
+                bc1t 3, taken
+                li $s2, 99
+            taken:
+                bc1f 3, end
+                li $s3, 1
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 1);
+            assert_eq!(cpu.get_reg("$s1"), 1);
+            assert_eq!(cpu.get_reg("$s2"), 0);
+            assert_eq!(cpu.get_reg("$s3"), 1);
+
+            // condition code 0 is bit 23 and codes 1-7 are bits 25-31
+            assert!(cpu.fp_condition(3));
+            assert_eq!(cpu.get_fcsr(), 1 << 27);
+        }
+
+        #[test]
+        fn nan_compares_unordered_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x7FC00000
+                mtc1 $t0, $f0
+                c.eq.s $f0, $f0
+                bc1t end
+                li $s0, 1
+            end:
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s0"), 1);
+        }
+
+        #[test]
+        fn float_syscalls_test() {
+            let mut cpu = CPU::new();
+            cpu.console = Box::new(BufferedConsole::with_input("2.25\n-0.5\n"));
+            let program = r#"
+                li $v0, 6
+                syscall
+                mov.s $f12, $f0
+                li $v0, 2
+                syscall
+                li $v0, 7
+                syscall
+                mov.d $f12, $f0
+                li $v0, 3
+                syscall
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.console.take_output(), "2.25-0.5");
+            assert_eq!(cpu.get_double("$f0"), -0.5);
+        }
+
+        #[test]
+        fn fpu_snapshot_test() {
+            let mut cpu = CPU::new();
+            cpu.run_input("li $t0, 0x3F800000\nmtc1 $t0, $f31").unwrap();
+
+            let snapshot = cpu.snapshot();
+            assert_eq!(snapshot.fpr.len(), 32);
+            assert_eq!(snapshot.fpr["$f31"], 0x3F80_0000);
+            assert_eq!(snapshot.fcsr, 0);
+        }
+
+        #[test]
+        fn invalid_fp_operands_test() {
+            let mut cpu = CPU::new();
+            assert!(cpu.run_input("add.d $f1, $f2, $f4").is_err());
+            assert!(cpu.run_input("add.s $f32, $f2, $f4").is_err());
+            assert!(cpu.run_input("add.s $t0, $f2, $f4").is_err());
+            assert!(cpu.run_input("c.eq.s 8, $f0, $f2").is_err());
+        }
+
+        #[test]
+        fn fpu_encoding_round_trip_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+            top:
+                add.s $f0, $f1, $f2
+                sub.d $f0, $f2, $f4
+                mul.s $f3, $f4, $f5
+                div.d $f6, $f8, $f10
+                sqrt.s $f1, $f2
+                abs.d $f2, $f4
+                mov.s $f5, $f6
+                neg.d $f8, $f10
+                cvt.s.w $f0, $f1
+                cvt.d.s $f2, $f3
+                cvt.w.d $f4, $f6
+                c.lt.d 2, $f0, $f2
+                c.eq.s $f1, $f3
+                bc1t top
+                bc1f 5, top
+                lwc1 $f7, 4($sp)
+                swc1 $f7, -4($sp)
+                ldc1 $f2, 8($gp)
+                sdc1 $f2, 16($gp)
+                mtc1 $t0, $f9
+                mfc1 $t1, $f9
+            "#;
+
+            let parsed = crate::program::Program::parse(program, &mut cpu.memory).unwrap();
+
+            for (i, insn) in parsed.core_instructions.iter().enumerate() {
+                let pc = parsed.index_to_pc(i);
+                let word = cpu.memory.load_word(pc) as u32;
+                let decoded = decode(word, pc, &parsed.symbol_table).unwrap();
+
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", insn));
+            }
+
+            // add.s $f0, $f1, $f2 on the green sheet
+            assert_eq!(cpu.memory.load_word(parsed.index_to_pc(0)) as u32, 0x4602_0800);
+            assert_eq!(parsed.core_instructions[11].to_string(), "c.lt.d 2, $f0, $f2");
+        }
+    }
+
+    mod tests_harts {
+        use super::CPU;
+        use crate::instruction::Register;
+        use crate::machine::{Machine, Scheduler, HART_STACK_SIZE, MAX_HARTS};
+        use crate::program::EmuError;
+        use crate::segments::Access;
+        use crate::shadow::UninitCheck;
+        use crate::watch::WatchTarget;
+
+        // each hart adds 1 to the shared word 50 times
+        const RACY_COUNTER: &str = r#"
+            li $t0, 0x10000000
+            li $t3, 50
+        loop:
+            lw $t1, 0($t0)
+            addi $t1, $t1, 1
+            sw $t1, 0($t0)
+            addi $t3, $t3, -1
+            bnez $t3, loop
+        "#;
+
+        const ATOMIC_COUNTER: &str = r#"
+            li $t0, 0x10000000
+            li $t3, 50
+        loop:
+            ll $t1, 0($t0)
+            addi $t1, $t1, 1
+            sc $t1, 0($t0)
+            beqz $t1, loop
+            addi $t3, $t3, -1
+            bnez $t3, loop
+        "#;
+
+        #[test]
+        fn ll_sc_single_hart_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0x10000000
+                li $t1, 5
+                ll $t2, 0($t0)
+                sc $t1, 0($t0)
+                li $t3, 9
+                sc $t3, 0($t0)
+                ll $t4, 0($t0)
+                sw $zero, 0($t0)
+                li $t5, 7
+                sc $t5, 0($t0)
+                sync
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t1"), 1);
+
+            // the reservation was used by the first sc
+            assert_eq!(cpu.get_reg("$t3"), 0);
+
+            // a store to the word breaks the reservation
+            assert_eq!(cpu.get_reg("$t5"), 0);
+            assert_eq!(cpu.memory.load_word(0x1000_0000), 0);
+        }
+
+        #[test]
+        fn ll_sc_encoding_test() {
+            let mut cpu = CPU::new();
+            let parsed = crate::program::Program::parse("ll $t1, 4($t0)\nsc $t1, -4($t0)\nsync", &mut cpu.memory).unwrap();
+
+            let words: Vec<u32> = (0..3).map(|i| cpu.memory.load_word(parsed.index_to_pc(i)) as u32).collect();
+            assert_eq!(words, vec![0xC109_0004, 0xE109_FFFC, 0x0000_000F]);
+
+            for (i, word) in words.iter().enumerate() {
+                let decoded = crate::encoding::decode(*word, parsed.index_to_pc(i), &parsed.symbol_table).unwrap();
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", parsed.core_instructions[i]));
+            }
+        }
+
+        #[test]
+        fn store_from_other_hart_breaks_reservation_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            let program = r#"
+                li $t0, 0x10000004
+                bnez $a0, other
+                ll $t1, 0($t0)
+                sc $t1, 0($t0)
+                j end
+            other:
+                sw $t0, 0($t0)
+            end:
+            "#;
+            machine.load_source(program).unwrap();
+
+            // hart 0 reserves (li is lui + ori), hart 1 stores to the word, then hart 0's sc fails
+            for _ in 0..4 { machine.step_hart(0).unwrap(); }
+            for _ in 0..4 { machine.step_hart(1).unwrap(); }
+            machine.step_hart(0).unwrap();
+
+            assert_eq!(machine.harts[0].get_reg("$t1"), 0);
+            assert_eq!(machine.memory.load_word(0x1000_0004), 0x1000_0004);
+        }
+
+        #[test]
+        fn racy_counter_loses_updates_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            machine.load_source(RACY_COUNTER).unwrap();
+            machine.run().unwrap();
+
+            assert!(machine.memory.load_word(0x1000_0000) < 100);
+        }
+
+        #[test]
+        fn atomic_counter_test() {
+            for scheduler in [Scheduler::RoundRobin { quantum: 1 }, Scheduler::RoundRobin { quantum: 3 }, Scheduler::Random { seed: 42 }] {
+                let mut machine = Machine::new(3, scheduler);
+                machine.load_source(ATOMIC_COUNTER).unwrap();
+                machine.run().unwrap();
+
+                assert_eq!(machine.memory.load_word(0x1000_0000), 150);
+            }
+        }
+
+        #[test]
+        fn seeded_scheduler_is_deterministic_test() {
+            let order = |seed| {
+                let mut machine = Machine::new(3, Scheduler::Random { seed });
+                machine.load_source(RACY_COUNTER).unwrap();
+                (0..60).map(|_| machine.step().unwrap()).collect::<Vec<_>>()
+            };
+
+            assert_eq!(order(7), order(7));
+            assert_ne!(order(7), order(8));
+        }
+
+        #[test]
+        fn harts_get_own_id_and_stack_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 2 });
+            let program = r#"
+                addi $sp, $sp, -4
+                sw $a0, 0($sp)
+                li $v0, 1
+                syscall
+            "#;
+            machine.load_source(program).unwrap();
+            machine.run().unwrap();
+
+            assert_eq!(machine.console.take_output(), "01");
+            assert!(machine.is_finished(0) && machine.is_finished(1));
+            assert_ne!(machine.harts[0].get_reg("$sp"), machine.harts[1].get_reg("$sp"));
+            assert_eq!(machine.snapshot(1).hart, 1);
+        }
+
+        #[test]
+        fn hart_exit_does_not_stop_others_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            let program = r#"
+                bnez $a0, work
+                li $v0, 10
+                syscall
+            work:
+                li $t0, 1
+                li $t1, 2
+                li $t2, 3
+            "#;
+            machine.load_source(program).unwrap();
+            machine.run().unwrap();
+
+            assert_eq!(machine.harts[0].exit_code, Some(0));
+            assert_eq!(machine.harts[1].get_reg("$t2"), 3);
+        }
+        #[test]
+        fn harts_copy_cpu_settings_test() {
+            let mut template = CPU::new();
+            template.set_breakpoints(vec![6]);
+            template.delayed_branching = true;
+            template.fetch_from_memory = true;
+            template.check_heap = true;
+            template.set_uninit_check(UninitCheck::Strict);
+            template.add_watchpoint(WatchTarget::Register(Register::from_name("$t5").unwrap()), None);
+
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            machine.configure(&template);
+            let program = r#"
+                .data
+            value: .word 7
+                .text
+                la $t4, value
+                lw $t0, 0($t4)
+                addi $t1, $t0, 1
+                add $t2, $t3, $t1
+            "#;
+            machine.load_source(program).unwrap();
+
+            for hart in &machine.harts {
+                assert!(hart.delayed_branching && hart.fetch_from_memory && hart.check_heap);
+                assert_eq!(hart.uninit_check(), UninitCheck::Strict);
+                assert_eq!(hart.watchpoints.len(), 1);
+            }
+
+            // the breakpoint on the addi (line 6, counted from 0) stops the first hart that reaches it
+            assert!(matches!(machine.run(), Err(EmuError::Breakpoint)));
+            assert_eq!(machine.harts[machine.current_hart].get_reg("$t0"), 7);
+
+            // $t3 was never written
+            machine.harts.iter_mut().for_each(|hart| hart.breakpoints.clear());
+            assert!(matches!(machine.run(), Err(EmuError::UninitializedRead { .. })));
+        }
+
+        #[test]
+        fn wasm_setters_apply_to_every_hart_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            machine.load_source("li $t0, 1").unwrap();
+            let mut wasm = crate::WasmCPU { cpu: CPU::new(), harts: Some(machine) };
+
+            wasm.set_breakpoints(vec![0]);
+            wasm.set_heap_checking(true);
+            wasm.set_segment_checking(true);
+            wasm.set_delayed_branching(true);
+            wasm.set_fetch_from_memory(true);
+            wasm.set_heap_limit(64);
+            let id = wasm.watch_register("$t0", None, None).unwrap();
+
+            let machine = wasm.harts.as_ref().unwrap();
+            for hart in machine.harts.iter().chain([&wasm.cpu]) {
+                assert!(hart.breakpoints.contains_key(&0));
+                assert!(hart.check_heap && hart.check_segments && hart.delayed_branching && hart.fetch_from_memory);
+                assert_eq!(hart.watchpoints[0].id, id);
+            }
+            assert_eq!(machine.memory.heap_limit(), machine.memory.heap_base() + 64);
+
+            assert!(wasm.remove_watchpoint(id));
+            assert!(wasm.harts.as_ref().unwrap().harts.iter().all(|hart| hart.watchpoints.is_empty()));
+        }
+
+        #[test]
+        fn wasm_views_show_the_current_hart_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            let program = r#"
+                li $t0, 0x10000000
+                sw $a0, 0($t0)
+                li $v0, 10
+                syscall
+            "#;
+            machine.load_source(program).unwrap();
+            let mut wasm = crate::WasmCPU { cpu: CPU::new(), harts: Some(machine) };
+
+            // li is lui + ori, so hart 0 stops before its sw on line 3 (counted from 1)
+            for _ in 0..2 { wasm.harts.as_mut().unwrap().step_hart(0).unwrap(); }
+            assert_eq!(wasm.get_current_line(), 2);
+            assert_eq!(wasm.next_instruction(), "0x00400008: sw $a0, 0($t0)");
+
+            wasm.set_register("$a0", 0x1234);
+            wasm.set_memory_word(0x1000_0004, 7);
+            wasm.harts.as_mut().unwrap().step_hart(0).unwrap();
+            assert_eq!(wasm.get_memory(0x1000_0000, 8), vec![0x34, 0x12, 0, 0, 7, 0, 0, 0]);
+
+            assert_eq!(wasm.exit_code(), None);
+            while wasm.harts.as_mut().unwrap().step_hart(0).is_ok() {}
+            assert_eq!(wasm.exit_code(), Some(0));
+            assert!(!wasm.disassemble(crate::memory::DEFAULT_TEXT_BASE_ADDRESS, 2).is_empty());
+        }
+
+        #[test]
+        fn watchpoint_on_a_hart_is_reported_test() {
+            let mut template = CPU::new();
+            template.add_watchpoint(WatchTarget::Register(Register::from_name("$t1").unwrap()), None);
+
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            machine.configure(&template);
+            machine.load_source("li $t0, 1\nbeqz $a0, end\nli $t1, 5\nend:").unwrap();
+
+            let result = machine.run();
+            assert!(matches!(result, Err(EmuError::Watchpoint { id: 1, new: 5, .. })));
+
+            let hart = machine.current_hart;
+            let report = crate::WasmCPU::hart_report(&mut machine, hart, result);
+            assert_eq!(hart, 1);
+            assert_eq!(report.error, "Watchpoint");
+            let watchpoint = report.watchpoint.unwrap();
+            assert_eq!((watchpoint.id, watchpoint.location.as_str(), watchpoint.new), (1, "$t1", 5));
+        }
+
+        #[test]
+        fn reload_resets_shared_memory_test() {
+            let mut machine = Machine::new(2, Scheduler::RoundRobin { quantum: 1 });
+            machine.load_source(RACY_COUNTER).unwrap();
+            machine.run().unwrap();
+            assert_ne!(machine.memory.load_word(0x1000_0000), 0);
+
+            machine.load_source("li $t0, 1").unwrap();
+            assert_eq!(machine.memory.load_word(0x1000_0000), 0);
+            assert!(!machine.is_finished(0) && !machine.is_finished(1));
+        }
+
+        #[test]
+        fn hart_stacks_fit_in_stack_segment_test() {
+            let mut machine = Machine::new(MAX_HARTS + 4, Scheduler::RoundRobin { quantum: 1 });
+            machine.load_source("li $t0, 1").unwrap();
+
+            assert_eq!(machine.harts.len(), MAX_HARTS);
+            for hart in &machine.harts {
+                let bottom = hart.get_reg("$sp") - (HART_STACK_SIZE - 4);
+                assert!(machine.memory.segments.check(bottom, HART_STACK_SIZE, Access::Store).is_ok());
+            }
+        }
+    }
+
+    mod tests_traps {
+        use super::CPU;
+        use crate::TrapReport;
+        use crate::encoding::decode;
+        use crate::memory::DEFAULT_TEXT_BASE_ADDRESS;
+        use crate::program::EmuError;
+
+        /// runs `trap $t0, $t1` (or `trap $t0, b` for the immediate forms) and returns whether it fired
+        fn fires(trap: &str, a: i32, b: i32) -> bool {
+            let mut cpu = CPU::new();
+            let operand = if trap.ends_with('i') || trap.ends_with("iu") { b.to_string() } else { "$t1".to_string() };
+            let program = format!(r#"
+                li $t0, {}
+                li $t1, {}
+                {} $t0, {}
+            "#, a, b, trap, operand);
+
+            match cpu.run_input(&program) {
+                Ok(()) => false,
+                Err(EmuError::Trap { .. }) => true,
+                Err(e) => panic!("unexpected error {:?}", e),
+            }
+        }
+
+        #[test]
+        fn register_trap_conditions_test() {
+            assert!(fires("teq", 3, 3));
+            assert!(!fires("teq", 3, 4));
+            assert!(fires("tne", 3, 4));
+            assert!(!fires("tne", 3, 3));
+            assert!(fires("tge", 3, -1));
+            assert!(!fires("tgeu", 3, -1));
+            assert!(fires("tlt", -1, 3));
+            assert!(!fires("tltu", -1, 3));
+            assert!(fires("tltu", 3, -1));
+        }
+
+        #[test]
+        fn immediate_trap_conditions_test() {
+            assert!(fires("teqi", 5, 5));
+            assert!(!fires("tnei", 5, 5));
+            assert!(fires("tgei", 0, -3));
+            assert!(fires("tlti", -4, -3));
+
+            // the immediate is sign-extended before the unsigned compare
+            assert!(fires("tltiu", 5, -1));
+            assert!(!fires("tgeiu", 5, -1));
+        }
+
+        #[test]
+        fn trap_reports_instruction_and_line_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $v0, 41
+                li $t0, 42
+                tne $v0, $t0, 3
+                li $t1, 1
+            "#;
+
+            match cpu.run_input(program) {
+                Err(EmuError::Trap { insn, code, pc, line }) => {
+                    assert_eq!(insn, "tne $v0, $t0, 3");
+                    assert_eq!(code, 3);
+                    assert_eq!(pc, DEFAULT_TEXT_BASE_ADDRESS + 16);
+                    assert_eq!(line, 4);
+                },
+                other => panic!("expected trap, got {:?}", other),
+            }
+
+            // execution stops at the trap
+            assert_eq!(cpu.get_reg("$t1"), 0);
+        }
+
+        #[test]
+        fn trap_reports_source_of_expanded_immediate_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 100000
+            check:  teqi   $t0, 100000   # does not fit in 16 bits, so it goes through $at
+            "#;
+
+            match cpu.run_input(program) {
+                Err(EmuError::Trap { insn, code, line, .. }) => {
+                    assert_eq!(insn, "teqi $t0, 100000");
+                    assert_eq!(code, 0);
+                    assert_eq!(line, 3);
+                },
+                other => panic!("expected trap, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn break_reports_code_and_line_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 1
+                break 17
+            "#;
+
+            let error = cpu.run_input(program).unwrap_err();
+            assert!(matches!(error, EmuError::BreakInstruction { code: 17, line: 3, .. }));
+
+            let report = TrapReport::from_error(&error).unwrap();
+            assert_eq!(report.kind, "break");
+            assert_eq!(report.instruction, "break 17");
+            assert_eq!(report.line, 3);
+
+            assert_eq!(TrapReport::from_error(&EmuError::DivideByZero), None);
+        }
+
+        #[test]
+        fn traps_vector_to_handler_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                teqi $zero, 0
+                move $s1, $s0
+                break
+                li $t2, 1
+            .ktext
+                mfc0 $k0, $13
+                srl $s0, $k0, 2
+                andi $s0, $s0, 0x1f
+                mfc0 $k0, $14
+                addiu $k0, $k0, 4
+                mtc0 $k0, $14
+                eret
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$s1"), 13);
+            assert_eq!(cpu.get_reg("$s0"), 9);
+            assert_eq!(cpu.get_reg("$t2"), 1);
+        }
+
+        #[test]
+        fn trap_encoding_round_trip_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                teq $t1, $t2, 7
+                tne $t1, $t2
+                tge $t1, $t2
+                tgeu $t1, $t2
+                tlt $t1, $t2
+                tltu $t1, $t2, 1023
+                teqi $t1, -5
+                tnei $t1, 5
+                tgei $t1, 5
+                tgeiu $t1, 5
+                tlti $t1, 5
+                tltiu $t1, 5
+                break
+                break 0xFFFFF
+            "#;
+
+            let parsed = crate::program::Program::parse(program, &mut cpu.memory).unwrap();
+
+            for (i, insn) in parsed.core_instructions.iter().enumerate() {
+                let pc = parsed.index_to_pc(i);
+                let word = cpu.memory.load_word(pc) as u32;
+                let decoded = decode(word, pc, &parsed.symbol_table).unwrap();
+
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", insn));
+            }
+
+            // teq $t1, $t2, 7 keeps the code in bits 15..6
+            assert_eq!(cpu.memory.load_word(parsed.index_to_pc(0)) as u32, 0x012A_01F4);
+        }
+
+        #[test]
+        fn trap_code_out_of_range_test() {
+            let mut cpu = CPU::new();
+            assert!(cpu.run_input("teq $t0, $t1, 1024").is_err());
+            assert!(cpu.run_input("break 0x100000").is_err());
+        }
+    }
+
+    mod tests_register_file {
+        use super::CPU;
+        use crate::instruction::{CoreInstruction, FpRegister, Register};
+        use crate::memory::DEFAULT_STACK_POINTER;
+        use crate::program::Program;
+
+        #[test]
+        fn register_names_resolve_to_numbers_test() {
+            assert_eq!(Register::from_name("$t0"), Some(Register(8)));
+            assert_eq!(Register::from_name("$ra"), Some(Register::RA));
+            assert_eq!(Register::from_name("$t10"), None);
+            assert_eq!(Register(29).to_string(), "$sp");
+
+            assert_eq!(FpRegister::from_name("$f12"), Some(FpRegister(12)));
+            assert_eq!(FpRegister::from_name("$f32"), None);
+            assert_eq!(FpRegister(3).to_string(), "$f3");
+        }
+
+        #[test]
+        fn parser_resolves_register_operands_test() {
+            let mut cpu = CPU::new();
+            let program = Program::parse("addu $s0, $t1, $ra", &mut cpu.memory).unwrap();
+
+            match &program.core_instructions[0] {
+                CoreInstruction::Addu { rd, rs, rt } => {
+                    assert_eq!((rd.number(), rs.number(), rt.number()), (16, 9, 31));
+                },
+                other => panic!("expected addu, got {:?}", other),
+            }
+            assert_eq!(program.core_instructions[0].to_string(), "addu $s0, $t1, $ra");
+        }
+
+        #[test]
+        fn name_api_matches_register_file_test() {
+            let mut cpu = CPU::new();
+            assert_eq!(cpu.get_reg("$sp"), DEFAULT_STACK_POINTER);
+
+            cpu.set_reg("$t3", 77);
+            assert_eq!(cpu.read_reg(Register(11)), 77);
+
+            cpu.write_reg(Register::V0, 5);
+            assert_eq!(cpu.get_reg("$v0"), 5);
+
+            // unknown names read as 0 and writes to them are ignored
+            cpu.set_reg("$nope", 9);
+            assert_eq!(cpu.get_reg("$nope"), 0);
+
+            let snapshot = cpu.snapshot();
+            assert_eq!(snapshot.registers.len(), 32);
+            assert_eq!(snapshot.registers["$t3"], 77);
+        }
+
+        #[test]
+        fn call_snapshot_checks_stack_pointer_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                jal leaky
+                li $t0, 1
+                j end
+            leaky:
+                addi $sp, $sp, -4
+                jr $ra
+            end:
+            "#;
+
+            assert!(matches!(cpu.run_input(program), Err(crate::program::EmuError::CallingConventionViolation(_))));
+        }
+    }
+
+    mod tests_register_names {
+        use super::CPU;
+        use crate::instruction::{CoreInstruction, Register};
+        use crate::program::Program;
+
+        #[test]
+        fn zero_register_is_hardwired_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                addi $zero, $zero, 5
+                li $0, 0x1234
+                addu $t0, $zero, $0
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$zero"), 0);
+            assert_eq!(cpu.get_reg("$t0"), 0);
+
+            cpu.set_reg("$zero", 9);
+            cpu.write_reg(Register::ZERO, 9);
+            assert_eq!(cpu.read_reg(Register::ZERO), 0);
+        }
+
+        #[test]
+        fn numeric_register_names_resolve_test() {
+            assert_eq!(Register::from_name("$8"), Some(Register(8)));
+            assert_eq!(Register::from_name("$31"), Some(Register::RA));
+            assert_eq!(Register::from_name("$s8"), Some(Register::FP));
+            assert_eq!(Register::from_name("$32"), None);
+            assert_eq!(Register::from_name("$"), None);
+            assert_eq!(Register::from_name("$-1"), None);
+        }
+
+        #[test]
+        fn parser_normalises_numeric_registers_test() {
+            let mut cpu = CPU::new();
+            let program = Program::parse("addu $16, $9, $s8", &mut cpu.memory).unwrap();
+
+            match &program.core_instructions[0] {
+                CoreInstruction::Addu { rd, rs, rt } => {
+                    assert_eq!((rd.number(), rs.number(), rt.number()), (16, 9, 30));
+                },
+                other => panic!("expected addu, got {:?}", other),
+            }
+            assert_eq!(program.core_instructions[0].to_string(), "addu $s0, $t1, $fp");
+
+            assert!(Program::parse("addu $32, $t0, $t1", &mut cpu.memory).is_err());
+        }
+
+        #[test]
+        fn numeric_names_share_the_register_file_test() {
+            let mut cpu = CPU::new();
+            cpu.set_reg("$8", 42);
+            cpu.set_reg("$s8", 7);
+
+            assert_eq!(cpu.get_reg("$t0"), 42);
+            assert_eq!(cpu.get_reg("$fp"), 7);
+            assert_eq!(cpu.get_reg("$30"), 7);
+
+            cpu.run_input("addi $31, $8, 1").unwrap();
+            assert_eq!(cpu.get_reg("$ra"), 43);
+        }
+    }
+
+    mod tests_memory_backend {
+        use super::CPU;
+        use crate::backend::{ContiguousBackend, MemoryBackend, PagedBackend};
+        use crate::memory::{Memory, DEFAULT_STACK_POINTER, DEFAULT_STATIC_DATA_BASE_ADDRESS, DEFAULT_TEXT_BASE_ADDRESS};
+
+        fn backends() -> Vec<Box<dyn MemoryBackend>> {
+            vec![Box::new(ContiguousBackend::new()), Box::new(PagedBackend::new())]
+        }
+
+        #[test]
+        fn backends_store_words_in_every_region_test() {
+            let addresses = [DEFAULT_TEXT_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS + 0x8000, DEFAULT_STACK_POINTER, 0x7FFF_0000, 0x8000_0180, 0x9000_0000];
+
+            for backend in backends() {
+                let mut memory = Memory::with_backend(backend);
+                for (i, addr) in addresses.iter().enumerate() {
+                    memory.set_word(*addr, -(i as i32) - 1);
+                }
+                for (i, addr) in addresses.iter().enumerate() {
+                    assert_eq!(memory.load_word(*addr), -(i as i32) - 1);
+                }
+                assert_eq!(memory.load_word(0x7FFE_0000), 0);
+            }
+        }
+
+        #[test]
+        fn accesses_straddling_a_segment_boundary_test() {
+            let mut memory = Memory::new();
+
+            // the first byte lies below the text segment, the rest inside it
+            memory.set_halfword(DEFAULT_TEXT_BASE_ADDRESS - 1, 0x1234);
+            assert_eq!(memory.load_byte(DEFAULT_TEXT_BASE_ADDRESS - 1), 0x34);
+            assert_eq!(memory.load_byte(DEFAULT_TEXT_BASE_ADDRESS), 0x12);
+            assert_eq!(memory.load_halfword(DEFAULT_TEXT_BASE_ADDRESS - 1), 0x1234);
+
+            memory.set_double(DEFAULT_STACK_POINTER, 2.5);
+            assert_eq!(memory.load_double(DEFAULT_STACK_POINTER), 2.5);
+        }
+
+        #[test]
+        fn isolation_applies_to_every_load_test() {
+            let base = DEFAULT_STATIC_DATA_BASE_ADDRESS;
+            let mut memory = Memory::new();
+            memory.set_word(base, 7);
+            memory.set_float(base + 4, 1.5);
+            memory.set_double(base + 8, 3.25);
+            memory.freeze_inputs();
+
+            memory.set_word(base, 8);
+            memory.set_float(base + 4, -1.0);
+            memory.set_double(base + 8, 0.0);
+
+            assert_eq!(memory.load_word(base), 7);
+            assert_eq!(memory.load_byte(base), 7);
+            assert_eq!(memory.load_halfword(base), 7);
+            assert_eq!(memory.load_float(base + 4), 1.5);
+            assert_eq!(memory.load_double(base + 8), 3.25);
+
+            memory.thaw_inputs();
+            assert_eq!(memory.load_word(base), 8);
+            assert_eq!(memory.load_float(base + 4), -1.0);
+        }
+
+        #[test]
+        fn sub_word_mmio_accesses_merge_into_the_device_word_test() {
+            let mut memory = Memory::new();
+            memory.set_word(0xFFFF_0000, 0x00FF_00FF);
+            memory.set_halfword(0xFFFF_0002, 0x1234);
+            assert_eq!(memory.load_word(0xFFFF_0000), 0x1234_00FF);
+            assert_eq!(memory.load_halfword(0xFFFF_0002), 0x1234);
+
+            memory.set_float(0xFFFF_0000, 1.0);
+            assert_eq!(memory.load_float(0xFFFF_0000), 1.0);
+        }
+
+        #[test]
+        fn programs_run_on_either_backend_test() {
+            let program = r#"
+            .data
+            values: .word 3, 4, 5
+            .text
+                la $t0, values
+                lw $t1, 0($t0)
+                lw $t2, 8($t0)
+                addi $sp, $sp, -4
+                sw $t2, 0($sp)
+                lh $t3, 0($sp)
+                addu $v1, $t1, $t3
+            "#;
+
+            for backend in backends() {
+                let mut cpu = CPU::new();
+                cpu.memory = Memory::with_backend(backend);
+                cpu.run_input(program).unwrap();
+                assert_eq!(cpu.get_reg("$v1"), 8);
+            }
+        }
+    }
+
+    mod tests_segments {
+        use super::CPU;
+        use crate::cpu::{CP0_BADVADDR, CP0_CAUSE};
+        use crate::memory::DEFAULT_TEXT_BASE_ADDRESS;
+        use crate::program::EmuError;
+        use crate::segments::{Access, FaultKind, Permissions, SegmentMap};
+
+        fn checked_cpu() -> CPU {
+            let mut cpu = CPU::new();
+            cpu.check_segments = true;
+            cpu
+        }
+
+        #[test]
+        fn segments_are_not_checked_by_default_test() {
+            let mut cpu = CPU::new();
+            cpu.run_input("li $t0, 4000\nli $t1, 7\nsw $t1, 0($t0)\nlw $t2, 0($t0)").unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 7);
+        }
+
+        #[test]
+        fn store_to_text_faults_test() {
+            let mut cpu = checked_cpu();
+            let program = "li $t0, 0x00400000\nli $t1, 7\nsw $t1, 4($t0)\n";
+
+            match cpu.run_input(program) {
+                Err(e @ EmuError::SegmentationFault { .. }) => {
+                    let EmuError::SegmentationFault { addr, pc, ref kind } = e else { unreachable!() };
+                    assert_eq!(addr, DEFAULT_TEXT_BASE_ADDRESS + 4);
+                    assert_eq!(pc, DEFAULT_TEXT_BASE_ADDRESS + 16);
+                    assert_eq!(*kind, FaultKind::Protected { access: Access::Store, segment: "text".to_string() });
+                    assert_eq!(cpu.error_message(&e), "SegmentationFault: store to read-only text at line 3 (address 0x00400004)");
+                },
+                other => panic!("expected a segmentation fault, got {:?}", other),
+            }
+
+            // the text is untouched
+            assert_ne!(cpu.memory.load_word(DEFAULT_TEXT_BASE_ADDRESS + 4), 7);
+        }
+
+        #[test]
+        fn out_of_segment_accesses_fault_test() {
+            let cases = [
+                ("sw $t1, 0($zero)", 0, Access::Store),
+                ("li $t0, 0x20000000\nlw $t1, 0($t0)", 0x2000_0000, Access::Load),
+                ("li $t0, 0x7FEFFFFF\nlb $t1, 0($t0)", 0x7FEF_FFFF, Access::Load),
+            ];
+
+            for (program, address, access) in cases {
+                let mut cpu = checked_cpu();
+                match cpu.run_input(program) {
+                    Err(EmuError::SegmentationFault { addr, kind, .. }) => {
+                        assert_eq!(addr, address);
+                        assert_eq!(kind, FaultKind::Unmapped(access));
+                    },
+                    other => panic!("expected a segmentation fault for {:?}, got {:?}", program, other),
+                }
+            }
+        }
+
+        #[test]
+        fn data_heap_stack_and_mmio_are_writable_test() {
+            let mut cpu = checked_cpu();
+            let program = r#"
+            .data
+            value: .word 5
+            .text
+                la $t0, value
+                lw $t0, 0($t0)
+                li $a0, 8
+                li $v0, 9
+                syscall
+                sw $t0, 4($v0)
+                lw $t1, 4($v0)
+                addi $sp, $sp, -4
+                sw $t1, 0($sp)
+                lw $t2, 0($sp)
+                addi $sp, $sp, 4
+                li $t3, 0xFFFF0000
+                sw $t2, 0($t3)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 5);
+            assert_eq!(cpu.memory.load_word(0xFFFF_0000), 5);
+        }
+
+        #[test]
+        fn permissions_are_configurable_test() {
+            let mut cpu = checked_cpu();
+            assert!(cpu.memory.segments.set_permissions("data", Permissions::from_flags("r--").unwrap()));
+            assert!(!cpu.memory.segments.set_permissions("bss", Permissions::ALL));
+
+            // kept across the reset that loads a program
+            cpu.reset();
+            let program = ".data\nvalue: .word 5\n.text\nla $t1, value\nlw $t0, 0($t1)\nsw $t0, 0($t1)\n";
+            assert!(matches!(
+                cpu.run_input(program),
+                Err(EmuError::SegmentationFault { kind: FaultKind::Protected { access: Access::Store, .. }, .. })
+            ));
+            assert_eq!(cpu.get_reg("$t0"), 5);
+
+            let mut segments = SegmentMap::new();
+            segments.set_permissions("stack", Permissions::NONE);
+            assert_eq!(segments.check(0x7FFF_FFFC, 4, Access::Load), Err(FaultKind::Protected { access: Access::Load, segment: "stack".to_string() }));
+            assert_eq!(Permissions::READ_EXECUTE.to_string(), "r-x");
+        }
+
+        #[test]
+        fn accesses_spanning_segments_are_checked_test() {
+            let segments = SegmentMap::new();
+            assert!(segments.check(0x1000_7FFC, 8, Access::Store).is_ok());
+            assert_eq!(segments.check(0x0FFF_FFFC, 8, Access::Store), Err(FaultKind::Protected { access: Access::Store, segment: "text".to_string() }));
+            assert_eq!(segments.check(0x003F_FFFE, 4, Access::Load), Err(FaultKind::Unmapped(Access::Load)));
+        }
+
+        #[test]
+        fn segmentation_fault_vectors_to_handler_test() {
+            let mut cpu = checked_cpu();
+            let program = r#"
+                .text
+                li $t0, 0x00400000
+                sw $t0, 0($t0)
+                li $t2, 5
+            .ktext 0x80000180
+                mfc0 $k0, $13
+                srl $s0, $k0, 2
+                andi $s0, $s0, 0x1f
+                mfc0 $k0, $14
+                addi $k0, $k0, 4
+                mtc0 $k0, $14
+                eret
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 5);
+            assert_eq!(cpu.get_reg("$s0"), 5);
+            assert_eq!(cpu.get_cp0(CP0_BADVADDR), DEFAULT_TEXT_BASE_ADDRESS);
+            assert_eq!((cpu.get_cp0(CP0_CAUSE) >> 2) & 0x1F, 5);
+        }
+    }
+
+    mod tests_heap {
+        use super::CPU;
+        use crate::memory::{HeapAllocation, Memory, DEFAULT_HEAP_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS};
+        use crate::program::EmuError;
+        use crate::segments::{Access, FaultKind};
+
+        const BASE: u32 = DEFAULT_HEAP_BASE_ADDRESS;
+
+        #[test]
+        fn allocations_are_tracked_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $a0, 10
+                li $v0, 9
+                syscall
+                li $a0, 4
+                li $v0, 9
+                syscall
+            "#;
+
+            cpu.run_input(program).unwrap();
+            let heap = cpu.memory.heap_view();
+            assert_eq!(heap.heap_break, BASE + 16);
+            assert_eq!(heap.allocations, vec![
+                HeapAllocation { address: BASE, size: 12, requested: 10, line: 4 },
+                HeapAllocation { address: BASE + 12, size: 4, requested: 4, line: 7 },
+            ]);
+        }
+
+        #[test]
+        fn sbrk_past_the_limit_fails_test() {
+            let mut cpu = CPU::new();
+            cpu.memory.set_heap_limit(64);
+            cpu.reset();
+            let program = r#"
+                li $a0, 48
+                li $v0, 9
+                syscall
+                li $a0, 32
+                li $v0, 9
+                syscall
+            "#;
+
+            match cpu.run_input(program) {
+                Err(e @ EmuError::OutOfMemory { .. }) => {
+                    assert!(matches!(e, EmuError::OutOfMemory { requested: 32, available: 16, line: 7, .. }));
+                    assert_eq!(cpu.error_message(&e), "OutOfMemory: sbrk(32) at line 7 needs more than the 16 bytes left on the heap");
+                },
+                other => panic!("expected OutOfMemory, got {:?}", other),
+            }
+            assert_eq!(cpu.memory.heap_view().limit, BASE + 64);
+            assert_eq!(cpu.memory.heap_break, BASE + 48);
+        }
+
+        #[test]
+        fn negative_sbrk_releases_memory_test() {
+            let mut memory = Memory::new();
+            assert_eq!(memory.sbrk(8, 1), Ok(BASE));
+            assert_eq!(memory.sbrk(8, 2), Ok(BASE + 8));
+            assert_eq!(memory.sbrk(-12, 3), Ok(BASE + 16));
+
+            assert_eq!(memory.heap_break, BASE + 4);
+            assert_eq!(memory.allocations, vec![HeapAllocation { address: BASE, size: 4, requested: 4, line: 1 }]);
+
+            // cannot move below the heap base
+            assert_eq!(memory.sbrk(-8, 4), Err(4));
+            assert_eq!(memory.heap_break, BASE + 4);
+        }
+
+        #[test]
+        fn heap_checking_catches_reads_past_the_break_test() {
+            let program = r#"
+                li $a0, 8
+                li $v0, 9
+                syscall
+                sw $zero, 4($v0)
+                lw $t0, 8($v0)
+            "#;
+
+            // without checking the read just returns zero
+            let mut cpu = CPU::new();
+            cpu.run_input(program).unwrap();
+
+            let mut cpu = CPU::new();
+            cpu.check_heap = true;
+            match cpu.run_input(program) {
+                Err(e @ EmuError::SegmentationFault { .. }) => {
+                    assert!(matches!(e, EmuError::SegmentationFault { addr, kind: FaultKind::Unallocated(Access::Load), .. } if addr == BASE + 8));
+                    assert!(cpu.error_message(&e).contains("load from unallocated heap memory at line 6"));
+                },
+                other => panic!("expected a segmentation fault, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn heap_starts_after_large_static_data_test() {
+            let mut cpu = CPU::new();
+            cpu.check_segments = true;
+            cpu.check_heap = true;
+            let program = r#"
+                .data
+            big: .space 40000
+            last: .word 7
+                .text
+                li $a0, 16
+                li $v0, 9
+                syscall
+                move $s0, $v0
+                li $t1, 99
+                sw $t1, 0($s0)
+                la $t2, big
+                sw $t1, 0($t2)
+                la $t2, last
+                lw $t3, 0($t2)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            let data_end = DEFAULT_STATIC_DATA_BASE_ADDRESS + 40004;
+            assert_eq!(cpu.get_reg("$s0"), data_end);
+            assert_eq!(cpu.get_reg("$t3"), 7);
+
+            let heap = cpu.memory.heap_view();
+            assert_eq!((heap.base, heap.heap_break), (data_end, data_end + 16));
+            assert_eq!(cpu.memory.segments.get("data").unwrap().last, data_end - 1);
+        }
+    }
+
+    mod tests_uninitialized {
+        use super::CPU;
+        use crate::memory::DEFAULT_STACK_POINTER;
+        use crate::program::EmuError;
+        use crate::shadow::{UninitCheck, UninitializedRead};
+
+        fn cpu_with(check: UninitCheck) -> CPU {
+            let mut cpu = CPU::new();
+            cpu.set_uninit_check(check);
+            cpu
+        }
+
+        #[test]
+        fn unset_register_is_reported_in_strict_mode_test() {
+            let mut cpu = cpu_with(UninitCheck::Strict);
+            let program = r#"
+                li $t1, 5
+                add $t2, $t1, $t0
+            "#;
+
+            match cpu.run_input(program) {
+                Err(EmuError::UninitializedRead { location, line, .. }) => {
+                    assert_eq!(location, "$t0");
+                    assert_eq!(line, 3);
+                },
+                other => panic!("expected UninitializedRead, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn syscall_with_unset_argument_is_reported_test() {
+            let mut cpu = cpu_with(UninitCheck::Strict);
+            let program = r#"
+                li $v0, 1
+                syscall
+            "#;
+
+            match cpu.run_input(program) {
+                Err(EmuError::UninitializedRead { location, line, .. }) => {
+                    assert_eq!(location, "$a0");
+                    assert_eq!(line, 3);
+                },
+                other => panic!("expected UninitializedRead, got {:?}", other),
+            }
+            assert_eq!(cpu.console.take_output(), "0");
+
+            // exit does not read $a0, exit2 does
+            let mut cpu = cpu_with(UninitCheck::Warn);
+            cpu.run_input("li $v0, 2\nsyscall\nli $v0, 10\nsyscall").unwrap();
+            let locations: Vec<&str> = cpu.uninit_warnings.iter().map(|w| w.location.as_str()).collect();
+            assert_eq!(locations, vec!["$f12"]);
+
+            let mut cpu = cpu_with(UninitCheck::Strict);
+            assert!(matches!(cpu.run_input("li $v0, 17\nsyscall"), Err(EmuError::UninitializedRead { .. })));
+
+            let mut cpu = cpu_with(UninitCheck::Strict);
+            cpu.run_input("li $t0, 7\nmtc1 $t0, $f12\nli $v0, 2\nsyscall").unwrap();
+        }
+
+        #[test]
+        fn warn_mode_records_each_read_once_test() {
+            let mut cpu = cpu_with(UninitCheck::Warn);
+            let program = r#"
+                li $t1, 3
+            loop:
+                add $t2, $t2, $t1
+                addi $t1, $t1, -1
+                bne $t1, $zero, loop
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.uninit_warnings, vec![UninitializedRead { location: "$t2".to_string(), pc: 0x0040_0008, line: 4 }]);
+            assert_eq!(cpu.get_reg("$t2"), 6);
+        }
+
+        #[test]
+        fn saving_an_unset_register_is_not_reported_test() {
+            let mut cpu = cpu_with(UninitCheck::Warn);
+            let program = r#"
+                addi $sp, $sp, -4
+                sw $s0, 0($sp)
+                lw $s0, 0($sp)
+                addi $sp, $sp, 4
+                move $t0, $s0
+            "#;
+
+            cpu.run_input(program).unwrap();
+            let locations: Vec<&str> = cpu.uninit_warnings.iter().map(|w| w.location.as_str()).collect();
+            assert_eq!(locations, vec!["$s0"]);
+            assert_eq!(cpu.uninit_warnings[0].line, 6);
+        }
+
+        #[test]
+        fn unwritten_memory_is_reported_test() {
+            let mut cpu = cpu_with(UninitCheck::Warn);
+            let program = r#"
+                .data
+            value: .word 7
+            buffer: .space 8
+                .text
+                la $t0, value
+                lw $t1, 0($t0)
+                la $t0, buffer
+                lw $t2, 4($t0)
+                lw $t3, -8($sp)
+                li $a0, 8
+                li $v0, 9
+                syscall
+                lb $t4, 1($v0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            let heap = cpu.get_reg("$v0");
+            let locations: Vec<String> = cpu.uninit_warnings.iter().map(|w| w.location.clone()).collect();
+            assert_eq!(locations, vec![
+                format!("0x{:08x}", DEFAULT_STACK_POINTER - 8),
+                format!("0x{:08x}", heap + 1),
+            ]);
+        }
+
+        #[test]
+        fn grader_inputs_count_as_written_test() {
+            let mut cpu = cpu_with(UninitCheck::Strict);
+            cpu.set_reg("$a0", 0x1001_0000);
+            cpu.memory.set_word(0x1001_0000, 42);
+
+            cpu.run_input("lw $t0, 0($a0)").unwrap();
+            assert_eq!(cpu.get_reg("$t0"), 42);
+        }
+
+        #[test]
+        fn check_is_off_by_default_test() {
+            let mut cpu = CPU::new();
+            cpu.run_input("add $t0, $t1, $t2\nlw $t3, -8($sp)").unwrap();
+            assert!(cpu.uninit_warnings.is_empty());
+            assert!(cpu.memory.shadow.is_none());
+        }
+
+        #[test]
+        fn error_message_names_the_location_test() {
+            let mut cpu = cpu_with(UninitCheck::Strict);
+            let error = cpu.run_input("lw $t0, -4($sp)").unwrap_err();
+            assert_eq!(
+                cpu.error_message(&error),
+                format!("UninitializedRead: memory at 0x{:08x} is read before it was written at line 1", DEFAULT_STACK_POINTER - 4)
+            );
+        }
+    }
+
+    mod tests_watchpoints {
+        use super::CPU;
+        use crate::WatchReport;
+        use crate::instruction::Register;
+        use crate::memory::DEFAULT_STATIC_DATA_BASE_ADDRESS;
+        use crate::program::EmuError;
+        use crate::watch::{Comparison, WatchAccess, WatchTarget};
+
+        const PROGRAM: &str = r#"
+            .data
+        value: .word 1
+            .text
+            la $t0, value
+            lw $t1, 0($t0)
+            li $t2, 0x105
+            sw $t2, 0($t0)
+            sb $zero, 1($t0)
+        "#;
+
+        /// the (id, addr, old, new) of a watchpoint stop
+        const VALUE: u32 = DEFAULT_STATIC_DATA_BASE_ADDRESS;
+
+        fn hit(result: Result<(), EmuError>) -> (usize, u32, u32, u32) {
+            match result {
+                Err(EmuError::Watchpoint { id, addr, old, new }) => (id, addr, old, new),
+                other => panic!("expected a watchpoint, got {:?}", other),
+            }
+        }
+
+        fn watch_value(cpu: &mut CPU, access: WatchAccess) -> usize {
+            cpu.add_watchpoint(WatchTarget::Memory { start: VALUE, len: 4, access }, None)
+        }
+
+        #[test]
+        fn write_watchpoint_stops_after_the_store_test() {
+            let mut cpu = CPU::new();
+            let id = watch_value(&mut cpu, WatchAccess::Write);
+
+            assert_eq!(hit(cpu.run_input(PROGRAM)), (id, VALUE, 1, 0x105));
+            assert_eq!(cpu.pc, 0x0040_0018);
+
+            // byte stores report the byte
+            assert_eq!(hit(cpu.run()), (id, VALUE + 1, 1, 0));
+            assert!(cpu.run().is_ok());
+        }
+
+        #[test]
+        fn read_watchpoint_ignores_stores_test() {
+            let mut cpu = CPU::new();
+            let id = watch_value(&mut cpu, WatchAccess::Read);
+
+            assert_eq!(hit(cpu.run_input(PROGRAM)), (id, VALUE, 1, 1));
+            assert_eq!(cpu.get_reg("$t1"), 1);
+            assert!(cpu.run().is_ok());
+        }
+
+        #[test]
+        fn memory_outside_the_range_is_not_watched_test() {
+            let mut cpu = CPU::new();
+            cpu.add_watchpoint(WatchTarget::Memory { start: VALUE + 4, len: 4, access: WatchAccess::Both }, None);
+
+            assert!(cpu.run_input(PROGRAM).is_ok());
+        }
+
+        #[test]
+        fn register_watchpoint_with_condition_test() {
+            let mut cpu = CPU::new();
+            let id = cpu.add_watchpoint(WatchTarget::Register(Register(16)), Some((Comparison::Ge, 3)));
+            let program = r#"
+                li $s0, 0
+            loop:
+                addi $s0, $s0, 1
+                slti $t0, $s0, 5
+                bne $t0, $zero, loop
+            "#;
+
+            assert_eq!(hit(cpu.run_input(program)), (id, 16, 2, 3));
+            assert_eq!(hit(cpu.run()), (id, 16, 3, 4));
+
+            assert!(cpu.remove_watchpoint(id));
+            assert!(!cpu.remove_watchpoint(id));
+            assert!(cpu.run().is_ok());
+            assert_eq!(cpu.get_reg("$s0"), 5);
+        }
+
+        #[test]
+        fn watch_report_names_the_location_test() {
+            let mut cpu = CPU::new();
+            let register = cpu.add_watchpoint(WatchTarget::Register(Register(16)), None);
+            let memory = watch_value(&mut cpu, WatchAccess::Both);
+
+            let error = EmuError::Watchpoint { id: register, addr: 16, old: 0, new: 7 };
+            assert_eq!(WatchReport::from_error(&error, &cpu.watchpoints).unwrap().location, "$s0");
+
+            let error = EmuError::Watchpoint { id: memory, addr: VALUE, old: 1, new: 5 };
+            assert_eq!(WatchReport::from_error(&error, &cpu.watchpoints).unwrap().location, "0x10000000");
+            assert_eq!(WatchReport::from_error(&EmuError::Breakpoint, &cpu.watchpoints), None);
+        }
+    }
+
+    mod tests_breakpoints {
+        use super::CPU;
+        use crate::breakpoint::Breakpoint;
+        use crate::expr::Expr;
+        use crate::memory::Memory;
+        use crate::program::EmuError;
+
+        // the breakpoints use 0-based lines, so line 3 is the `addi`
+        const LOOP: &str = r#"
+            li $t0, 0
+        loop:
+            addi $t0, $t0, 1
+            slti $t1, $t0, 5
+            bne $t1, $zero, loop
+        "#;
+        const ADDI_LINE: usize = 3;
+
+        fn breakpoint(condition: Option<&str>, ignore_count: u32, log_message: Option<&str>) -> Breakpoint {
+            Breakpoint::new(condition, ignore_count, log_message).unwrap()
+        }
+
+        fn eval(text: &str, registers: &[u32; 32], memory: &mut Memory) -> i32 {
+            Expr::parse(text).unwrap().eval(registers, memory)
+        }
+
+        #[test]
+        fn expressions_test() {
+            let mut registers = [0; 32];
+            registers[8] = 10;              // $t0
+            registers[29] = 0x7FFF_EFF0;    // $sp
+            let mut memory = Memory::new();
+            memory.set_word(0x7FFF_EFF4, 3);
+
+            assert_eq!(eval("$t0 == 10 && mem[$sp+4] > 0", &registers, &mut memory), 1);
+            assert_eq!(eval("$t0 == 10 && mem[$sp] > 0", &registers, &mut memory), 0);
+            assert_eq!(eval("$8 != 10 || !(mem[$sp + 4] - 3)", &registers, &mut memory), 1);
+            assert_eq!(eval("1 + 2 * 3", &registers, &mut memory), 7);
+            assert_eq!(eval("-1 < 0x7fffffff", &registers, &mut memory), 1);
+            assert_eq!(eval("0xffffffff", &registers, &mut memory), -1);
+        }
+
+        #[test]
+        fn expression_errors_test() {
+            assert_eq!(Expr::parse("$t10 == 1"), Err("unknown register $t10".to_string()));
+            assert_eq!(Expr::parse("mem[$sp + 4 > 0"), Err("expected ']'".to_string()));
+            assert_eq!(Expr::parse("$t0 =="), Err("unexpected end of expression".to_string()));
+            assert_eq!(Expr::parse("$t0 = 1"), Err("unexpected '='".to_string()));
+            assert_eq!(Expr::parse("$t0 1"), Err("unexpected text after the expression".to_string()));
+
+            let error = Breakpoint::new(None, 0, Some("i = {$t0")).unwrap_err();
+            assert_eq!(error, "log message: missing '}'");
+        }
+
+        #[test]
+        fn conditional_breakpoint_test() {
+            let mut cpu = CPU::new();
+            cpu.set_breakpoint(ADDI_LINE, breakpoint(Some("$t0 == 3"), 0, None));
+
+            assert!(matches!(cpu.run_input(LOOP), Err(EmuError::Breakpoint)));
+            assert_eq!(cpu.get_reg("$t0"), 3);
+            assert!(cpu.run().is_ok());
+            assert_eq!(cpu.get_reg("$t0"), 5);
+        }
+
+        #[test]
+        fn ignore_count_test() {
+            let mut cpu = CPU::new();
+            cpu.set_breakpoint(ADDI_LINE, breakpoint(None, 2, None));
+
+            assert!(matches!(cpu.run_input(LOOP), Err(EmuError::Breakpoint)));
+            assert_eq!(cpu.get_reg("$t0"), 2);
+            assert_eq!(cpu.breakpoints[&ADDI_LINE].hits, 3);
+
+            // the IDE sends the lines again before every run, which keeps the count
+            cpu.set_breakpoints(vec![ADDI_LINE]);
+            assert!(matches!(cpu.run(), Err(EmuError::Breakpoint)));
+            assert_eq!(cpu.get_reg("$t0"), 3);
+            assert_eq!(cpu.breakpoints[&ADDI_LINE].hits, 4);
+        }
+
+        #[test]
+        fn log_message_does_not_stop_test() {
+            let mut cpu = CPU::new();
+            cpu.set_breakpoint(ADDI_LINE, breakpoint(Some("$t0 > 1"), 0, Some("t0 = {$t0}, doubled {$t0 * 2}")));
+
+            assert!(cpu.run_input(LOOP).is_ok());
+            assert_eq!(cpu.console.take_output(), "t0 = 2, doubled 4\nt0 = 3, doubled 6\nt0 = 4, doubled 8\n");
+        }
+
+        #[test]
+        fn pseudo_instruction_is_hit_once_test() {
+            let mut cpu = CPU::new();
+            let program = r#"
+                li $t0, 0
+            loop:
+                li $t2, 0x12345678
+                addi $t0, $t0, 1
+                slti $t1, $t0, 3
+                bne $t1, $zero, loop
+            "#;
+            cpu.set_breakpoint(3, breakpoint(None, 100, None));
+
+            assert!(cpu.run_input(program).is_ok());
+            assert_eq!(cpu.breakpoints[&3].hits, 3);
+        }
+    }
+
+    mod tests_history {
+        use super::CPU;
+        use crate::breakpoint::Breakpoint;
+        use crate::memory::{DEFAULT_HEAP_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS};
+
+        fn cpu_with_history(size: usize) -> CPU {
+            let mut cpu = CPU::new();
+            cpu.history.set_limit(size);
+            cpu
+        }
+
+        #[test]
+        fn step_back_restores_registers_and_memory_test() {
+            let mut cpu = cpu_with_history(100);
+            let program = r#"
+                .data
+            value: .word 7
+                .text
+                la $t0, value
+                li $t1, 6
+                mult $t1, $t1
+                mflo $t2
+                sw $t2, 0($t0)
+                sb $t1, 2($t0)
+            "#;
+
+            cpu.run_input(program).unwrap();
+            assert_eq!(cpu.memory.load_word(DEFAULT_STATIC_DATA_BASE_ADDRESS), 0x0006_0024);
+            let length = cpu.history.len();
+
+            // back over the two stores
+            assert!(cpu.step_back());
+            assert_eq!(cpu.memory.load_word(DEFAULT_STATIC_DATA_BASE_ADDRESS), 36);
+            assert!(cpu.step_back());
+            assert_eq!(cpu.memory.load_word(DEFAULT_STATIC_DATA_BASE_ADDRESS), 7);
+
+            while cpu.step_back() {}
+            assert_eq!(length, 8);
+            assert_eq!(cpu.pc, 0x0040_0000);
+            assert_eq!(cpu.get_lo(), 0);
+            for register in ["$t0", "$t1", "$t2"] {
+                assert_eq!(cpu.get_reg(register), 0);
+            }
+
+            // and forwards again to the same result
+            cpu.run().unwrap();
+            assert_eq!(cpu.get_reg("$t2"), 36);
+            assert_eq!(cpu.memory.load_word(DEFAULT_STATIC_DATA_BASE_ADDRESS), 0x0006_0024);
+        }
+
+        #[test]
+        fn history_is_bounded_test() {
+            let mut cpu = cpu_with_history(3);
+            cpu.run_input("li $t0, 1\naddi $t0, $t0, 1\naddi $t0, $t0, 1\naddi $t0, $t0, 1\naddi $t0, $t0, 1").unwrap();
+
+            assert_eq!(cpu.history.len(), 3);
+            assert!(cpu.step_back() && cpu.step_back() && cpu.step_back());
+            assert!(!cpu.step_back());
+            assert_eq!(cpu.get_reg("$t0"), 2);
+        }
+
+        #[test]
+        fn history_is_off_by_default_test() {
+            let mut cpu = CPU::new();
+            cpu.run_input("li $t0, 1").unwrap();
+            assert!(!cpu.step_back());
+        }
+
+        #[test]
+        fn step_back_over_calls_and_exit_test() {
+            let mut cpu = cpu_with_history(100);
+            let program = r#"
+                li $s0, 4
+                jal double
+                move $a0, $v0
+                li $v0, 17
+                syscall
+            double:
+                add $v0, $s0, $s0
+                jr $ra
+            "#;
+
+            assert!(cpu.run_input(program).is_ok());
+            assert_eq!(cpu.exit_code, Some(8));
+
+            // back to before the jal, then rerun without a calling convention violation
+            while cpu.get_reg("$ra") != 0 {
+                assert!(cpu.step_back());
+            }
+            assert_eq!(cpu.call_depth(), 0);
+            assert_eq!(cpu.exit_code, None);
+            assert!(cpu.run().is_ok());
+            assert_eq!(cpu.exit_code, Some(8));
+        }
+
+        #[test]
+        fn sbrk_is_undone_test() {
+            let mut cpu = cpu_with_history(10);
+            cpu.run_input("li $a0, 16\nli $v0, 9\nsyscall").unwrap();
+            assert_eq!(cpu.memory.heap_break, DEFAULT_HEAP_BASE_ADDRESS + 16);
+
+            assert!(cpu.step_back());
+            assert_eq!(cpu.memory.heap_break, DEFAULT_HEAP_BASE_ADDRESS);
+            assert!(cpu.memory.allocations.is_empty());
+        }
+
+        #[test]
+        fn run_back_to_breakpoint_test() {
+            let mut cpu = cpu_with_history(100);
+            let program = r#"
+                li $t0, 0
+            loop:
+                addi $t0, $t0, 1
+                slti $t1, $t0, 5
+                bne $t1, $zero, loop
+            "#;
+
+            cpu.run_input(program).unwrap();
+            cpu.set_breakpoint(3, Breakpoint::new(Some("$t0 == 2"), 0, None).unwrap());
+
+            assert!(cpu.run_back_to_breakpoint());
+            assert_eq!(cpu.get_reg("$t0"), 2);
+            assert!(cpu.at_breakpoint());
+
+            // nothing earlier matches
+            assert!(!cpu.run_back_to_breakpoint());
+            assert_eq!(cpu.pc, 0x0040_0000);
+        }
+    }
+
+    mod tests_stepping {
+        use super::CPU;
+        use crate::breakpoint::Breakpoint;
+        use crate::program::Program;
+        use crate::EmuError;
+
+        const SQUARE: &str = r#"
+                addi $a0, $zero, 3
+                jal square
+                move $s0, $v0
+                li $v0, 10
+                syscall
+            square:
+                mult $a0, $a0
+                mflo $v0
+                jr $ra
+            "#;
+
+        fn load(source: &str) -> CPU {
+            let mut cpu = CPU::new();
+            cpu.history.set_limit(100);
+            let program = Program::parse(source, &mut cpu.memory).unwrap();
+            cpu.load_program(program);
+            cpu
+        }
+
+        #[test]
+        fn faulting_return_keeps_call_stack_test() {
+            let mut cpu = load(r#"
+                jal leak
+                li $v0, 10
+                syscall
+            leak:
+                addi $sp, $sp, -8
+                jr $ra
+            "#);
+
+            cpu.next().unwrap();
+            cpu.next().unwrap();
+            assert!(matches!(cpu.next(), Err(EmuError::CallingConventionViolation(_))));
+            assert_eq!(cpu.call_depth(), 1);
+            assert_eq!(cpu.call_stack[0].return_address, 0x0040_0004);
+        }
+
+        #[test]
+        fn step_over_call_test() {
+            let mut cpu = load(SQUARE);
+
+            // an ordinary instruction is a single step
+            cpu.step_over().unwrap();
+            assert_eq!(cpu.pc, 0x0040_0004);
+
+            cpu.step_over().unwrap();
+            assert_eq!(cpu.pc, 0x0040_0008);
+            assert_eq!(cpu.get_reg("$v0"), 9);
+            assert_eq!(cpu.call_depth(), 0);
+
+            // undoing the return re-enters the call
+            assert!(cpu.step_back());
+            assert_eq!(cpu.call_depth(), 1);
+            assert_eq!(cpu.pc, 0x0040_0024);
+        }
+
+        #[test]
+        fn step_over_stops_at_breakpoint_in_call_test() {
+            let mut cpu = load(SQUARE);
+            cpu.set_breakpoints(vec![7]);
+
+            cpu.step_over().unwrap();
+            assert!(matches!(cpu.step_over(), Err(EmuError::Breakpoint)));
+            assert_eq!(cpu.pc, 0x0040_001c);
+            assert_eq!(cpu.call_depth(), 1);
+        }
+
+        #[test]
+        fn step_out_test() {
+            let mut cpu = load(SQUARE);
+            cpu.next().unwrap();
+            cpu.next().unwrap();
+            cpu.next().unwrap();
+            assert_eq!(cpu.call_depth(), 1);
+
+            cpu.step_out().unwrap();
+            assert_eq!(cpu.pc, 0x0040_0008);
+            assert_eq!(cpu.get_reg("$v0"), 9);
+
+            // outside of a call it runs to the end
+            assert!(matches!(cpu.step_out(), Err(EmuError::Termination)));
+            assert_eq!(cpu.get_reg("$s0"), 9);
+        }
+
+        #[test]
+        fn step_out_of_recursion_test() {
+            let mut cpu = load(r#"
+                addi $a0, $zero, 2
+                jal down
+                li $v0, 10
+                syscall
+            down:
+                addi $sp, $sp, -4
+                sw $ra, 0($sp)
+                beq $a0, $zero, done
+                addi $a0, $a0, -1
+                jal down
+            done:
+                lw $ra, 0($sp)
+                addi $sp, $sp, 4
+                jr $ra
+            "#);
+            cpu.set_breakpoint(8, Breakpoint::new(Some("$a0 == 0"), 0, None).unwrap());
+
+            assert!(matches!(cpu.run(), Err(EmuError::Breakpoint)));
+            assert_eq!(cpu.call_depth(), 3);
+
+            // each step out leaves one level
+            cpu.step_out().unwrap();
+            assert_eq!(cpu.call_depth(), 2);
+            assert_eq!(cpu.pc, 0x0040_0028);
+            cpu.step_out().unwrap();
+            cpu.step_out().unwrap();
+            assert_eq!(cpu.call_depth(), 0);
+            assert_eq!(cpu.pc, 0x0040_0008);
+        }
+
+        #[test]
+        fn return_through_other_register_test() {
+            let mut cpu = load(r#"
+                jal f
+                li $v0, 10
+                syscall
+            f:
+                addu $t9, $ra, $zero
+                jr $t9
+            "#);
+
+            cpu.next().unwrap();
+            assert_eq!(cpu.call_depth(), 1);
+            cpu.next().unwrap();
+            cpu.next().unwrap();
+            assert_eq!(cpu.call_depth(), 0);
+        }
+
+        #[test]
+        fn run_to_line_test() {
+            let mut cpu = load(SQUARE);
+
+            cpu.run_to_line(8).unwrap();
+            assert_eq!(cpu.pc, 0x0040_0020);
+            assert_eq!(cpu.get_lo(), 9);
+
+            cpu.run_to_line(3).unwrap();
+            assert_eq!(cpu.pc, 0x0040_0008);
+
+            // a line that is never reached runs to the end
+            assert!(matches!(cpu.run_to_line(1), Err(EmuError::Termination)));
+        }
+    }
 }
\ No newline at end of file
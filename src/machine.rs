@@ -0,0 +1,218 @@
+use crate::backend::STACK_SEGMENT_SIZE;
+use crate::console::{BufferedConsole, Console};
+use crate::cpu::CPU;
+use crate::memory::{Memory, DEFAULT_STACK_POINTER};
+use crate::program::{EmuError, Program};
+use crate::shadow::{ShadowMemory, UninitCheck};
+use crate::Snapshot;
+
+/// bytes of stack given to each hart below the default stack pointer
+pub const HART_STACK_SIZE: u32 = 0x1_0000;
+
+/// most harts whose stacks fit in the stack segment
+pub const MAX_HARTS: usize = (STACK_SEGMENT_SIZE / HART_STACK_SIZE) as usize;
+
+/// picks which hart executes the next instruction
+#[derive(Debug, Clone, Copy)]
+pub enum Scheduler {
+    /// each hart runs `quantum` instructions in turn
+    RoundRobin { quantum: u32 },
+
+    /// every instruction goes to a pseudo-randomly chosen hart (the same seed replays the same interleaving)
+    Random { seed: u64 },
+}
+
+/// several CPU harts running the same program on one shared memory and console
+///
+/// every hart starts at the text base with its index in $a0 and its own stack, and a hart that
+/// exits (or runs off the end of the program) stops while the others keep running
+pub struct Machine {
+    pub harts: Vec<CPU>,
+    pub memory: Memory,
+    pub console: Box<dyn Console>,
+    pub scheduler: Scheduler,
+
+    // maximum number of instructions (across all harts) before halting
+    pub max_instructions: u64,
+
+    // hart that executed the last instruction
+    pub current_hart: usize,
+
+    finished: Vec<bool>,
+    quantum_used: u32,
+    rng_state: u64,
+}
+
+impl Machine {
+    /// creates a machine with `hart_count` harts (between 1 and `MAX_HARTS`)
+    pub fn new(hart_count: usize, scheduler: Scheduler) -> Self {
+        let hart_count = hart_count.clamp(1, MAX_HARTS);
+        let harts = (0..hart_count).map(|id| {
+            let mut cpu = CPU::new();
+            cpu.hart_id = id;
+            cpu
+        }).collect();
+
+        let rng_state = match scheduler {
+            Scheduler::Random { seed } => seed,
+            Scheduler::RoundRobin { .. } => 0,
+        };
+
+        Machine {
+            harts,
+            memory: Memory::new(),
+            console: Box::new(BufferedConsole::new()),
+            scheduler,
+            max_instructions: 1_000_000,
+            current_hart: 0,
+            finished: vec![false; hart_count],
+            quantum_used: 0,
+            // xorshift never leaves a zero state
+            rng_state: rng_state.max(1),
+        }
+    }
+
+    /// copies the settings of `template` to every hart and its segment map (permissions and heap limit) to the
+    /// shared memory, they are kept when a program is loaded
+    pub fn configure(&mut self, template: &CPU) {
+        for cpu in self.harts.iter_mut() {
+            cpu.copy_settings(template);
+        }
+        self.memory.segments = template.memory.segments.clone();
+    }
+
+    /// assembles the program into a fresh shared memory and loads it on every hart
+    pub fn load_source(&mut self, source: &str) -> Result<(), EmuError> {
+        // the segment map is configuration, so it survives the reload like in `CPU::reset`
+        let segments = std::mem::take(&mut self.memory.segments);
+        self.memory = Memory::new();
+        self.memory.segments = segments;
+        self.console = Box::new(BufferedConsole::new());
+
+        // the program's .data counts as written when the harts check uninitialized reads
+        if self.harts[0].uninit_check() != UninitCheck::Off {
+            self.memory.shadow = Some(ShadowMemory::default());
+        }
+
+        let program = Program::parse(source, &mut self.memory)?;
+
+        for (id, cpu) in self.harts.iter_mut().enumerate() {
+            // breakpoints are set before the load here (see `configure`), so the reset keeps them
+            let breakpoints = std::mem::take(&mut cpu.breakpoints);
+            cpu.reset();
+            cpu.breakpoints = breakpoints;
+            cpu.hart_id = id;
+            cpu.load_program(program.clone());
+            cpu.set_reg("$a0", id as u32);
+            cpu.set_reg("$sp", DEFAULT_STACK_POINTER - id as u32 * HART_STACK_SIZE);
+        }
+
+        self.finished.fill(false);
+        self.current_hart = 0;
+        self.quantum_used = 0;
+        Ok(())
+    }
+
+    /// returns true once the hart has exited or run past the end of the program
+    pub fn is_finished(&self, hart: usize) -> bool {
+        self.finished[hart]
+    }
+
+    /// executes one instruction on a specific hart (used by the IDE to step harts independently)
+    pub fn step_hart(&mut self, hart: usize) -> Result<(), EmuError> {
+        if self.finished[hart] {
+            return Err(EmuError::Termination);
+        }
+
+        self.current_hart = hart;
+        let result = self.with_shared(hart, |cpu| cpu.next());
+
+        if matches!(result, Err(EmuError::Termination)) {
+            self.finished[hart] = true;
+        }
+        result
+    }
+
+    /// executes one instruction on the hart chosen by the scheduler and returns its index
+    /// `Termination` is only returned once every hart has finished
+    pub fn step(&mut self) -> Result<usize, EmuError> {
+        let hart = self.next_hart().ok_or(EmuError::Termination)?;
+        self.quantum_used += 1;
+
+        match self.step_hart(hart) {
+            Err(EmuError::Termination) if self.finished.contains(&false) => Ok(hart),
+            result => result.map(|_| hart),
+        }
+    }
+
+    /// runs the harts until all of them finish, one of them faults or reaches a breakpoint
+    pub fn run(&mut self) -> Result<(), EmuError> {
+        let mut instruction_count: u64 = 0;
+
+        loop {
+            if self.max_instructions > 0 && instruction_count >= self.max_instructions {
+                return Err(EmuError::ExecutionLimitExceeded(instruction_count));
+            }
+
+            let hart = match self.step() {
+                Ok(hart) => hart,
+                Err(EmuError::Termination) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            instruction_count += 1;
+
+            if !self.finished[hart] && self.with_shared(hart, |cpu| cpu.breakpoint_reached()) {
+                return Err(EmuError::Breakpoint);
+            }
+        }
+    }
+
+    /// returns the snapshot of a hart (with the shared memory's devices)
+    pub fn snapshot(&mut self, hart: usize) -> Snapshot {
+        self.with_shared(hart, |cpu| cpu.snapshot())
+    }
+
+    /// lends the shared memory and console to a hart while `f` runs
+    pub fn with_shared<T>(&mut self, hart: usize, f: impl FnOnce(&mut CPU) -> T) -> T {
+        let cpu = &mut self.harts[hart];
+        std::mem::swap(&mut cpu.memory, &mut self.memory);
+        std::mem::swap(&mut cpu.console, &mut self.console);
+
+        let result = f(cpu);
+
+        std::mem::swap(&mut cpu.memory, &mut self.memory);
+        std::mem::swap(&mut cpu.console, &mut self.console);
+        result
+    }
+
+    /// picks the hart for the next instruction (`None` once every hart has finished)
+    fn next_hart(&mut self) -> Option<usize> {
+        let running: Vec<usize> = (0..self.harts.len()).filter(|h| !self.finished[*h]).collect();
+        if running.is_empty() {
+            return None;
+        }
+
+        match self.scheduler {
+            Scheduler::RoundRobin { quantum } => {
+                if self.finished[self.current_hart] || self.quantum_used >= quantum.max(1) {
+                    self.quantum_used = 0;
+
+                    // the first running hart after the current one, wrapping around
+                    let next = running.iter().find(|h| **h > self.current_hart).unwrap_or(&running[0]);
+                    self.current_hart = *next;
+                }
+                Some(self.current_hart)
+            },
+
+            Scheduler::Random { .. } => {
+                // xorshift64
+                self.rng_state ^= self.rng_state << 13;
+                self.rng_state ^= self.rng_state >> 7;
+                self.rng_state ^= self.rng_state << 17;
+
+                Some(running[(self.rng_state % running.len() as u64) as usize])
+            },
+        }
+    }
+}
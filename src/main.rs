@@ -1,6 +1,9 @@
 use std::io::{self, Read};
 use std::collections::HashMap;
+use mips_emu_wasm::console::BufferedConsole;
 use mips_emu_wasm::cpu::CPU;
+use mips_emu_wasm::shadow::UninitCheck;
+use mips_emu_wasm::TrapReport;
 use serde::{Serialize, Deserialize};
 
 /// receive input from the Python autograder from stdin
@@ -18,6 +21,26 @@ struct GraderInput {
     /// memory addresses to read back after execution (for memory test cases)
     #[serde(default)]
     check_memory: Vec<u64>,
+
+    /// scripted console input consumed by the read syscalls
+    #[serde(default)]
+    stdin: String,
+
+    /// emulate branch delay slots (lab setting)
+    #[serde(default)]
+    delayed_branching: bool,
+
+    /// fault on accesses outside the text, data, heap, stack, kernel and MMIO segments (lab setting)
+    #[serde(default)]
+    check_segments: bool,
+
+    /// fault on heap accesses above the program break (lab setting)
+    #[serde(default)]
+    check_heap: bool,
+
+    /// stop on reads of registers and memory the program never wrote (lab setting)
+    #[serde(default)]
+    check_uninitialized: bool,
 }
 
 /// output format sent back to the Python autograder via stdout
@@ -26,6 +49,12 @@ struct GraderOutput {
     registers: HashMap<String, i64>,
     memory: HashMap<String, i64>,
     error: String,
+
+    /// console output produced by the print syscalls
+    stdout: String,
+
+    /// the trap or `break` that stopped the program (a failed self-check in the student's code)
+    trap: Option<TrapReport>,
 }
 
 fn main() {
@@ -44,6 +73,14 @@ fn main() {
     };
 
     let mut cpu = CPU::new();
+    cpu.console = Box::new(BufferedConsole::with_input(&input.stdin));
+    cpu.delayed_branching = input.delayed_branching;
+    cpu.check_segments = input.check_segments;
+    cpu.check_heap = input.check_heap;
+    if input.check_uninitialized {
+        // before the initial values, which count as written
+        cpu.set_uninit_check(UninitCheck::Strict);
+    }
 
     // initial register values from autograder
     for (reg, val) in &input.initial_registers {
@@ -65,7 +102,8 @@ fn main() {
     // run the code from the student received from autograder
     if let Err(e) = cpu.run_input(&input.source_code) {
         // ExecutionLimitExceeded and other fatal errors
-        let err_msg = format!("{:?}", e);
+        let err_msg = cpu.error_message(&e);
+        let trap = TrapReport::from_error(&e);
 
         let registers = collect_registers(&cpu);
         let memory = collect_memory(&mut cpu, &input.check_memory);
@@ -74,6 +112,8 @@ fn main() {
             registers,
             memory,
             error: err_msg,
+            stdout: cpu.console.take_output(),
+            trap,
         };
 
         println!("{}", serde_json::to_string(&output).unwrap());
@@ -88,6 +128,8 @@ fn main() {
         registers,
         memory,
         error: String::new(),
+        stdout: cpu.console.take_output(),
+        trap: None,
     };
 
     println!("{}", serde_json::to_string(&output).unwrap());
@@ -127,6 +169,8 @@ fn print_error(msg: &str) {
         registers: HashMap::new(),
         memory: HashMap::new(),
         error: msg.to_string(),
+        stdout: String::new(),
+        trap: None,
     };
     println!("{}", serde_json::to_string(&output).unwrap());
 }
\ No newline at end of file
@@ -1,5 +1,8 @@
-use std::collections::HashMap;
-use crate::mmio::{MmioBus, LedDevice, IoDevice}; 
+use crate::backend::{ContiguousBackend, MemoryBackend};
+use crate::segments::SegmentMap;
+use crate::shadow::{ByteState, ShadowMemory};
+use crate::mmio::{MmioBus, LedDevice, TimerDevice}; 
+use serde::Serialize;
 
 // Constants
 pub const DOUBLE_SIZE: usize = 8;          
@@ -19,238 +22,327 @@ pub const DEFAULT_STACK_POINTER: u32 = 0x7FFF_FFFC;
 pub const DEFAULT_TEXT_BASE_ADDRESS: u32 = 0x0040_0000;
 pub const DEFAULT_STATIC_DATA_BASE_ADDRESS: u32 = 0x1000_0000;
 pub const DEFAULT_HEAP_BASE_ADDRESS: u32 = 0x1000_8000;
+pub const EXCEPTION_HANDLER_ADDRESS: u32 = 0x8000_0180;
+
+/// a block handed out by `sbrk`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeapAllocation {
+    pub address: u32,
+    pub size: u32,          // bytes reserved (the request rounded up to a word)
+    pub requested: u32,     // bytes the program asked for
+    pub line: usize,        // source line of the `syscall`
+}
 
-#[inline]
-fn page_index(addr: u32) -> u32 { addr >> PAGE_POWER }
-
-#[inline]
-fn page_offset(addr: u32) -> usize { (addr & PAGE_MASK) as usize }
+/// state of the heap for the IDE's heap view
+#[derive(Debug, Clone, Serialize)]
+pub struct HeapView {
+    pub base: u32,
+    #[serde(rename = "break")]
+    pub heap_break: u32,
+    pub limit: u32,
+    pub allocations: Vec<HeapAllocation>,
+}
 
 pub struct Memory {
-    pub pages: HashMap<u32, Box<[i8; PAGE_SIZE]>>,
-    pub snapshot: Option<HashMap<u32, Box<[i8; PAGE_SIZE]>>>, // Holds "frozen" input state
+    pub backend: Box<dyn MemoryBackend>,
+    pub snapshot: Option<Box<dyn MemoryBackend>>,          // Holds "frozen" input state
     pub isolation_active: bool,                             // Toggle for autograder mode
     pub mmio: MmioBus, 
+    pub heap_break: u32,                                    // Current program break moved by sbrk
+    pub allocations: Vec<HeapAllocation>,                   // Blocks below the break, in address order
+    pub reservations: Vec<(usize, u32)>,                    // (hart, word address) reserved by `ll`
+    pub segments: SegmentMap,                               // Permissions the CPU checks before each access
+    pub shadow: Option<ShadowMemory>,                       // Written bytes, when uninitialized reads are checked
+    pub journal: Option<Vec<(u32, u8)>>,                    // (address, old byte) of each store, while the CPU records history
 }
 
 impl Memory {
     pub fn new() -> Self {        
-        let mut pages = HashMap::new();
-
-        // standard pages
-        pages.insert((DEFAULT_STACK_BASE_ADDRESS - PAGE_SIZE as u32) >> PAGE_POWER, Box::new([0; PAGE_SIZE]));
-        pages.insert(DEFAULT_HEAP_BASE_ADDRESS >> PAGE_POWER, Box::new([0; PAGE_SIZE]));
-        pages.insert(DEFAULT_STATIC_DATA_BASE_ADDRESS >> PAGE_POWER, Box::new([0; PAGE_SIZE]));
-        pages.insert(DEFAULT_TEXT_BASE_ADDRESS >> PAGE_POWER, Box::new([0; PAGE_SIZE]));
+        Self::with_backend(Box::new(ContiguousBackend::new()))
+    }
 
+    /// creates memory over any backend (e.g. `PagedBackend` for a sparse address space)
+    pub fn with_backend(backend: Box<dyn MemoryBackend>) -> Self {
         // MMIO bus
         let mut bus = MmioBus::new();
         
         // Address: 0xFFFF0000, Size: 8 bytes (two registers)
         bus.register(0xFFFF_0000, 8, Box::new(LedDevice::new()));
+
+        // Address: 0xFFFF0010, Size: 8 bytes (count, compare)
+        bus.register(0xFFFF_0010, 8, Box::new(TimerDevice::new()));
         
         Memory {
-            pages,
+            backend,
             snapshot: None,         // Initialize as None
             isolation_active: false, // Default to standard hardware mode
             mmio: bus,
+            heap_break: DEFAULT_HEAP_BASE_ADDRESS,
+            allocations: Vec::new(),
+            reservations: Vec::new(),
+            segments: SegmentMap::new(),
+            shadow: None,
+            journal: None,
         }
     }
 
-    pub fn set_word(&mut self, address: u32, value: i32) {
-        if address >= MMIO_START {
-            self.mmio.store(address, value as u32);
-            return; 
-        }
-        let bytes = value.to_le_bytes();        
-        for i in 0..WORD_SIZE {                 
-            self.set_byte(address + i as u32, bytes[i] as i8);
-        }
+    /// records the word reserved by `ll` on a hart (replacing any previous reservation of that hart)
+    pub fn reserve(&mut self, hart: usize, address: u32) {
+        self.clear_reservation(hart);
+        self.reservations.push((hart, address & !3));
     }
 
-    pub fn load_word(&mut self, address: u32) -> i32 {
-        if address >= MMIO_START { return self.mmio.load(address) as i32; }
-        let mut bytes = [0u8; WORD_SIZE];           
-        for i in 0..WORD_SIZE {                         
-            bytes[i] = self.load_byte(address + i as u32) as u8;
-        }
-        i32::from_le_bytes(bytes)             
+    /// returns true if the hart still holds its reservation on the word at `address` and releases it (used by `sc`)
+    pub fn take_reservation(&mut self, hart: usize, address: u32) -> bool {
+        let held = self.reservations.contains(&(hart, address & !3));
+        self.clear_reservation(hart);
+        held
     }
 
-    pub fn set_byte(&mut self, address: u32, value: i8) {
-        if address >= MMIO_START {
-             let aligned = address & !3;
-             let shift = (address & 3) * 8;
-             let current = self.mmio.load(aligned);
-             let mask = !(0xFF << shift);
-             let new_val = (current & mask) | ((value as u32 & 0xFF) << shift);
-
-             self.mmio.store(aligned, new_val);
-             return;
+    pub fn clear_reservation(&mut self, hart: usize) {
+        self.reservations.retain(|(h, _)| *h != hart);
+    }
+
+    /// a store to a reserved word makes the next `sc` to it fail on every hart
+    fn invalidate_reservations(&mut self, address: u32, size: u32) {
+        if self.reservations.is_empty() {
+            return;
         }
 
-        let page = page_index(address);       
-        let offset = page_offset(address);  
+        let first = address & !3;
+        let last = address.wrapping_add(size - 1) & !3;
+        self.reservations.retain(|(_, word)| *word < first || *word > last);
+    }
 
-        if !self.pages.contains_key(&page) {                   
-            self.pages.insert(page, Box::new([0; PAGE_SIZE]));
+    /// backend a load at `address` reads from (the frozen inputs while isolation is active)
+    #[inline]
+    fn source(&self, address: u32) -> &dyn MemoryBackend {
+        match &self.snapshot {
+            Some(snapshot) if self.isolation_active && snapshot.is_mapped(address) => snapshot.as_ref(),
+            _ => self.backend.as_ref(),
         }
-
-        let page_data = self.pages.get_mut(&page).unwrap();     
-        page_data[offset] = value;
     }
 
-    pub fn load_byte(&mut self, address: u32) -> i8 {
+    /// reads the bytes starting at `address`, one MMIO word at a time for device addresses
+    fn read_bytes(&mut self, address: u32, buf: &mut [u8]) {
         if address >= MMIO_START {
-            let aligned = address & !3;
-            let word = self.mmio.load(aligned);
-            let shift = (address & 3) * 8;
-            return ((word >> shift) & 0xFF) as i8;
+            for (i, b) in buf.iter_mut().enumerate() {
+                let addr = address.wrapping_add(i as u32);
+                *b = (self.mmio.load(addr & !3) >> ((addr & 3) * 8)) as u8;
+            }
+            return;
         }
 
-        let page = page_index(address);        
-        let offset = page_offset(address);   
+        self.source(address).read(address, buf);
+    }
 
-        // If isolation is active and a snapshot exists, read from the "original" data
-        if self.isolation_active {
-            if let Some(snap) = &self.snapshot {
-                if let Some(page_data) = snap.get(&page) {
-                    return page_data[offset];
-                }
+    /// writes the bytes starting at `address`; partial MMIO words are merged into the device's current value
+    fn write_bytes(&mut self, address: u32, bytes: &[u8]) {
+        if address >= MMIO_START {
+            for (i, b) in bytes.iter().enumerate() {
+                let addr = address.wrapping_add(i as u32);
+                let aligned = addr & !3;
+                let shift = (addr & 3) * 8;
+
+                let current = self.mmio.load(aligned);
+                self.mmio.store(aligned, (current & !(0xFF << shift)) | ((*b as u32) << shift));
             }
+            return;
         }
 
-        if let Some(page_data) = self.pages.get(&page) {    
-            page_data[offset]
-        } else {
-            0
-        }
+        self.invalidate_reservations(address, bytes.len() as u32);
+        self.mark_initialized(address, bytes.len() as u32);
+        self.record_old_bytes(address, bytes.len() as u32);
+        self.backend.write(address, bytes);
     }
 
-    pub fn set_halfword(&mut self, address: u32, value: i16) {
+    pub fn set_word(&mut self, address: u32, value: i32) {
         if address >= MMIO_START {
-             // we just map it to word for now to prevent crashing.
-             self.mmio.store(address & !3, value as u32);
-             return;
+            self.mmio.store(address, value as u32);
+            return; 
         }
 
-        let page = page_index(address);
-        let offset = page_offset(address);
+        self.invalidate_reservations(address, WORD_SIZE as u32);
+        self.mark_initialized(address, WORD_SIZE as u32);
+        self.record_old_bytes(address, WORD_SIZE as u32);
+        self.backend.write_word(address, value as u32);
+    }
 
-        if !self.pages.contains_key(&page) {                   
-            self.pages.insert(page, Box::new([0; PAGE_SIZE]));
-        }
+    pub fn load_word(&mut self, address: u32) -> i32 {
+        if address >= MMIO_START { return self.mmio.load(address) as i32; }
+        self.source(address).read_word(address) as i32
+    }
 
-        let page_data = self.pages.get_mut(&page).unwrap();     
+    pub fn set_byte(&mut self, address: u32, value: i8) {
+        self.write_bytes(address, &[value as u8]);
+    }
 
-        let bytes = value.to_le_bytes();        
-        for i in 0..HALF_SIZE {                 
-            page_data[offset + i] = bytes[i] as i8;
-        }
+    pub fn load_byte(&mut self, address: u32) -> i8 {
+        let mut bytes = [0; 1];
+        self.read_bytes(address, &mut bytes);
+        bytes[0] as i8
+    }
+
+    pub fn set_halfword(&mut self, address: u32, value: i16) {
+        self.write_bytes(address, &value.to_le_bytes());
     }
 
     pub fn load_halfword(&mut self, address: u32) -> i16 {
-        if address >= MMIO_START {
-            return self.mmio.load(address & !3) as i16;
-        }
+        let mut bytes = [0; HALF_SIZE];
+        self.read_bytes(address, &mut bytes);
+        i16::from_le_bytes(bytes)
+    }
 
-        let page = page_index(address);        
-        let offset = page_offset(address);   
+    pub fn set_double(&mut self, address: u32, value: f64) {
+        self.write_bytes(address, &value.to_le_bytes());
+    }
 
-        if let Some(page_data) = self.pages.get(&page) {    
-            let mut bytes = [0 as u8; HALF_SIZE];           
-            for i in 0..HALF_SIZE {                         
-                bytes[i] = page_data[offset + i] as u8;
-            }
-            i16::from_le_bytes(bytes)             
-        } else {
-            0
-        }
+    pub fn load_double(&mut self, address: u32) -> f64 {
+        let mut bytes = [0; DOUBLE_SIZE];
+        self.read_bytes(address, &mut bytes);
+        f64::from_le_bytes(bytes)
     }
 
-    pub fn set_double(&mut self, address: u32, value: f64) {
-        let page = page_index(address);       
-        let offset = page_offset(address);   
+    pub fn set_float(&mut self, address: u32, value: f32) {
+        self.set_word(address, value.to_bits() as i32);
+    }
 
-        if !self.pages.contains_key(&page) {                   
-            self.pages.insert(page, Box::new([0; PAGE_SIZE]));
-        }
+    pub fn load_float(&mut self, address: u32) -> f32 {
+        f32::from_bits(self.load_word(address) as u32)
+    }
 
-        let page_data = self.pages.get_mut(&page).unwrap();     
-        let bytes = value.to_le_bytes();
-        for i in 0..DOUBLE_SIZE {
-            page_data[offset + i] = bytes[i] as i8;
+    pub fn set_string(&mut self, address: u32, value: &str) {
+        if address >= MMIO_START { return; } 
+
+        let string_bytes = value.as_bytes();
+        for (i, &byte) in string_bytes.iter().enumerate() {
+            self.set_byte(address + i as u32, byte as i8);
         }
     }
 
-    pub fn load_double(&mut self, address: u32) -> f64 {
-        let page = page_index(address);
-        let offset = page_offset(address);
+    /// records bytes as holding a value (writes do this themselves, `.space` uses it for its zero fill)
+    #[inline]
+    pub fn mark_initialized(&mut self, address: u32, len: u32) {
+        if let Some(shadow) = self.shadow.as_mut() {
+            shadow.mark(address, len, ByteState::Defined);
+        }
+    }
 
-        if let Some(page_data) = self.pages.get(&page) {    
-            let mut bytes = [0 as u8; DOUBLE_SIZE];
-            for i in 0..DOUBLE_SIZE {
-                bytes[i] = page_data[offset + i] as u8;
+    /// saves the bytes a store is about to overwrite so the instruction can be undone
+    #[inline]
+    fn record_old_bytes(&mut self, address: u32, len: u32) {
+        if let Some(journal) = self.journal.as_mut() {
+            for i in 0..len {
+                let addr = address.wrapping_add(i);
+                let mut byte = [0; 1];
+                self.backend.read(addr, &mut byte);
+                journal.push((addr, byte[0]));
             }
-            f64::from_le_bytes(bytes)
-        } else {
-            0.0
         }
     }
 
-    pub fn set_float(&mut self, address: u32, value: f32) {
-        let page = page_index(address);       
-        let offset = page_offset(address);   
+    /// moves the program break by `bytes` (rounded up to a word) and returns the previous break
+    /// growing past the end of the heap segment or shrinking below its base fails with the number of bytes
+    /// the break could still move that way; growth is recorded as an allocation made on `line`
+    pub fn sbrk(&mut self, bytes: i32, line: usize) -> Result<u32, u32> {
+        let old_break = self.heap_break;
+        let aligned = (bytes as i64 + 3) & !3;
+
+        if aligned < 0 {
+            let in_use = old_break - self.heap_base();
+            if aligned.unsigned_abs() > in_use as u64 {
+                return Err(in_use);
+            }
+
+            // release the blocks (or the parts of them) above the new break
+            self.heap_break = old_break - aligned.unsigned_abs() as u32;
+            let new_break = self.heap_break;
+            self.allocations.retain(|a| a.address < new_break);
+            if let Some(last) = self.allocations.last_mut() {
+                last.size = last.size.min(new_break - last.address);
+                last.requested = last.requested.min(last.size);
+            }
+            return Ok(old_break);
+        }
 
-        if !self.pages.contains_key(&page) {                   
-            self.pages.insert(page, Box::new([0; PAGE_SIZE]));
+        let available = self.heap_limit().saturating_sub(old_break);
+        if aligned as u64 > available as u64 {
+            return Err(available);
         }
 
-        let page_data = self.pages.get_mut(&page).unwrap();     
-        let bytes = value.to_le_bytes();
-        for i in 0..FLOAT_SIZE {
-            page_data[offset + i] = bytes[i] as i8;
+        self.heap_break = old_break + aligned as u32;
+        if aligned > 0 {
+            self.allocations.push(HeapAllocation { address: old_break, size: aligned as u32, requested: bytes as u32, line });
         }
+        Ok(old_break)
     }
 
-    pub fn load_float(&mut self, address: u32) -> f32 {
-        let page = page_index(address);       
-        let offset = page_offset(address);   
+    /// first address of the heap (the start of the "heap" segment)
+    pub fn heap_base(&self) -> u32 {
+        self.segments.get("heap").map_or(DEFAULT_HEAP_BASE_ADDRESS, |heap| heap.start)
+    }
 
-        if let Some(page_data) = self.pages.get(&page) {    
-            let mut bytes = [0 as u8; FLOAT_SIZE];
-            for i in 0..FLOAT_SIZE {
-                bytes[i] = page_data[offset + i] as u8;
-            }
-            f32::from_le_bytes(bytes)
-        } else {
-            0.0
-        }
+    /// starts the heap and the program break at the word-aligned end of static data (`DEFAULT_HEAP_BASE_ADDRESS`
+    /// if the data ends below it), growing the data segment to meet it; the heap keeps its size
+    pub fn place_heap(&mut self, data_end: u32) {
+        let base = (data_end.saturating_add(3) & !3).max(DEFAULT_HEAP_BASE_ADDRESS);
+        let size = self.heap_limit() - self.heap_base();
+
+        self.segments.resize("data", base - 1);
+        self.segments.set_range("heap", base, base.saturating_add(size) - 1);
+        self.heap_break = base;
     }
 
-    pub fn set_string(&mut self, address: u32, value: &str) {
-        if address >= MMIO_START { return; } 
+    /// first address past the heap (the end of the "heap" segment)
+    pub fn heap_limit(&self) -> u32 {
+        self.segments.get("heap").map_or(DEFAULT_HEAP_BASE_ADDRESS, |heap| heap.last.saturating_add(1))
+    }
 
-        let string_bytes = value.as_bytes();
-        for (i, &byte) in string_bytes.iter().enumerate() {
-            self.set_byte(address + i as u32, byte as i8);
+    /// limits the heap to `bytes` (at least a word) from its base by resizing the "heap" segment
+    pub fn set_heap_limit(&mut self, bytes: u32) {
+        let last = self.heap_base().saturating_add(bytes.max(WORD_SIZE as u32)) - 1;
+        self.segments.resize("heap", last);
+    }
+
+    /// returns true if the heap segment holds `address` but the break has not reached it yet
+    pub fn is_unallocated_heap(&self, address: u32) -> bool {
+        address >= self.heap_break && address < self.heap_limit()
+    }
+
+    pub fn heap_view(&self) -> HeapView {
+        HeapView {
+            base: self.heap_base(),
+            heap_break: self.heap_break,
+            limit: self.heap_limit(),
+            allocations: self.allocations.clone(),
         }
     }
 
-    pub fn get_memory_slice(&mut self, start_address: u32, length: usize) -> Vec<u8> {
-        let mut result = Vec::with_capacity(length);
+    /// reads a null-terminated string starting at `address`
+    pub fn load_string(&mut self, address: u32) -> String {
+        let mut bytes = Vec::new();
+        let mut addr = address;
 
-        for i in 0..length {
-            // use load byte for MMIO
-            let val = self.load_byte(start_address + i as u32);
-            result.push(val as u8);
+        loop {
+            let b = self.load_byte(addr) as u8;
+            if b == 0 {
+                break;
+            }
+            bytes.push(b);
+            addr = addr.wrapping_add(1);
         }
-        
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    pub fn get_memory_slice(&mut self, start_address: u32, length: usize) -> Vec<u8> {
+        let mut result = vec![0; length];
+        self.read_bytes(start_address, &mut result);
         result
     }
 
     pub fn freeze_inputs(&mut self) {
-        self.snapshot = Some(self.pages.clone());
+        self.snapshot = Some(self.backend.clone_box());
         self.isolation_active = true;
     }
 
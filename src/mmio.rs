@@ -11,6 +11,12 @@ pub trait IoDevice: Send + Sync {
 
     /// provides a representation of state for the frontend
     fn get_state(&self) -> DeviceState;
+
+    /// advances the device by one instruction cycle
+    fn tick(&mut self) {}
+
+    /// hardware interrupt line (0-5) the device is currently asserting, if any
+    fn interrupt(&self) -> Option<u32> { None }
 }
 
 /// serialized state for the device peripherals supported
@@ -19,6 +25,7 @@ pub trait IoDevice: Send + Sync {
 #[serde(tag = "type", content = "data")] 
 pub enum DeviceState {
     Led { value: u32, color: u32 },
+    Timer { count: u32, compare: u32, pending: bool },
 }
 
 /// represents a generic LED
@@ -55,6 +62,61 @@ impl IoDevice for LedDevice {
     }
 }
 
+/// represents a cycle timer modelled on the CP0 Count/Compare pair
+/// offset 0 is the count (incremented every instruction), offset 4 is the compare value
+/// the timer interrupts when count reaches compare (0 disables it), writing compare acknowledges
+#[derive(Default)]
+pub struct TimerDevice {
+    pub count: u32,
+    pub compare: u32,
+    pub pending: bool,
+}
+
+/// interrupt line used by the timer (Cause IP7, as on real MIPS hardware)
+pub const TIMER_INTERRUPT_LINE: u32 = 5;
+
+impl TimerDevice {
+    pub fn new() -> Self { Self { count: 0, compare: 0, pending: false } }
+}
+
+impl IoDevice for TimerDevice {
+    fn read(&mut self, offset: u32) -> u32 {
+        match offset {
+            0 => self.count,
+            4 => self.compare,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u32) {
+        match offset {
+            0 => self.count = value,
+            4 => {
+                self.compare = value;
+                self.pending = false;
+            },
+            _ => {}
+        }
+    }
+
+    fn name(&self) -> &'static str { "Timer" }
+
+    fn get_state(&self) -> DeviceState {
+        DeviceState::Timer { count: self.count, compare: self.compare, pending: self.pending }
+    }
+
+    fn tick(&mut self) {
+        self.count = self.count.wrapping_add(1);
+        if self.compare != 0 && self.count == self.compare {
+            self.pending = true;
+        }
+    }
+
+    fn interrupt(&self) -> Option<u32> {
+        if self.pending { Some(TIMER_INTERRUPT_LINE) } else { None }
+    }
+}
+
 /// represents a MMIO controller that will route memory accesses to the corresponding device
 pub struct MmioBus {
     // (Start Address, End Address, Device)
@@ -94,6 +156,20 @@ impl MmioBus {
         }
     }
 
+    /// advances every device by one instruction cycle
+    pub fn tick(&mut self) {
+        for (_, _, device) in &mut self.devices {
+            device.tick();
+        }
+    }
+
+    /// bitmask of the hardware interrupt lines asserted by devices (bit n = line n)
+    pub fn pending_interrupts(&self) -> u32 {
+        self.devices.iter()
+            .filter_map(|(_, _, device)| device.interrupt())
+            .fold(0, |mask, line| mask | (1 << line))
+    }
+
     /// generate mapping for the UI
     pub fn snapshot(&self) -> HashMap<u32, DeviceState> {
         let mut map = HashMap::new();
@@ -3,6 +3,7 @@ use crate::lexer::{Lexer, Token, TokenType};
 use crate::instruction::Instruction;
 use crate::instruction::CoreInstruction;
 use crate::instruction::PseudoInstruction;
+use crate::instruction::{FpCondition, FpFormat, FpRegister, Register, TrapCondition};
 use crate::program::EmuError;
 use crate::memory::Memory;
 use std::collections::{HashMap, VecDeque};
@@ -16,6 +17,12 @@ pub struct Label {
 pub enum ProgramStatement {
     Instruction(Instruction),
     Label(Label),
+
+    /// following statements belong to the user text segment (`.text`)
+    UserText,
+
+    /// following statements belong to the kernel text segment (`.ktext`)
+    KernelText,
 }
 
 #[derive(Clone)]
@@ -38,6 +45,11 @@ pub struct Parser {
     current_line: usize,
     instruction_index: u32,
     tokens: VecDeque<Token>,
+
+    // base address of the kernel text segment (set by `.ktext <address>`)
+    pub kernel_text_base: u32,
+    in_kernel_text: bool,
+    kernel_text_used: bool,
 }
 
 impl Parser {
@@ -54,6 +66,9 @@ impl Parser {
             tokens: VecDeque::new(),
             section: Section::Text,
             data_section_pointer: crate::memory::DEFAULT_STATIC_DATA_BASE_ADDRESS,
+            kernel_text_base: crate::memory::EXCEPTION_HANDLER_ADDRESS,
+            in_kernel_text: false,
+            kernel_text_used: false,
         }
     }
 
@@ -87,11 +102,28 @@ impl Parser {
                 ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Jal { label })) |
                 ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Beq { label, .. })) |
                 ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bne { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bgez { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bgtz { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Blez { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bltz { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bgezal { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bltzal { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bc1t { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Core(CoreInstruction::Bc1f { label, .. })) |
                 ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::La { label, .. })) |
                 ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Blt { label, .. })) |
                 ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bgt { label, .. })) |
                 ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Ble { label, .. })) |
-                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bge { label, .. })) => {
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bge { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bltu { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bgtu { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bleu { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bgeu { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Beqz { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bnez { label, .. })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::B { label })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::Bal { label })) |
+                ProgramStatement::Instruction(Instruction::Pseudo(PseudoInstruction::BranchImm { label, .. })) => {
                     if !self.symbol_table.contains_key(label) {
                         return Err(EmuError::UndefinedLabel(label.clone()));
                     }
@@ -100,6 +132,9 @@ impl Parser {
             }
         }
 
+        // the heap starts after the static data
+        memory.place_heap(self.data_section_pointer);
+
         Ok((self.program_statements.clone(), self.symbol_table.clone(), self.line_numbers.clone()))
     }
 
@@ -110,6 +145,10 @@ impl Parser {
                 self.section = Section::Data;
             } else if token.token_type == TokenType::Directive && token.lexeme == ".text" {
                 self.section = Section::Text;
+                self.in_kernel_text = false;
+                self.program_statements.push(ProgramStatement::UserText);
+            } else if token.token_type == TokenType::Directive && token.lexeme == ".ktext" {
+                self.parse_ktext()?;
             } else {
                 match self.section {
                     Section::Data => self.parse_data(memory)?,
@@ -120,6 +159,30 @@ impl Parser {
         Ok(())
     }
 
+    /// handles `.ktext [address]`, the address defaults to the exception handler (0x80000180)
+    fn parse_ktext(&mut self) -> Result<(), EmuError> {
+        self.expect(TokenType::Directive)?;
+
+        if self.peek(0).is_some_and(|t| t.token_type == TokenType::Integer) {
+            let token = self.expect(TokenType::Integer)?;
+            let address = Self::parse_int_literal(&token.lexeme)
+                .and_then(|v| u32::try_from(v).ok())
+                .filter(|v| v % 4 == 0)
+                .ok_or_else(|| self.error(format!("Line {}: Invalid .ktext address {}", self.current_line, token.lexeme)))?;
+
+            // the kernel segment is laid out contiguously from a single base address
+            if self.kernel_text_used && address != self.kernel_text_base {
+                return Err(self.error(format!("Line {}: .ktext address must be given before any kernel instructions", self.current_line)));
+            }
+            self.kernel_text_base = address;
+        }
+
+        self.section = Section::Text;
+        self.in_kernel_text = true;
+        self.program_statements.push(ProgramStatement::KernelText);
+        Ok(())
+    }
+
     pub fn parse_data(&mut self, memory: &mut Memory) -> Result<(), EmuError> {
         // alert("Parsing data section");
 
@@ -180,26 +243,26 @@ impl Parser {
 
                     match directive {
                         ".byte" => {
-                            if value.lexeme.parse::<i8>().is_err() {
+                            let Some(byte) = Self::parse_data_value(&value.lexeme, 8) else {
                                 return Err(self.error(format!("At line {}: Value {} is out of range for .byte", line_num, value.lexeme)));
-                            }
-                            memory.set_byte(address, value.lexeme.parse::<i8>().unwrap());
+                            };
+                            memory.set_byte(address, byte as i8);
 
                             self.data_section_pointer += 1;
                         },
                         ".half" => {
-                            if value.lexeme.parse::<i16>().is_err() {
+                            let Some(half) = Self::parse_data_value(&value.lexeme, 16) else {
                                 return Err(self.error(format!("At line {}: Value {} is out of range for .half", line_num, value.lexeme)));
-                            }
-                            memory.set_halfword(address, value.lexeme.parse::<i16>().unwrap());
+                            };
+                            memory.set_halfword(address, half as i16);
 
                             self.data_section_pointer += 2;
                         },
                         ".word" => {
-                            if value.lexeme.parse::<i32>().is_err() {
+                            let Some(word) = Self::parse_data_value(&value.lexeme, 32) else {
                                 return Err(self.error(format!("At line {}: Value {} is out of range for .word", line_num, value.lexeme)));
-                            }
-                            memory.set_word(address, value.lexeme.parse::<i32>().unwrap());
+                            };
+                            memory.set_word(address, word as i32);
 
                             self.data_section_pointer += 4;
                         },
@@ -277,23 +340,27 @@ impl Parser {
                         return Err(self.error(format!("At line {}: Value {} is out of range for .space", line_num, value.lexeme)));
                     }
 
-                    self.data_section_pointer += value.lexeme.parse::<u32>().unwrap();
+                    // the reserved bytes are zero filled, so reading them is not an uninitialized read
+                    let size = value.lexeme.parse::<u32>().unwrap();
+                    memory.mark_initialized(self.data_section_pointer, size);
+                    self.data_section_pointer += size;
                 },
                 ".ascii" => {
                     let value = self.expect(TokenType::QuotedString)?;
                     // alert(format!("Parsed .ascii value: {}", value.lexeme).as_str());
 
                     let address = self.data_section_pointer;
+                    let lexeme = Self::unescape_string(&value.lexeme);
 
-                    memory.set_string(address, value.lexeme.as_str());
+                    memory.set_string(address, lexeme.as_str());
 
-                    self.data_section_pointer += value.lexeme.len() as u32;
+                    self.data_section_pointer += lexeme.len() as u32;
                 },
                 ".asciiz" => {
                     let value = self.expect(TokenType::QuotedString)?;
                     // alert(format!("Parsed .asciiz value: {}", value.lexeme).as_str());
 
-                    let mut lexeme = value.lexeme.clone();
+                    let mut lexeme = Self::unescape_string(&value.lexeme);
                     let address = self.data_section_pointer;
 
                     lexeme.push('\0');
@@ -339,6 +406,7 @@ impl Parser {
                 self.program_statements.push(ProgramStatement::Instruction(insn));
                 self.line_numbers.push(self.current_line);
                 self.instruction_index += 1;
+                self.kernel_text_used |= self.in_kernel_text;
             } else if token.token_type != TokenType::Comment {
                 // If it's not an instruction, a label, or a comment, it's an error
                 return Err(self.error(format!("At line {}: Unexpected token {:?}", self.current_line, token.lexeme)));
@@ -355,10 +423,23 @@ impl Parser {
 
             // match the instruction by lexeme to the right parsing fn
             match lexeme.as_str() {
-                "add" | "sub" | "or" | "addu" | "subu" | "and" | "slt" | "sltu" | "mult" | "multu" | "mflo" | "mfhi" | "xor" | "div" | "divu" | "nor" | "sll" | "srl" | "sra" => self.parse_r_type(&lexeme),
-                "j" | "jal" | "jr" => self.parse_j_type(&lexeme),
+                "add" | "sub" | "or" | "addu" | "subu" | "and" | "slt" | "sltu" | "mult" | "multu" | "mflo" | "mfhi" | "xor" | "div" | "divu" | "nor" | "sll" | "srl" | "sra" | "syscall" | "nop" => self.parse_r_type(&lexeme),
+                "sllv" | "srlv" | "srav" | "sync" => self.parse_r_type(&lexeme),
+                "mul" | "madd" | "maddu" | "msub" | "msubu" | "clz" | "clo" | "movn" | "movz" | "seb" | "seh" | "ext" | "ins" => self.parse_r_type(&lexeme),
+                "j" | "jal" | "jr" | "jalr" => self.parse_j_type(&lexeme),
                 "addi" | "addiu" | "lb" | "sb" | "lh" | "sh" | "lw" | "sw" | "ori" | "beq" | "bne" | "andi"| "slti" | "sltiu"| "xori" | "lui" => self.parse_i_type(&lexeme),
+                "lbu" | "lhu" | "lwl" | "lwr" | "swl" | "swr" | "ll" | "sc" => self.parse_i_type(&lexeme),
+                "bgez" | "bgtz" | "blez" | "bltz" | "bgezal" | "bltzal" => self.parse_i_type(&lexeme),
                 "move" | "la" | "li" | "blt" | "bgt" | "ble" | "bge" => self.parse_pseudo_instruction(&lexeme),
+                "bltu" | "bgtu" | "bleu" | "bgeu" | "beqz" | "bnez" | "b" | "bal" => self.parse_pseudo_instruction(&lexeme),
+                "rem" | "neg" | "not" | "abs" | "seq" | "sne" | "sge" | "sgt" => self.parse_pseudo_instruction(&lexeme),
+                "mfc0" | "mtc0" | "eret" => self.parse_cop0(&lexeme),
+                "teq" | "tne" | "tge" | "tgeu" | "tlt" | "tltu" | "teqi" | "tnei" | "tgei" | "tgeiu" | "tlti" | "tltiu" | "break" => self.parse_trap(&lexeme),
+                "add.s" | "add.d" | "sub.s" | "sub.d" | "mul.s" | "mul.d" | "div.s" | "div.d" |
+                "abs.s" | "abs.d" | "neg.s" | "neg.d" | "mov.s" | "mov.d" | "sqrt.s" | "sqrt.d" |
+                "cvt.s.d" | "cvt.s.w" | "cvt.d.s" | "cvt.d.w" | "cvt.w.s" | "cvt.w.d" |
+                "c.eq.s" | "c.eq.d" | "c.lt.s" | "c.lt.d" | "c.le.s" | "c.le.d" | "bc1t" | "bc1f" |
+                "lwc1" | "swc1" | "ldc1" | "sdc1" | "mtc1" | "mfc1" => self.parse_cop1(&lexeme),
                 _ => Err(self.error(format!("Line {}: Unknown instruction {}", self.current_line, lexeme)))
             }
         } else {
@@ -366,24 +447,16 @@ impl Parser {
         }
     }
 
-    fn is_valid_register(&self, name: &str) -> bool {
-        matches!(name,
-            "$zero" | "$at" |
-            "$v0" | "$v1" |
-            "$a0" | "$a1" | "$a2" | "$a3" |
-            "$t0" | "$t1" | "$t2" | "$t3" | "$t4" | "$t5" | "$t6" | "$t7" |
-            "$s0" | "$s1" | "$s2" | "$s3" | "$s4" | "$s5" | "$s6" | "$s7" |
-            "$t8" | "$t9" |
-            "$k0" | "$k1" |
-            "$gp" | "$sp" | "$fp" | "$ra"
-        )
-    }
-
-    fn parse_register(&mut self) -> Result<String, EmuError> {
-        let token = self.expect(TokenType::RegisterName)?;
+    /// parses a general purpose register by name or number (`$t0`, `$8`), normalised to its register number
+    fn parse_register(&mut self) -> Result<Register, EmuError> {
+        let token = if self.peek(0).is_some_and(|t| t.token_type == TokenType::RegisterNumber) {
+            self.expect(TokenType::RegisterNumber)?
+        } else {
+            self.expect(TokenType::RegisterName)?
+        };
         
-        if self.is_valid_register(&token.lexeme) {
-            Ok(token.lexeme)
+        if let Some(register) = Register::from_name(&token.lexeme) {
+            Ok(register)
         } else {
             let err_msg = format!("Line {}: Invalid Register {}", self.current_line, token.lexeme);
             
@@ -393,6 +466,54 @@ impl Parser {
         }
     }
 
+    /// parses a coprocessor 0 register written by number (e.g. `$12` for Status)
+    fn parse_cp0_register(&mut self) -> Result<u32, EmuError> {
+        let token = self.expect(TokenType::RegisterNumber)?;
+
+        match token.lexeme[1..].parse::<u32>() {
+            Ok(n) if n < 32 => Ok(n),
+            _ => {
+                let err_msg = format!("Line {}: Invalid CP0 Register {}", self.current_line, token.lexeme);
+
+                self.syntax_error = true;
+                self.syntax_error_message = err_msg.clone();
+                Err(EmuError::InvalidReg(err_msg))
+            }
+        }
+    }
+
+    /// parses a floating-point register (`$f0`-`$f31`), double precision operands must be an even register
+    fn parse_fp_register(&mut self, fmt: FpFormat) -> Result<FpRegister, EmuError> {
+        let token = self.expect(TokenType::RegisterName)?;
+
+        let err_msg = match FpRegister::from_name(&token.lexeme) {
+            Some(register) if fmt == FpFormat::D && register.number() % 2 != 0 => {
+                format!("Line {}: Double precision operand {} must be an even register", self.current_line, token.lexeme)
+            },
+            Some(register) => return Ok(register),
+            None => format!("Line {}: Invalid FP Register {}", self.current_line, token.lexeme),
+        };
+
+        self.syntax_error = true;
+        self.syntax_error_message = err_msg.clone();
+        Err(EmuError::InvalidReg(err_msg))
+    }
+
+    /// parses the optional condition code operand of `c.cond.fmt` and `bc1t`/`bc1f` (defaults to 0)
+    fn parse_condition_code(&mut self) -> Result<u32, EmuError> {
+        if !self.next_is_immediate() {
+            return Ok(0);
+        }
+
+        let cc = self.parse_immediate::<u32>()?;
+        if cc > 7 {
+            return Err(self.error(format!("Line {}: Invalid condition code {}", self.current_line, cc)));
+        }
+
+        self.expect(TokenType::Delimiter)?;
+        Ok(cc)
+    }
+
     fn parse_immediate<T>(&mut self) -> Result<T, EmuError>
     where
         T: TryFrom<i64>,
@@ -404,6 +525,30 @@ impl Parser {
             .map_err(|_| self.error(format!("Line {}: Immediate value out of range {}", self.current_line, token.lexeme)))
     }
 
+    /// parses a `.byte`/`.half`/`.word` value that fits in `bits` as either a signed or unsigned number (e.g. -1 or 0xFF)
+    fn parse_data_value(lexeme: &str, bits: u32) -> Option<i64> {
+        let value = Self::parse_int_literal(lexeme)?;
+        let min = -(1i64 << (bits - 1));
+        let max = (1i64 << bits) - 1;
+
+        (min..=max).contains(&value).then_some(value)
+    }
+
+    /// parses the immediate operand of a branch pseudo-instruction (signed or unsigned 32-bit, like `li`)
+    fn parse_branch_immediate(&mut self) -> Result<u32, EmuError> {
+        let token = self.expect(TokenType::Integer)?;
+
+        Self::parse_int_literal(&token.lexeme)
+            .filter(|v| *v >= i32::MIN as i64 && *v <= u32::MAX as i64)
+            .map(|v| v as u32)
+            .ok_or_else(|| self.error(format!("Line {}: Invalid immediate value {}", self.current_line, token.lexeme)))
+    }
+
+    /// returns true if the next operand is an immediate instead of a register
+    fn next_is_immediate(&self) -> bool {
+        self.peek(0).is_some_and(|t| t.token_type == TokenType::Integer)
+    }
+
     fn parse_int_literal(s: &str) -> Option<i64> {
         let s = s.trim();
         if s.starts_with("0x") || s.starts_with("0X") {
@@ -415,6 +560,30 @@ impl Parser {
         }
     }
     
+    /// converts escape sequences in a quoted string (e.g. `\n`) to the characters they represent
+    fn unescape_string(s: &str) -> String {
+        let mut result = String::with_capacity(s.len());
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('r') => result.push('\r'),
+                Some('0') => result.push('\0'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        }
+
+        result
+    }
+    
     fn parse_label(&mut self) -> Result<String, EmuError> {
         let token = self.next_token().ok_or_else(|| self.error("Expected label".to_string()))?;
         if token.token_type == TokenType::Mnemonic || token.token_type == TokenType::Identifier || token.token_type == TokenType::Directive {
@@ -428,7 +597,7 @@ impl Parser {
         self.expect(TokenType::Mnemonic)?;
 
         match mnemonic {
-            "add" | "sub" | "or" | "addu" | "subu" | "and" | "slt" | "sltu" | "xor" | "nor" => {
+            "add" | "sub" | "or" | "addu" | "subu" | "and" | "slt" | "sltu" | "xor" | "nor" | "movn" | "movz" => {
                 let rd = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
                 let rs = self.parse_register()?;
@@ -446,11 +615,13 @@ impl Parser {
                     "sltu" => Ok(Instruction::Core(CoreInstruction::Sltu { rd, rs, rt })), 
                     "xor"  => Ok(Instruction::Core(CoreInstruction::Xor {rd, rs, rt})),
                     "nor" => Ok(Instruction::Core(CoreInstruction::Nor {rd, rs, rt})),
+                    "movn" => Ok(Instruction::Core(CoreInstruction::Movn { rd, rs, rt })),
+                    "movz" => Ok(Instruction::Core(CoreInstruction::Movz { rd, rs, rt })),
                     _ => unreachable!()
                 }
             },
 
-            "mult" | "multu" => {
+            "mult" | "multu" | "madd" | "maddu" | "msub" | "msubu" => {
                 let rs = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
                 let rt = self.parse_register()?;
@@ -458,6 +629,71 @@ impl Parser {
                 match mnemonic {
                     "mult" => Ok(Instruction::Core(CoreInstruction::Mult { rs, rt })),
                     "multu" => Ok(Instruction::Core(CoreInstruction::Multu { rs, rt })),
+                    "madd" => Ok(Instruction::Core(CoreInstruction::Madd { rs, rt })),
+                    "maddu" => Ok(Instruction::Core(CoreInstruction::Maddu { rs, rt })),
+                    "msub" => Ok(Instruction::Core(CoreInstruction::Msub { rs, rt })),
+                    "msubu" => Ok(Instruction::Core(CoreInstruction::Msubu { rs, rt })),
+                    _ => unreachable!()
+                }
+            },
+
+            // `mul rd, rs, imm` is a pseudo-instruction that loads the immediate into $at
+            "mul" => {
+                let rd = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rs = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+
+                if self.next_is_immediate() {
+                    let imm = self.parse_branch_immediate()?;
+                    return Ok(Instruction::Pseudo(PseudoInstruction::MulImm { rd, rs, imm }));
+                }
+
+                let rt = self.parse_register()?;
+                Ok(Instruction::Core(CoreInstruction::Mul { rd, rs, rt }))
+            },
+
+            "clz" | "clo" => {
+                let rd = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rs = self.parse_register()?;
+
+                match mnemonic {
+                    "clz" => Ok(Instruction::Core(CoreInstruction::Clz { rd, rs })),
+                    "clo" => Ok(Instruction::Core(CoreInstruction::Clo { rd, rs })),
+                    _ => unreachable!()
+                }
+            },
+
+            "seb" | "seh" => {
+                let rd = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rt = self.parse_register()?;
+
+                match mnemonic {
+                    "seb" => Ok(Instruction::Core(CoreInstruction::Seb { rd, rt })),
+                    "seh" => Ok(Instruction::Core(CoreInstruction::Seh { rd, rt })),
+                    _ => unreachable!()
+                }
+            },
+
+            // ext/ins rt, rs, pos, size
+            "ext" | "ins" => {
+                let rt = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rs = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let pos = self.parse_immediate::<u32>()?;
+                self.expect(TokenType::Delimiter)?;
+                let size = self.parse_immediate::<u32>()?;
+
+                if pos >= 32 || size == 0 || pos + size > 32 {
+                    return Err(self.error(format!("Line {}: Invalid bit field position {} and size {}", self.current_line, pos, size)));
+                }
+
+                match mnemonic {
+                    "ext" => Ok(Instruction::Core(CoreInstruction::Ext { rt, rs, pos, size })),
+                    "ins" => Ok(Instruction::Core(CoreInstruction::Ins { rt, rs, pos, size })),
                     _ => unreachable!()
                 }
             },
@@ -503,10 +739,211 @@ impl Parser {
                 }
             },
 
+            // variable shifts take the shift amount from a register: sllv rd, rt, rs
+            "sllv" | "srlv" | "srav" => {
+                let rd = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rt = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rs = self.parse_register()?;
+
+                match mnemonic {
+                    "sllv" => Ok(Instruction::Core(CoreInstruction::Sllv { rd, rt, rs })),
+                    "srlv" => Ok(Instruction::Core(CoreInstruction::Srlv { rd, rt, rs })),
+                    "srav" => Ok(Instruction::Core(CoreInstruction::Srav { rd, rt, rs })),
+                    _ => unreachable!()
+                }
+            },
+
+            "syscall" => Ok(Instruction::Core(CoreInstruction::Syscall)),
+
+            // encoded as sll $zero, $zero, 0 (used to fill branch delay slots)
+            "nop" => Ok(Instruction::Core(CoreInstruction::Sll { rd: Register::ZERO, rt: Register::ZERO, sa: 0 })),
+            "sync" => Ok(Instruction::Core(CoreInstruction::Sync)),
+
             _ => Err(self.error(format!("Line {}: Unknown R-Type instruction {}", self.current_line, mnemonic)))
         }
     }
 
+    pub fn parse_cop0(&mut self, mnemonic: &str) -> Result<Instruction, EmuError> {
+        self.expect(TokenType::Mnemonic)?;
+
+        match mnemonic {
+            "mfc0" | "mtc0" => {
+                let rt = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let rd = self.parse_cp0_register()?;
+
+                match mnemonic {
+                    "mfc0" => Ok(Instruction::Core(CoreInstruction::Mfc0 { rt, rd })),
+                    "mtc0" => Ok(Instruction::Core(CoreInstruction::Mtc0 { rt, rd })),
+                    _ => unreachable!()
+                }
+            },
+
+            "eret" => Ok(Instruction::Core(CoreInstruction::Eret)),
+
+            _ => Err(self.error(format!("Line {}: Unknown coprocessor 0 instruction {}", self.current_line, mnemonic)))
+        }
+    }
+
+    /// parses `break [code]`, the register traps (`teq rs, rt[, code]`) and the immediate traps (`teqi rs, imm`)
+    pub fn parse_trap(&mut self, mnemonic: &str) -> Result<Instruction, EmuError> {
+        self.expect(TokenType::Mnemonic)?;
+
+        if mnemonic == "break" {
+            let code = if self.next_is_immediate() { self.parse_immediate::<u32>()? } else { 0 };
+            return Ok(Instruction::Core(CoreInstruction::Break { code }));
+        }
+
+        // the immediate forms insert an `i` before the optional `u` (tgeu -> tgeiu)
+        let immediate = mnemonic.ends_with('i') || mnemonic.ends_with("iu");
+        let cond = match mnemonic.replace('i', "").as_str() {
+            "teq" => TrapCondition::Eq,
+            "tne" => TrapCondition::Ne,
+            "tge" => TrapCondition::Ge,
+            "tgeu" => TrapCondition::Geu,
+            "tlt" => TrapCondition::Lt,
+            "tltu" => TrapCondition::Ltu,
+            _ => return Err(self.error(format!("Line {}: Unknown trap instruction {}", self.current_line, mnemonic)))
+        };
+
+        let rs = self.parse_register()?;
+        self.expect(TokenType::Delimiter)?;
+
+        if immediate {
+            let imm = self.parse_immediate::<i32>()?;
+            return Ok(Instruction::Core(CoreInstruction::TrapImm { cond, rs, imm }));
+        }
+
+        let rt = self.parse_register()?;
+        let code = if self.peek(0).is_some() {
+            self.expect(TokenType::Delimiter)?;
+            self.parse_immediate::<u32>()?
+        } else {
+            0
+        };
+
+        Ok(Instruction::Core(CoreInstruction::Trap { cond, rs, rt, code }))
+    }
+
+    pub fn parse_cop1(&mut self, mnemonic: &str) -> Result<Instruction, EmuError> {
+        self.expect(TokenType::Mnemonic)?;
+
+        // the lexer only accepts valid suffixes (e.g. `cvt.s.w`), so every part is a known format
+        let format = |suffix: &str| match suffix {
+            "s" => FpFormat::S,
+            "d" => FpFormat::D,
+            _ => FpFormat::W,
+        };
+
+        let parts: Vec<&str> = mnemonic.split('.').collect();
+
+        match parts.as_slice() {
+            [op @ ("add" | "sub" | "mul" | "div"), suffix] => {
+                let fmt = format(suffix);
+                let fd = self.parse_fp_register(fmt)?;
+                self.expect(TokenType::Delimiter)?;
+                let fs = self.parse_fp_register(fmt)?;
+                self.expect(TokenType::Delimiter)?;
+                let ft = self.parse_fp_register(fmt)?;
+
+                match *op {
+                    "add" => Ok(Instruction::Core(CoreInstruction::AddF { fmt, fd, fs, ft })),
+                    "sub" => Ok(Instruction::Core(CoreInstruction::SubF { fmt, fd, fs, ft })),
+                    "mul" => Ok(Instruction::Core(CoreInstruction::MulF { fmt, fd, fs, ft })),
+                    "div" => Ok(Instruction::Core(CoreInstruction::DivF { fmt, fd, fs, ft })),
+                    _ => unreachable!()
+                }
+            },
+
+            [op @ ("sqrt" | "abs" | "neg" | "mov"), suffix] => {
+                let fmt = format(suffix);
+                let fd = self.parse_fp_register(fmt)?;
+                self.expect(TokenType::Delimiter)?;
+                let fs = self.parse_fp_register(fmt)?;
+
+                match *op {
+                    "sqrt" => Ok(Instruction::Core(CoreInstruction::SqrtF { fmt, fd, fs })),
+                    "abs" => Ok(Instruction::Core(CoreInstruction::AbsF { fmt, fd, fs })),
+                    "neg" => Ok(Instruction::Core(CoreInstruction::NegF { fmt, fd, fs })),
+                    "mov" => Ok(Instruction::Core(CoreInstruction::MovF { fmt, fd, fs })),
+                    _ => unreachable!()
+                }
+            },
+
+            ["cvt", to, from] => {
+                let (to, from) = (format(to), format(from));
+                let fd = self.parse_fp_register(to)?;
+                self.expect(TokenType::Delimiter)?;
+                let fs = self.parse_fp_register(from)?;
+
+                Ok(Instruction::Core(CoreInstruction::Cvt { to, from, fd, fs }))
+            },
+
+            // c.cond.fmt [cc,] fs, ft
+            ["c", cond, suffix] => {
+                let fmt = format(suffix);
+                let cond = match *cond {
+                    "eq" => FpCondition::Eq,
+                    "lt" => FpCondition::Lt,
+                    _ => FpCondition::Le,
+                };
+
+                let cc = self.parse_condition_code()?;
+                let fs = self.parse_fp_register(fmt)?;
+                self.expect(TokenType::Delimiter)?;
+                let ft = self.parse_fp_register(fmt)?;
+
+                Ok(Instruction::Core(CoreInstruction::CompareF { cond, fmt, cc, fs, ft }))
+            },
+
+            // bc1t/bc1f [cc,] label
+            ["bc1t" | "bc1f"] => {
+                let cc = self.parse_condition_code()?;
+                let label = self.parse_label()?;
+
+                if mnemonic == "bc1t" {
+                    Ok(Instruction::Core(CoreInstruction::Bc1t { cc, label }))
+                } else {
+                    Ok(Instruction::Core(CoreInstruction::Bc1f { cc, label }))
+                }
+            },
+
+            ["lwc1" | "swc1" | "ldc1" | "sdc1"] => {
+                let fmt = if matches!(mnemonic, "ldc1" | "sdc1") { FpFormat::D } else { FpFormat::S };
+                let ft = self.parse_fp_register(fmt)?;
+                self.expect(TokenType::Delimiter)?;
+                let imm = self.parse_immediate::<i32>()?;
+                self.expect(TokenType::LeftParen)?;
+                let rs = self.parse_register()?;
+                self.expect(TokenType::RightParen)?;
+
+                match mnemonic {
+                    "lwc1" => Ok(Instruction::Core(CoreInstruction::Lwc1 { ft, rs, imm })),
+                    "swc1" => Ok(Instruction::Core(CoreInstruction::Swc1 { ft, rs, imm })),
+                    "ldc1" => Ok(Instruction::Core(CoreInstruction::Ldc1 { ft, rs, imm })),
+                    "sdc1" => Ok(Instruction::Core(CoreInstruction::Sdc1 { ft, rs, imm })),
+                    _ => unreachable!()
+                }
+            },
+
+            ["mtc1" | "mfc1"] => {
+                let rt = self.parse_register()?;
+                self.expect(TokenType::Delimiter)?;
+                let fs = self.parse_fp_register(FpFormat::S)?;
+
+                if mnemonic == "mtc1" {
+                    Ok(Instruction::Core(CoreInstruction::Mtc1 { rt, fs }))
+                } else {
+                    Ok(Instruction::Core(CoreInstruction::Mfc1 { rt, fs }))
+                }
+            },
+
+            _ => Err(self.error(format!("Line {}: Unknown coprocessor 1 instruction {}", self.current_line, mnemonic)))
+        }
+    }
+
     pub fn parse_i_type(&mut self, mnemonic: &str) -> Result<Instruction, EmuError> {
         self.expect(TokenType::Mnemonic)?;
 
@@ -518,7 +955,7 @@ impl Parser {
             return Ok(Instruction::Core(CoreInstruction::Lui { rt, imm }));
         }
 
-        if mnemonic == "lw" || mnemonic == "sw" || mnemonic == "lb" || mnemonic == "lh" || mnemonic == "sb" || mnemonic == "sh" {
+        if matches!(mnemonic, "lw" | "sw" | "lb" | "lh" | "sb" | "sh" | "lbu" | "lhu" | "lwl" | "lwr" | "swl" | "swr" | "ll" | "sc") {
             let rt = self.parse_register()?;
             self.expect(TokenType::Delimiter)?;
 
@@ -537,6 +974,14 @@ impl Parser {
                         "sb" => Ok(Instruction::Core(CoreInstruction::Sb { rt, rs, imm })),
                         "lh" => Ok(Instruction::Core(CoreInstruction::Lh { rt, rs, imm })),
                         "sh" => Ok(Instruction::Core(CoreInstruction::Sh { rt, rs, imm })),
+                        "lbu" => Ok(Instruction::Core(CoreInstruction::Lbu { rt, rs, imm })),
+                        "lhu" => Ok(Instruction::Core(CoreInstruction::Lhu { rt, rs, imm })),
+                        "lwl" => Ok(Instruction::Core(CoreInstruction::Lwl { rt, rs, imm })),
+                        "lwr" => Ok(Instruction::Core(CoreInstruction::Lwr { rt, rs, imm })),
+                        "swl" => Ok(Instruction::Core(CoreInstruction::Swl { rt, rs, imm })),
+                        "swr" => Ok(Instruction::Core(CoreInstruction::Swr { rt, rs, imm })),
+                        "ll" => Ok(Instruction::Core(CoreInstruction::Ll { rt, rs, imm })),
+                        "sc" => Ok(Instruction::Core(CoreInstruction::Sc { rt, rs, imm })),
                         _ => Err(self.error(format!("At line {}: Unexpected token {:?}", self.current_line, mnemonic))),
                     }
                 } else if token.token_type == TokenType::Identifier {
@@ -555,6 +1000,15 @@ impl Parser {
         } else if mnemonic == "beq" || mnemonic == "bne" {
             let rs = self.parse_register()?;
             self.expect(TokenType::Delimiter)?;
+
+            // `beq $t0, 5, label` compares against an immediate loaded into $at
+            if self.next_is_immediate() {
+                let imm = self.parse_branch_immediate()?;
+                self.expect(TokenType::Delimiter)?;
+                let label = self.parse_label()?;
+                return Ok(Instruction::Pseudo(PseudoInstruction::BranchImm { op: mnemonic.to_string(), rs, imm, label }));
+            }
+
             let rt = self.parse_register()?;
             self.expect(TokenType::Delimiter)?;
             let label = self.parse_label()?;
@@ -564,6 +1018,20 @@ impl Parser {
             } else {
                 Ok(Instruction::Core(CoreInstruction::Bne { rs, rt, label }))
             }
+        } else if matches!(mnemonic, "bgez" | "bgtz" | "blez" | "bltz" | "bgezal" | "bltzal") {
+            let rs = self.parse_register()?;
+            self.expect(TokenType::Delimiter)?;
+            let label = self.parse_label()?;
+
+            match mnemonic {
+                "bgez" => Ok(Instruction::Core(CoreInstruction::Bgez { rs, label })),
+                "bgtz" => Ok(Instruction::Core(CoreInstruction::Bgtz { rs, label })),
+                "blez" => Ok(Instruction::Core(CoreInstruction::Blez { rs, label })),
+                "bltz" => Ok(Instruction::Core(CoreInstruction::Bltz { rs, label })),
+                "bgezal" => Ok(Instruction::Core(CoreInstruction::Bgezal { rs, label })),
+                "bltzal" => Ok(Instruction::Core(CoreInstruction::Bltzal { rs, label })),
+                _ => unreachable!()
+            }
         } else {
             let rt = self.parse_register()?;
             self.expect(TokenType::Delimiter)?;
@@ -607,6 +1075,19 @@ impl Parser {
             "j" => Ok(Instruction::Core(CoreInstruction::J { label: self.parse_label()? })),
             "jal" => Ok(Instruction::Core(CoreInstruction::Jal { label: self.parse_label()? })),
             "jr" => Ok(Instruction::Core(CoreInstruction::Jr { rs: self.parse_register()? })),
+
+            // `jalr $rs` links in $ra, `jalr $rd, $rs` links in $rd
+            "jalr" => {
+                let first = self.parse_register()?;
+
+                if self.peek(0).is_some_and(|t| t.token_type == TokenType::Delimiter) {
+                    self.expect(TokenType::Delimiter)?;
+                    let rs = self.parse_register()?;
+                    Ok(Instruction::Core(CoreInstruction::Jalr { rd: first, rs }))
+                } else {
+                    Ok(Instruction::Core(CoreInstruction::Jalr { rd: Register::RA, rs: first }))
+                }
+            },
             _ => Err(self.error(format!("Line {}: Unknown J-Type", self.current_line)))
         }
     }
@@ -638,38 +1119,75 @@ impl Parser {
 
                 Ok(Instruction::Pseudo(PseudoInstruction::Li { rd, imm: imm as u32 }))
             },
-            "blt" => {
+            "blt" | "bgt" | "ble" | "bge" | "bltu" | "bgtu" | "bleu" | "bgeu" => {
                 let rs = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
+
+                // `blt $t0, 5, label` compares against an immediate loaded into $at
+                if self.next_is_immediate() {
+                    let imm = self.parse_branch_immediate()?;
+                    self.expect(TokenType::Delimiter)?;
+                    let label = self.parse_label()?;
+                    return Ok(Instruction::Pseudo(PseudoInstruction::BranchImm { op: mnemonic.to_string(), rs, imm, label }));
+                }
+
                 let rt = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
                 let label = self.parse_label()?;
-                Ok(Instruction::Pseudo(PseudoInstruction::Blt { rs, rt, label }))
+
+                match mnemonic {
+                    "blt" => Ok(Instruction::Pseudo(PseudoInstruction::Blt { rs, rt, label })),
+                    "bgt" => Ok(Instruction::Pseudo(PseudoInstruction::Bgt { rs, rt, label })),
+                    "ble" => Ok(Instruction::Pseudo(PseudoInstruction::Ble { rs, rt, label })),
+                    "bge" => Ok(Instruction::Pseudo(PseudoInstruction::Bge { rs, rt, label })),
+                    "bltu" => Ok(Instruction::Pseudo(PseudoInstruction::Bltu { rs, rt, label })),
+                    "bgtu" => Ok(Instruction::Pseudo(PseudoInstruction::Bgtu { rs, rt, label })),
+                    "bleu" => Ok(Instruction::Pseudo(PseudoInstruction::Bleu { rs, rt, label })),
+                    "bgeu" => Ok(Instruction::Pseudo(PseudoInstruction::Bgeu { rs, rt, label })),
+                    _ => unreachable!()
+                }
             },
-            "bgt" => {
+            "beqz" | "bnez" => {
                 let rs = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
-                let rt = self.parse_register()?;
-                self.expect(TokenType::Delimiter)?;
                 let label = self.parse_label()?;
-                Ok(Instruction::Pseudo(PseudoInstruction::Bgt { rs, rt, label }))
+
+                if mnemonic == "beqz" {
+                    Ok(Instruction::Pseudo(PseudoInstruction::Beqz { rs, label }))
+                } else {
+                    Ok(Instruction::Pseudo(PseudoInstruction::Bnez { rs, label }))
+                }
             },
-            "ble" => {
-                let rs = self.parse_register()?;
-                self.expect(TokenType::Delimiter)?;
-                let rt = self.parse_register()?;
+            "rem" | "seq" | "sne" | "sge" | "sgt" => {
+                let rd = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
-                let label = self.parse_label()?;
-                Ok(Instruction::Pseudo(PseudoInstruction::Ble { rs, rt, label }))
-            },
-            "bge" => {
                 let rs = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
                 let rt = self.parse_register()?;
+
+                match mnemonic {
+                    "rem" => Ok(Instruction::Pseudo(PseudoInstruction::Rem { rd, rs, rt })),
+                    "seq" => Ok(Instruction::Pseudo(PseudoInstruction::Seq { rd, rs, rt })),
+                    "sne" => Ok(Instruction::Pseudo(PseudoInstruction::Sne { rd, rs, rt })),
+                    "sge" => Ok(Instruction::Pseudo(PseudoInstruction::Sge { rd, rs, rt })),
+                    "sgt" => Ok(Instruction::Pseudo(PseudoInstruction::Sgt { rd, rs, rt })),
+                    _ => unreachable!()
+                }
+            },
+            "neg" | "not" | "abs" => {
+                let rd = self.parse_register()?;
                 self.expect(TokenType::Delimiter)?;
-                let label = self.parse_label()?;
-                Ok(Instruction::Pseudo(PseudoInstruction::Bge { rs, rt, label }))
+                let rs = self.parse_register()?;
+
+                match mnemonic {
+                    "neg" => Ok(Instruction::Pseudo(PseudoInstruction::Neg { rd, rs })),
+                    "not" => Ok(Instruction::Pseudo(PseudoInstruction::Not { rd, rs })),
+                    "abs" => Ok(Instruction::Pseudo(PseudoInstruction::Abs { rd, rs })),
+                    _ => unreachable!()
+                }
             },
+            "b" => Ok(Instruction::Pseudo(PseudoInstruction::B { label: self.parse_label()? })),
+            "bal" => Ok(Instruction::Pseudo(PseudoInstruction::Bal { label: self.parse_label()? })),
             _ => Err(self.error(format!("Line {}: Unknown pseudo-instruction", self.current_line)))
         }
     }
@@ -725,5 +1243,9 @@ impl Parser {
         self.tokens.clear();
         self.current_line = 0;
         self.instruction_index = 0;
+
+        self.kernel_text_base = crate::memory::EXCEPTION_HANDLER_ADDRESS;
+        self.in_kernel_text = false;
+        self.kernel_text_used = false;
     }
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use crate::instruction::CoreInstruction;
 use crate::memory::*;
 use crate::assembler::Assembler;
 use crate::memory::Memory;
+use crate::segments::FaultKind;
 
 /// enum used to indicate a runtime emulation error (e.g. parsing error)
 #[derive(Debug, Clone)]
@@ -39,7 +40,41 @@ pub enum EmuError {
     Breakpoint,
 
     // when dividing by zero
-    DivideByZero  
+    DivideByZero,
+
+    /// indicates an unsupported service number in $v0 for `syscall`
+    InvalidSyscall(u32),
+
+    /// indicates a read syscall with no console input available (the IDE should prompt and resume)
+    InputRequired,
+
+    /// indicates console input that could not be parsed by a read syscall
+    InvalidInput(String),
+
+    /// indicates a machine word that does not decode to a supported instruction
+    InvalidInstruction(u32),
+
+    /// indicates signed overflow in `add`, `addi` or `sub` (PC and source line of the instruction)
+    ArithmeticOverflow { pc: u32, line: usize },
+
+    /// indicates a conditional trap whose condition held (instruction text, its code, PC and source line)
+    Trap { insn: String, code: u32, pc: u32, line: usize },
+
+    /// indicates a `break` instruction (its code, PC and source line)
+    BreakInstruction { code: u32, pc: u32, line: usize },
+
+    /// indicates an access outside every segment or one its segment does not permit (address, PC and reason)
+    SegmentationFault { addr: u32, pc: u32, kind: FaultKind },
+
+    /// indicates an `sbrk` the heap cannot satisfy (bytes requested, bytes the break could still move that way, PC and source line)
+    OutOfMemory { requested: i32, available: u32, pc: u32, line: usize },
+
+    /// indicates a read of a register or memory location the program never wrote (register name or address, PC and source line)
+    UninitializedRead { location: String, pc: u32, line: usize },
+
+    /// indicates a watched register or memory range was accessed by the instruction that just ran
+    /// (watchpoint id, the accessed address or the register number, and the value before and after)
+    Watchpoint { id: usize, addr: u32, old: u32, new: u32 }
 }
 
 /// structure used to hold a list of Instructions
@@ -52,7 +87,16 @@ pub struct Program {
     pub symbol_table: HashMap<String, u32>,
 
     // list of line numbers 
-    pub line_numbers: Vec<usize>
+    pub line_numbers: Vec<usize>,
+
+    /// index of the first kernel text (`.ktext`) instruction
+    pub kernel_start: usize,
+
+    /// address of the first kernel text instruction
+    pub kernel_base: u32,
+
+    /// text of each source line (errors quote instructions as they were written, not their expansion)
+    pub source_lines: Vec<String>
 }  
 
 impl Program {
@@ -64,7 +108,10 @@ impl Program {
                 Ok(Program {
                     core_instructions,
                     symbol_table,
-                    line_numbers
+                    line_numbers,
+                    kernel_start: assembler.kernel_start,
+                    kernel_base: assembler.parser.kernel_text_base,
+                    source_lines: src.lines().map(String::from).collect()
                 })
             },
             Err(e) => {
@@ -73,25 +120,52 @@ impl Program {
         }
     }
 
+    /// instruction on a source line (counted from 1) without its labels, comment and extra spaces
+    pub fn source_instruction(&self, line: usize) -> Option<String> {
+        let text = self.source_lines.get(line.checked_sub(1)?)?;
+        let mut code = text.split('#').next().unwrap_or_default().trim();
+
+        while let Some((label, rest)) = code.split_once(':')
+            && !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+            code = rest.trim();
+        }
+
+        (!code.is_empty()).then(|| code.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
     /// get the line number for a label 
+    /// decoded instructions use the hex address (e.g. "0x00400010") for targets without a label
     pub fn get_label_address(&self, label: &str) -> Option<u32> {
-        self.symbol_table.get(label).copied()
+        self.symbol_table.get(label).copied().or_else(|| {
+            label.strip_prefix("0x").and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        })
     }
 
     /// convert $pc to an index to an instruction in the instruction array 
+    /// kernel text instructions are stored after the user instructions
     pub fn pc_to_index(&self, pc: u32) -> Option<usize> {
-        if pc < DEFAULT_TEXT_BASE_ADDRESS {
+        let kernel_len = self.core_instructions.len() - self.kernel_start;
+
+        if let Some(index) = Self::segment_index(pc, self.kernel_base, kernel_len) {
+            return Some(self.kernel_start + index);
+        }
+        Self::segment_index(pc, DEFAULT_TEXT_BASE_ADDRESS, self.kernel_start)
+    }
+
+    /// index of `pc` within a text segment of `len` instructions starting at `base`
+    fn segment_index(pc: u32, base: u32, len: usize) -> Option<usize> {
+        if pc < base {
             return None;
         }
 
         // check alignment since $pc is 4-byte aligned
-        let offset: u32 = pc - DEFAULT_TEXT_BASE_ADDRESS;
+        let offset: u32 = pc - base;
         if offset % 4 != 0 {
             return None; 
         }
 
         let index: usize = (offset / 4) as usize;
-        if index < self.core_instructions.len() {
+        if index < len {
             Some(index)
         } else {
             None
@@ -100,6 +174,9 @@ impl Program {
 
     /// get the $pc for an index in the instruction array 
     pub fn index_to_pc(&self, index: usize) -> u32 {
+        if index >= self.kernel_start {
+            return self.kernel_base + ((index - self.kernel_start) as u32 * 4);
+        }
         DEFAULT_TEXT_BASE_ADDRESS + (index as u32 * 4)
     }
 }
\ No newline at end of file
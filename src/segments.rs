@@ -0,0 +1,227 @@
+use std::fmt;
+use serde::Serialize;
+use crate::backend::{DATA_SEGMENT_SIZE, STACK_SEGMENT_SIZE};
+use crate::memory::{
+    DEFAULT_HEAP_BASE_ADDRESS, DEFAULT_STACK_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS,
+    DEFAULT_TEXT_BASE_ADDRESS, MMIO_START,
+};
+
+/// start of the kernel data segment (`.kdata` in MARS), which ends where MMIO begins
+pub const KERNEL_DATA_BASE_ADDRESS: u32 = 0x9000_0000;
+pub const KERNEL_TEXT_BASE_ADDRESS: u32 = 0x8000_0000;
+
+/// kind of memory access checked against a segment's permissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Load,
+    Store,
+    Fetch,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Load => write!(f, "load from"),
+            Access::Store => write!(f, "store to"),
+            Access::Fetch => write!(f, "fetch from"),
+        }
+    }
+}
+
+/// accesses a segment allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Permissions {
+    pub const NONE: Permissions = Permissions { read: false, write: false, execute: false };
+    pub const READ_WRITE: Permissions = Permissions { read: true, write: true, execute: false };
+    pub const READ_EXECUTE: Permissions = Permissions { read: true, write: false, execute: true };
+    pub const ALL: Permissions = Permissions { read: true, write: true, execute: true };
+
+    /// parses `ls -l` style flags such as "rwx", "r-x" or "rw-" (a dash or a missing letter denies the access)
+    pub fn from_flags(flags: &str) -> Option<Self> {
+        let mut permissions = Permissions::NONE;
+        for c in flags.chars() {
+            match c {
+                'r' => permissions.read = true,
+                'w' => permissions.write = true,
+                'x' => permissions.execute = true,
+                '-' => {},
+                _ => return None,
+            }
+        }
+        Some(permissions)
+    }
+
+    pub fn allows(&self, access: Access) -> bool {
+        match access {
+            Access::Load => self.read,
+            Access::Store => self.write,
+            Access::Fetch => self.execute,
+        }
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |allowed: bool, c: char| if allowed { c } else { '-' };
+        write!(f, "{}{}{}", flag(self.read, 'r'), flag(self.write, 'w'), flag(self.execute, 'x'))
+    }
+}
+
+/// why an access raised a segmentation fault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultKind {
+    /// the address is outside every segment
+    Unmapped(Access),
+
+    /// the segment does not permit the access
+    Protected { access: Access, segment: String },
+
+    /// the address is in the heap but above the program break (only checked when heap checking is on)
+    Unallocated(Access),
+}
+
+impl FaultKind {
+    pub fn access(&self) -> Access {
+        match self {
+            FaultKind::Unmapped(access) | FaultKind::Unallocated(access) => *access,
+            FaultKind::Protected { access, .. } => *access,
+        }
+    }
+}
+
+impl fmt::Display for FaultKind {
+    /// e.g. "store to read-only text" or "load from unmapped address"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaultKind::Unmapped(access) => write!(f, "{} unmapped address", access),
+            FaultKind::Unallocated(access) => write!(f, "{} unallocated heap memory", access),
+            FaultKind::Protected { access, segment } => {
+                let protection = match access {
+                    Access::Load => "unreadable",
+                    Access::Store => "read-only",
+                    Access::Fetch => "non-executable",
+                };
+                write!(f, "{} {} {}", access, protection, segment)
+            },
+        }
+    }
+}
+
+/// a named range of the address space (`last` is the final address, so a segment can end at 0xFFFFFFFF)
+#[derive(Debug, Clone, Serialize)]
+pub struct MemorySegment {
+    pub name: String,
+    pub start: u32,
+    pub last: u32,
+    pub permissions: Permissions,
+}
+
+impl MemorySegment {
+    pub fn new(name: &str, start: u32, last: u32, permissions: Permissions) -> Self {
+        MemorySegment { name: name.to_string(), start, last, permissions }
+    }
+
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.start && address <= self.last
+    }
+}
+
+/// the segments a program may touch, checked by the CPU on every load, store and fetch from memory
+#[derive(Debug, Clone)]
+pub struct SegmentMap {
+    pub segments: Vec<MemorySegment>,
+}
+
+impl Default for SegmentMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SegmentMap {
+    /// the MARS layout: user text, static data, heap and stack, kernel text and data, then MMIO
+    pub fn new() -> Self {
+        let data_end = DEFAULT_STATIC_DATA_BASE_ADDRESS + DATA_SEGMENT_SIZE;
+        let stack_start = DEFAULT_STACK_BASE_ADDRESS - (STACK_SEGMENT_SIZE - 1);
+
+        SegmentMap {
+            segments: vec![
+                MemorySegment::new("text", DEFAULT_TEXT_BASE_ADDRESS, DEFAULT_STATIC_DATA_BASE_ADDRESS - 1, Permissions::READ_EXECUTE),
+                MemorySegment::new("data", DEFAULT_STATIC_DATA_BASE_ADDRESS, DEFAULT_HEAP_BASE_ADDRESS - 1, Permissions::READ_WRITE),
+                MemorySegment::new("heap", DEFAULT_HEAP_BASE_ADDRESS, data_end - 1, Permissions::READ_WRITE),
+                MemorySegment::new("stack", stack_start, DEFAULT_STACK_BASE_ADDRESS, Permissions::READ_WRITE),
+                MemorySegment::new("kernel text", KERNEL_TEXT_BASE_ADDRESS, KERNEL_DATA_BASE_ADDRESS - 1, Permissions::READ_EXECUTE),
+                MemorySegment::new("kernel data", KERNEL_DATA_BASE_ADDRESS, MMIO_START - 1, Permissions::READ_WRITE),
+                MemorySegment::new("mmio", MMIO_START, u32::MAX, Permissions::READ_WRITE),
+            ],
+        }
+    }
+
+    /// returns the segment holding `address`
+    pub fn find(&self, address: u32) -> Option<&MemorySegment> {
+        self.segments.iter().find(|s| s.contains(address))
+    }
+
+    /// returns the segment called `name`
+    pub fn get(&self, name: &str) -> Option<&MemorySegment> {
+        self.segments.iter().find(|s| s.name == name)
+    }
+
+    /// moves the last address of the named segment (returns false if there is no such segment)
+    pub fn resize(&mut self, name: &str, last: u32) -> bool {
+        match self.segments.iter_mut().find(|s| s.name == name) {
+            Some(segment) => {
+                segment.last = last.max(segment.start);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// moves the named segment to `start..=last` (returns false if there is no such segment)
+    pub fn set_range(&mut self, name: &str, start: u32, last: u32) -> bool {
+        match self.segments.iter_mut().find(|s| s.name == name) {
+            Some(segment) => {
+                segment.start = start;
+                segment.last = last.max(start);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// changes the permissions of the named segment (returns false if there is no such segment)
+    pub fn set_permissions(&mut self, name: &str, permissions: Permissions) -> bool {
+        match self.segments.iter_mut().find(|s| s.name == name) {
+            Some(segment) => {
+                segment.permissions = permissions;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// checks an access of `len` bytes at `address` (every segment the bytes fall in has to allow it)
+    pub fn check(&self, address: u32, len: u32, access: Access) -> Result<(), FaultKind> {
+        let last = address.saturating_add(len.max(1) - 1);
+        let mut addr = address;
+
+        loop {
+            let segment = self.find(addr).ok_or(FaultKind::Unmapped(access))?;
+            if !segment.permissions.allows(access) {
+                return Err(FaultKind::Protected { access, segment: segment.name.clone() });
+            }
+
+            if segment.last >= last {
+                return Ok(());
+            }
+            addr = segment.last + 1;
+        }
+    }
+}
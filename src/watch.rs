@@ -0,0 +1,100 @@
+use crate::instruction::Register;
+
+/// memory accesses a watchpoint stops on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    Both,
+}
+
+impl WatchAccess {
+    /// parses the IDE setting ("read", "write" or "both")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "read" => Some(WatchAccess::Read),
+            "write" => Some(WatchAccess::Write),
+            "both" => Some(WatchAccess::Both),
+            _ => None,
+        }
+    }
+}
+
+/// comparison of a watched value against a constant (signed, like `slt`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// parses "==", "!=", "<", "<=", ">" or ">="
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            _ => None,
+        }
+    }
+
+    pub fn holds(&self, left: i32, right: i32) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+/// what a watchpoint observes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    /// loads and/or stores touching any of the `len` bytes from `start`
+    Memory { start: u32, len: u32, access: WatchAccess },
+
+    /// writes that change a general-purpose register
+    Register(Register),
+}
+
+/// stops `run`/`step` with `EmuError::Watchpoint` after an instruction touches its target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// identifies the watchpoint in `EmuError::Watchpoint` and for removing it
+    pub id: usize,
+    pub target: WatchTarget,
+
+    /// only stop once the new value satisfies the comparison (e.g. `$s0 > 10`)
+    pub condition: Option<(Comparison, i32)>,
+}
+
+impl Watchpoint {
+    /// returns true if a memory access of `len` bytes at `address` is one this watchpoint observes
+    pub fn observes(&self, address: u32, len: u32, write: bool) -> bool {
+        let WatchTarget::Memory { start, len: size, access } = self.target else {
+            return false;
+        };
+
+        let overlaps = address as u64 + len.max(1) as u64 > start as u64 && (address as u64) < start as u64 + size as u64;
+        overlaps && match access {
+            WatchAccess::Read => !write,
+            WatchAccess::Write => write,
+            WatchAccess::Both => true,
+        }
+    }
+
+    /// returns true if `value` satisfies the condition (always without one)
+    pub fn accepts(&self, value: u32) -> bool {
+        self.condition.is_none_or(|(comparison, constant)| comparison.holds(value as i32, constant))
+    }
+}